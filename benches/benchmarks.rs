@@ -1,4 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use poetic::{instruction::Instruction, interpreter::Interpreter};
 use split_digits::SplitDigitIterator;
 use std::collections::VecDeque;
 
@@ -105,5 +106,47 @@ fn benchmark_transform_char(c: &mut Criterion) {
     group.finish()
 }
 
-criterion_group!(benches, benchmark_split_digits, benchmark_transform_char);
+/// Two nested counting loops, running about 300000 instructions.
+fn nested_loops() -> Vec<Instruction> {
+    vec![
+        Instruction::INC(200),
+        Instruction::IF,
+        Instruction::FWD(1),
+        Instruction::INC(255),
+        Instruction::IF,
+        Instruction::FWD(1),
+        Instruction::INC(1),
+        Instruction::BAK(1),
+        Instruction::DEC(1),
+        Instruction::EIF,
+        Instruction::BAK(1),
+        Instruction::DEC(1),
+        Instruction::EIF,
+    ]
+}
+
+fn benchmark_run(c: &mut Criterion) {
+    let mut group = c.benchmark_group("run");
+    group.bench_function("interpreter", |b| {
+        b.iter(|| Interpreter::new(black_box(nested_loops())).unwrap().run())
+    });
+    #[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+    group.bench_function("jit", |b| {
+        b.iter(|| {
+            Interpreter::new(black_box(nested_loops()))
+                .unwrap()
+                .run_jit()
+                .unwrap()
+        })
+    });
+
+    group.finish()
+}
+
+criterion_group!(
+    benches,
+    benchmark_split_digits,
+    benchmark_transform_char,
+    benchmark_run
+);
 criterion_main!(benches);
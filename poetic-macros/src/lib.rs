@@ -0,0 +1,187 @@
+//! Macros running the poetic [`Parser`] at compile time, so malformed poems are reported as
+//! compile errors instead of failing at runtime.
+//!
+//! ```text
+//! let hello: &'static [Instruction] = poem!("inc aaaaaaaaa ...");
+//! let program: &'static [Instruction] = include_poem!("poems/hello.ptc");
+//! ```
+
+use poetic::{
+    instruction::Instruction,
+    parser::{ParseError, Parser, ParserOptions},
+};
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use std::{ops::Range, path::Path};
+use syn::{parse_macro_input, LitStr};
+
+/// Parses a poem and expands to its instructions as a `&'static [Instruction]`.
+#[proc_macro]
+pub fn poem(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    let source = literal.value();
+
+    match Parser::parse(&source) {
+        Ok(instructions) => instructions_to_tokens(&instructions),
+        Err(error) => {
+            let word = locate_error(&source, &error);
+            let span = word
+                .clone()
+                .and_then(|word| literal_subspan(&literal, word))
+                .unwrap_or_else(|| literal.span());
+            let message = match word {
+                Some(word) => format!("{} at \"{}\"", error, &source[word]),
+                None => error.to_string(),
+            };
+            syn::Error::new(span, message).to_compile_error()
+        }
+    }
+    .into()
+}
+
+/// Reads a poem from a file relative to the crate's `Cargo.toml`, parses it and expands to its
+/// instructions as a `&'static [Instruction]`.
+#[proc_macro]
+pub fn include_poem(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let path = Path::new(&manifest_dir).join(literal.value());
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(error) => {
+            let message = format!("couldn't read {}: {}", path.display(), error);
+            return syn::Error::new(literal.span(), message)
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    match Parser::parse(&source) {
+        Ok(instructions) => {
+            let instructions = instructions_to_tokens(&instructions);
+            let path = path.to_string_lossy();
+            // including the file makes cargo rebuild when the poem changes
+            quote! {
+                {
+                    const _: &str = include_str!(#path);
+                    #instructions
+                }
+            }
+        }
+        Err(error) => {
+            let message = match locate_error(&source, &error) {
+                Some(word) => {
+                    let (line, column) = position(&source, word.start);
+                    format!(
+                        "{} at \"{}\" ({}:{}:{})",
+                        error,
+                        &source[word],
+                        path.display(),
+                        line,
+                        column
+                    )
+                }
+                None => format!("{} ({})", error, path.display()),
+            };
+            syn::Error::new(literal.span(), message).to_compile_error()
+        }
+    }
+    .into()
+}
+
+/// The 1-based line and column of a byte offset, columns counting characters.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = source[..offset].matches('\n').count() + 1;
+    (line, source[line_start..offset].chars().count() + 1)
+}
+
+fn instructions_to_tokens(instructions: &[Instruction]) -> TokenStream2 {
+    let instructions = instructions.iter().map(|instruction| match *instruction {
+        Instruction::END => quote!(::poetic::instruction::Instruction::END),
+        Instruction::IF => quote!(::poetic::instruction::Instruction::IF),
+        Instruction::EIF => quote!(::poetic::instruction::Instruction::EIF),
+        Instruction::INC(n) => quote!(::poetic::instruction::Instruction::INC(#n)),
+        Instruction::DEC(n) => quote!(::poetic::instruction::Instruction::DEC(#n)),
+        Instruction::FWD(n) => quote!(::poetic::instruction::Instruction::FWD(#n)),
+        Instruction::BAK(n) => quote!(::poetic::instruction::Instruction::BAK(#n)),
+        Instruction::OUT => quote!(::poetic::instruction::Instruction::OUT),
+        Instruction::IN => quote!(::poetic::instruction::Instruction::IN),
+        Instruction::RND => quote!(::poetic::instruction::Instruction::RND),
+        Instruction::JIZ(n) => quote!(::poetic::instruction::Instruction::JIZ(#n)),
+        Instruction::JNZ(n) => quote!(::poetic::instruction::Instruction::JNZ(#n)),
+        Instruction::JMP(n) => quote!(::poetic::instruction::Instruction::JMP(#n)),
+        Instruction::SCF(n) => quote!(::poetic::instruction::Instruction::SCF(#n)),
+        Instruction::SCB(n) => quote!(::poetic::instruction::Instruction::SCB(#n)),
+        Instruction::PUT(n) => quote!(::poetic::instruction::Instruction::PUT(#n)),
+        Instruction::SET(n) => quote!(::poetic::instruction::Instruction::SET(#n)),
+    });
+
+    quote! {
+        {
+            const INSTRUCTIONS: &[::poetic::instruction::Instruction] = &[#(#instructions),*];
+            INSTRUCTIONS
+        }
+    }
+}
+
+/// Finds the word responsible for a parse error.
+fn locate_error(source: &str, error: &ParseError) -> Option<Range<usize>> {
+    let options = ParserOptions::default();
+    let words = Parser::word_spans(source, &options);
+
+    // the word every digit of the intermediate representation came from
+    let mut digit_words = Vec::new();
+    for (i, (_, letters)) in words.iter().enumerate() {
+        let digits = options.digits(*letters).map_or(0, |digits| digits.len());
+        digit_words.extend(std::iter::repeat_n(i, digits));
+    }
+
+    let intermediate = Parser::parse_intermediate(source);
+    let word_of_digit = |digit: usize| digit_words.get(digit).map(|w| words[*w].0.clone());
+
+    match error {
+        ParseError::NeedsArgument(_) => words.last().map(|(span, _)| span.clone()),
+        ParseError::MissingIf | ParseError::MissingEif => {
+            let mut open = Vec::new();
+            let mut digit = 0;
+            while digit < intermediate.len() {
+                match intermediate[digit] {
+                    1 => open.push(digit),
+                    2 if open.pop().is_none() && *error == ParseError::MissingIf => {
+                        return word_of_digit(digit);
+                    }
+                    3..=6 => digit += 1,
+                    _ => {}
+                }
+                digit += 1;
+            }
+
+            open.first().and_then(|digit| word_of_digit(*digit))
+        }
+        _ => None,
+    }
+}
+
+/// Points into the literal if the compiler supports it and the literal has no escapes that
+/// would shift the offsets.
+fn literal_subspan(literal: &LitStr, range: Range<usize>) -> Option<Span> {
+    let token = literal.token();
+    let text = token.to_string();
+    if !text.starts_with('"') || text[1..text.len() - 1] != literal.value() {
+        return None;
+    }
+
+    token.subspan(range.start + 1..range.end + 1)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_position_counts_characters() {
+        let source = "inc \u{e7}a\n\u{e7}a ei";
+        assert_eq!(super::position(source, source.rfind("ei").unwrap()), (2, 4));
+        assert_eq!(super::position(source, 0), (1, 1));
+    }
+}
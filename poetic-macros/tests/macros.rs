@@ -0,0 +1,51 @@
+use poetic::{instruction::Instruction, interpreter::Interpreter, parser::Parser};
+use poetic_macros::{include_poem, poem};
+
+const HELLO_WORLD: &str = include_str!("hello_world.ptc");
+
+fn run(instructions: &[Instruction]) -> String {
+    let mut output = String::new();
+    let mut interpreter = Interpreter::new(instructions.to_vec())
+        .unwrap()
+        .with_fixed_size_memory::<100>()
+        .with_output(|s: String| output.push_str(&s));
+    interpreter.run();
+    drop(interpreter);
+
+    output
+}
+
+#[test]
+fn poem_matches_parser() {
+    const INSTRUCTIONS: &[Instruction] = poem!("inc a i i fwd aaaaaaaaaa outputa ei a'b");
+
+    assert_eq!(
+        INSTRUCTIONS,
+        Parser::parse("inc a i i fwd aaaaaaaaaa outputa ei a'b").unwrap()
+    );
+}
+
+#[test]
+fn poem_empty() {
+    let instructions: &[Instruction] = poem!("");
+
+    assert!(instructions.is_empty());
+}
+
+#[test]
+fn include_poem_matches_parser() {
+    let instructions = include_poem!("tests/hello_world.ptc");
+
+    assert_eq!(instructions, Parser::parse(HELLO_WORLD).unwrap());
+}
+
+#[test]
+fn include_poem_runs_hello_world() {
+    assert_eq!(run(include_poem!("tests/hello_world.ptc")), "Hello, World!");
+}
+
+#[test]
+fn invalid_poems_fail_to_compile() {
+    let tests = trybuild::TestCases::new();
+    tests.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,6 @@
+use poetic::instruction::Instruction;
+use poetic_macros::poem;
+
+const INSTRUCTIONS: &[Instruction] = poem!("inc a i outputa");
+
+fn main() {}
@@ -0,0 +1,6 @@
+use poetic::instruction::Instruction;
+use poetic_macros::poem;
+
+const INSTRUCTIONS: &[Instruction] = poem!("i inc a ei outputa ei");
+
+fn main() {}
@@ -0,0 +1,7 @@
+use poetic::instruction::Instruction;
+use poetic_macros::poem;
+
+const INSTRUCTIONS: &[Instruction] = poem!("inc a
+outputa fwd");
+
+fn main() {}
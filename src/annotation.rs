@@ -0,0 +1,316 @@
+//! Explains what every word of a poem encodes: its letter count, the digits
+//! [`Parser::parse_intermediate`](crate::parser::Parser::parse_intermediate) reads from it and
+//! whether each digit is an opcode or the argument of one. [`render`] prints the poem with an
+//! annotation line beneath each verse.
+//!
+//! ```text
+//! inc   aaaaaaaaaaaa fwdfw
+//! 3:INC 12=1,2:EIF   5:FWD?
+//! ```
+//!
+//! Opcodes show their name, arguments only their digit, a `?` marks an opcode missing its
+//! argument and words of 10 or more letters show their letter count before the digits.
+
+use crate::parser::{opcode_name, takes_argument, Parser, ParserOptions};
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// The digit is the opcode of the instruction at the index.
+    Opcode(usize),
+    /// The digit is the argument of the instruction at the index.
+    Argument(usize),
+    /// The digit is an opcode at the end of the poem without the argument it needs.
+    MissingArgument,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Word {
+    /// Byte range of the word in the source, including apostrophes.
+    pub span: Range<usize>,
+    pub letters: usize,
+    /// Indices into [`Annotation::digits`], empty for a word too long for
+    /// [`LongWords::Error`](crate::parser::LongWords::Error).
+    pub digits: Range<usize>,
+}
+
+impl Word {
+    /// Whether the word is longer than 10 letters and split into several digits.
+    pub fn is_split(&self) -> bool {
+        self.digits.len() > 1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Digit {
+    pub value: u8,
+    /// Index into [`Annotation::words`].
+    pub word: usize,
+    pub role: Role,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Operation {
+    pub opcode: u8,
+    /// Index into [`Annotation::digits`] of the opcode.
+    pub digit: usize,
+    /// The matching `EIF` of an `IF` and vice versa.
+    pub matching: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Annotation {
+    pub words: Vec<Word>,
+    pub digits: Vec<Digit>,
+    pub operations: Vec<Operation>,
+}
+
+impl Annotation {
+    pub fn new(source: &str) -> Self {
+        Self::with_options(source, &ParserOptions::default())
+    }
+
+    /// Annotates the words [`Parser::parse_with`] reads from `source` with `options`.
+    pub fn with_options(source: &str, options: &ParserOptions) -> Self {
+        let mut words = Vec::new();
+        let mut digits = Vec::new();
+        for (span, letters) in Parser::word_spans(source, options) {
+            let start = digits.len();
+            // a word too long for the options reads as no digits
+            for value in options.digits(letters).unwrap_or_default() {
+                digits.push(Digit {
+                    value,
+                    word: words.len(),
+                    role: Role::MissingArgument,
+                });
+            }
+            words.push(Word {
+                span,
+                letters,
+                digits: start..digits.len(),
+            });
+        }
+
+        let mut operations = Vec::new();
+        let mut i = 0;
+        while i < digits.len() {
+            let opcode = digits[i].value;
+            if takes_argument(opcode) {
+                if i + 1 == digits.len() {
+                    break;
+                }
+                digits[i + 1].role = Role::Argument(operations.len());
+            }
+
+            digits[i].role = Role::Opcode(operations.len());
+            operations.push(Operation {
+                opcode,
+                digit: i,
+                matching: None,
+            });
+            i += if takes_argument(opcode) { 2 } else { 1 };
+        }
+
+        let mut open = Vec::new();
+        for i in 0..operations.len() {
+            match operations[i].opcode {
+                1 => open.push(i),
+                2 => {
+                    if let Some(start) = open.pop() {
+                        operations[start].matching = Some(i);
+                        operations[i].matching = Some(start);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            words,
+            digits,
+            operations,
+        }
+    }
+
+    /// The span of the word holding the opcode of the operation.
+    pub fn operation_span(&self, operation: usize) -> Range<usize> {
+        let digit = &self.digits[self.operations[operation].digit];
+        self.words[digit.word].span.clone()
+    }
+
+    /// The argument of the operation as the interpreter sees it, `0` read as `10`.
+    pub fn argument(&self, operation: usize) -> Option<u8> {
+        let operation = &self.operations[operation];
+        if !takes_argument(operation.opcode) {
+            return None;
+        }
+
+        match self.digits[operation.digit + 1].value {
+            0 => Some(10),
+            value => Some(value),
+        }
+    }
+
+    /// The index of the word at the byte offset, including its end.
+    pub fn word_at(&self, offset: usize) -> Option<usize> {
+        let i = self.words.partition_point(|word| word.span.end < offset);
+        self.words
+            .get(i)
+            .filter(|word| word.span.start <= offset)
+            .map(|_| i)
+    }
+
+    /// The digits of a word with their roles, as shown by [`render`].
+    pub fn label(&self, word: usize) -> String {
+        let word = &self.words[word];
+        let digits = self.digits[word.digits.clone()]
+            .iter()
+            .map(|digit| match digit.role {
+                Role::Opcode(_) => format!("{}:{}", digit.value, opcode_name(digit.value)),
+                Role::Argument(_) => digit.value.to_string(),
+                Role::MissingArgument => format!("{}:{}?", digit.value, opcode_name(digit.value)),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        if word.digits.is_empty() {
+            format!("{}=too long", word.letters)
+        } else if word.letters >= 10 {
+            format!("{}={}", word.letters, digits)
+        } else {
+            digits
+        }
+    }
+}
+
+/// Prints every line of the poem followed by a line with the [label](Annotation::label) of each
+/// word beneath it. Words are moved right where a label is wider than its word. Lines without
+/// words are printed as they are.
+pub fn render(source: &str) -> String {
+    let annotation = Annotation::new(source);
+    let mut result = String::new();
+    let mut word = 0;
+    let mut line_start = 0;
+    for line in source.split_inclusive('\n') {
+        let line_end = line_start + line.len();
+        let content = line.trim_end_matches(['\n', '\r']);
+
+        let mut verse = String::new();
+        let mut labels = String::new();
+        let mut position = line_start;
+        while let Some(current) = annotation
+            .words
+            .get(word)
+            .filter(|current| current.span.start < line_end)
+        {
+            verse.push_str(&source[position..current.span.start]);
+            if !labels.is_empty() {
+                let width = labels.chars().count() + 1;
+                pad(&mut verse, width);
+            }
+            pad(&mut labels, verse.chars().count());
+            verse.push_str(&source[current.span.clone()]);
+            labels.push_str(&annotation.label(word));
+
+            position = current.span.end;
+            word += 1;
+        }
+
+        if labels.is_empty() {
+            result.push_str(content);
+        } else {
+            verse.push_str(&source[position..line_start + content.len()]);
+            result.push_str(&verse);
+            result.push('\n');
+            result.push_str(&labels);
+        }
+        result.push('\n');
+        line_start = line_end;
+    }
+
+    result
+}
+
+fn pad(line: &mut String, width: usize) {
+    let length = line.chars().count();
+    if length < width {
+        line.extend(std::iter::repeat_n(' ', width - length));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render, Annotation, Role};
+    use crate::parser::{LongWords, Parser, ParserOptions};
+
+    #[test]
+    fn test_operations_and_matches() {
+        let annotation = Annotation::new("inc a\na fwdfw aaaaaaaaaaaa");
+        let opcodes = annotation
+            .operations
+            .iter()
+            .map(|operation| operation.opcode)
+            .collect::<Vec<_>>();
+        assert_eq!(opcodes, vec![3, 1, 5, 2]);
+        assert_eq!(annotation.operations[1].matching, Some(3));
+
+        // the 12 letter word is the argument 1 of FWD and the EIF
+        let word = &annotation.words[4];
+        assert_eq!(word.letters, 12);
+        assert!(word.is_split());
+        let roles = annotation.digits[word.digits.clone()]
+            .iter()
+            .map(|digit| digit.role)
+            .collect::<Vec<_>>();
+        assert_eq!(roles, vec![Role::Argument(2), Role::Opcode(3)]);
+        assert_eq!(annotation.argument(0), Some(1));
+        assert_eq!(annotation.argument(1), None);
+    }
+
+    #[test]
+    fn test_digits_match_parse_intermediate() {
+        let source = "Don't stop, extraordinarily   brave\nhearts! ' -- a b";
+        let digits = Annotation::new(source)
+            .digits
+            .iter()
+            .map(|digit| digit.value)
+            .collect::<Vec<_>>();
+        assert_eq!(digits, Parser::parse_intermediate(source));
+    }
+
+    #[test]
+    fn test_word_at() {
+        let annotation = Annotation::new("inc  can't");
+        assert_eq!(annotation.word_at(0), Some(0));
+        assert_eq!(annotation.word_at(3), Some(0));
+        assert_eq!(annotation.word_at(4), None);
+        assert_eq!(annotation.word_at(8), Some(1));
+        assert_eq!(annotation.label(1), "4");
+    }
+
+    #[test]
+    fn test_options() {
+        let options = ParserOptions::new()
+            .with_joiners(['-'])
+            .with_long_words(LongWords::Error);
+        let annotation = Annotation::with_options("well-known aaaaaaaaaaa", &options);
+        assert_eq!(annotation.words[0].span, 0..10);
+        assert_eq!(annotation.label(0), "9:RND");
+        assert!(annotation.words[1].digits.is_empty());
+        assert_eq!(annotation.label(1), "11=too long");
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("inc aaaaaaaaaaaa fwdfw\n\n  (outputa) decc, aaaaaaaaaa"),
+            concat!(
+                "inc   aaaaaaaaaaaa fwdfw\n",
+                "3:INC 12=1,2:EIF   5:FWD\n",
+                "\n",
+                "  (outputa) decc, aaaaaaaaaa\n",
+                "   7        4:DEC 10=0\n",
+            )
+        );
+    }
+}
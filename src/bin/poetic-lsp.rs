@@ -0,0 +1,12 @@
+//! Language server for poems over stdio, see [`poetic::lsp`].
+
+use lsp_server::Connection;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (connection, io_threads) = Connection::stdio();
+    poetic::lsp::run(&connection)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! A textual form of instructions, one per line with an optional argument. Everything after a
+//! `;` is a comment, mnemonics are case insensitive.
+//!
+//! ```text
+//! INC 3   ; first cell
+//! JIZ 4
+//! OUT
+//! JNZ 1
+//! ```
+
+use poetic::instruction::Instruction;
+
+pub fn to_asm(instructions: &[Instruction]) -> String {
+    instructions
+        .iter()
+        .map(|instruction| format!("{}\n", format_instruction(*instruction)))
+        .collect()
+}
+
+pub fn format_instruction(instruction: Instruction) -> String {
+    match instruction {
+        Instruction::END => "END".to_string(),
+        Instruction::IF => "IF".to_string(),
+        Instruction::EIF => "EIF".to_string(),
+        Instruction::INC(n) => format!("INC {}", n),
+        Instruction::DEC(n) => format!("DEC {}", n),
+        Instruction::FWD(n) => format!("FWD {}", n),
+        Instruction::BAK(n) => format!("BAK {}", n),
+        Instruction::OUT => "OUT".to_string(),
+        Instruction::IN => "IN".to_string(),
+        Instruction::RND => "RND".to_string(),
+        Instruction::JIZ(n) => format!("JIZ {}", n),
+        Instruction::JNZ(n) => format!("JNZ {}", n),
+        Instruction::JMP(n) => format!("JMP {}", n),
+        Instruction::SCF(n) => format!("SCF {}", n),
+        Instruction::SCB(n) => format!("SCB {}", n),
+        Instruction::PUT(n) => format!("PUT {}", n),
+        Instruction::SET(n) => format!("SET {}", n),
+    }
+}
+
+/// Reads instructions written by [`to_asm`], errors name the line they occur on.
+pub fn parse_asm(source: &str) -> Result<Vec<Instruction>, String> {
+    let mut result = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line = line.split(';').next().unwrap_or_default();
+        let mut parts = line.split_whitespace();
+        let Some(mnemonic) = parts.next() else {
+            continue;
+        };
+        let argument = parts.next();
+        if parts.next().is_some() {
+            return Err(format!("line {}: too many arguments", i + 1));
+        }
+
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let instruction = match (mnemonic.as_str(), argument) {
+            ("END", None) => Instruction::END,
+            ("IF", None) => Instruction::IF,
+            ("EIF", None) => Instruction::EIF,
+            ("OUT", None) => Instruction::OUT,
+            ("IN", None) => Instruction::IN,
+            ("RND", None) => Instruction::RND,
+            ("END" | "IF" | "EIF" | "OUT" | "IN" | "RND", Some(_)) => {
+                return Err(format!("line {}: {} takes no argument", i + 1, mnemonic))
+            }
+            (_, Some(argument)) => {
+                let byte = || {
+                    argument
+                        .parse::<u8>()
+                        .map_err(|_| format!("line {}: invalid argument {}", i + 1, argument))
+                };
+                let target = || {
+                    argument
+                        .parse::<usize>()
+                        .map_err(|_| format!("line {}: invalid jump target {}", i + 1, argument))
+                };
+                match mnemonic.as_str() {
+                    "INC" => Instruction::INC(byte()?),
+                    "DEC" => Instruction::DEC(byte()?),
+                    "FWD" => Instruction::FWD(byte()?),
+                    "BAK" => Instruction::BAK(byte()?),
+                    "SCF" => Instruction::SCF(byte()?),
+                    "SCB" => Instruction::SCB(byte()?),
+                    "PUT" => Instruction::PUT(byte()?),
+                    "SET" => Instruction::SET(byte()?),
+                    "JIZ" => Instruction::JIZ(target()?),
+                    "JNZ" => Instruction::JNZ(target()?),
+                    "JMP" => Instruction::JMP(target()?),
+                    _ => return Err(format!("line {}: unknown instruction {}", i + 1, mnemonic)),
+                }
+            }
+            (_, None) => {
+                return Err(format!(
+                    "line {}: unknown instruction {} or missing argument",
+                    i + 1,
+                    mnemonic
+                ))
+            }
+        };
+        result.push(instruction);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_asm, to_asm};
+    use poetic::instruction::Instruction;
+
+    #[test]
+    fn test_round_trip() {
+        let instructions = vec![
+            Instruction::INC(3),
+            Instruction::JIZ(4),
+            Instruction::SCF(2),
+            Instruction::OUT,
+            Instruction::JNZ(1),
+            Instruction::END,
+        ];
+        assert_eq!(parse_asm(&to_asm(&instructions)), Ok(instructions));
+    }
+
+    #[test]
+    fn test_comments_and_case() {
+        assert_eq!(
+            parse_asm("; header\n\n  inc 2 ; comment\nout\n"),
+            Ok(vec![Instruction::INC(2), Instruction::OUT])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse_asm("OUT\nINC"),
+            Err("line 2: unknown instruction INC or missing argument".to_string())
+        );
+        assert_eq!(
+            parse_asm("INC 256"),
+            Err("line 1: invalid argument 256".to_string())
+        );
+        assert_eq!(
+            parse_asm("OUT 1"),
+            Err("line 1: OUT takes no argument".to_string())
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use poetic::{
+    annotation::{Annotation, Operation, Role},
+    lint::Warning,
+    parser::ParseError,
+    synonyms::Suggestion,
+};
+use std::ops::Range;
+
+/// Finds the words in `source` responsible for `error`: the unmatched `IF` or `EIF`, or the
+/// instruction missing its argument.
+pub fn locate(source: &str, error: &ParseError) -> Option<Range<usize>> {
+    let annotation = Annotation::new(source);
+    let unmatched = |opcode| {
+        move |operation: &Operation| operation.opcode == opcode && operation.matching.is_none()
+    };
+
+    // the parser stops at the first stray EIF, and names the innermost open IF
+    let operation = match error {
+        ParseError::MissingIf => annotation.operations.iter().position(unmatched(2)),
+        ParseError::MissingEif => annotation.operations.iter().rposition(unmatched(1)),
+        ParseError::NeedsArgument(_) => {
+            let digit = annotation
+                .digits
+                .iter()
+                .find(|digit| digit.role == Role::MissingArgument)?;
+            return Some(annotation.words[digit.word].span.clone());
+        }
+        _ => None,
+    };
+    operation.map(|i| annotation.operation_span(i))
+}
+
+/// Formats `error` as `path:line:column: error: message`, pointing at the start of the
+/// responsible words if they can be found.
+pub fn report(path: &str, source: &str, error: &ParseError) -> String {
+    match locate(source, error) {
+        Some(span) => {
+            let (line, column) = position(source, span.start);
+            format!(
+                "{}:{}:{}: error: {} at \"{}\"",
+                path, line, column, error, &source[span]
+            )
+        }
+        None => format!("{}: error: {}", path, error),
+    }
+}
+
+/// Formats a lint warning as `path:line:column: warning[lint]: message`.
+pub fn report_warning(path: &str, source: &str, warning: &Warning) -> String {
+    let (line, column) = position(source, warning.span.start);
+    format!(
+        "{}:{}:{}: warning[{}]: {} at \"{}\"",
+        path,
+        line,
+        column,
+        warning.lint,
+        warning.message,
+        &source[warning.span.clone()]
+    )
+}
+
+/// Formats the synonyms of a word as `path:line:column: "word" could be synonym, synonym`.
+pub fn report_suggestion(path: &str, source: &str, suggestion: &Suggestion) -> String {
+    let (line, column) = position(source, suggestion.span.start);
+    format!(
+        "{}:{}:{}: \"{}\" could be {}",
+        path,
+        line,
+        column,
+        suggestion.word,
+        suggestion.synonyms.join(", ")
+    )
+}
+
+/// The 1-based line and column of a byte offset, columns counting characters.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rfind('\n')
+        .map_or(before, |newline| &before[newline + 1..])
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod test {
+    use super::report;
+    use poetic::parser::Parser;
+
+    fn check(source: &str) -> String {
+        report("poem.ptc", source, &Parser::parse(source).unwrap_err())
+    }
+
+    #[test]
+    fn test_missing_eif() {
+        assert_eq!(
+            check("a\nthe a a ee"),
+            "poem.ptc:1:1: error: Missing EIF at \"a\""
+        );
+    }
+
+    #[test]
+    fn test_missing_if() {
+        assert_eq!(
+            check("inc a\n  ei"),
+            "poem.ptc:2:3: error: Missing IF at \"ei\""
+        );
+    }
+
+    #[test]
+    fn test_needs_argument() {
+        assert_eq!(
+            check("outputa inc"),
+            "poem.ptc:1:9: error: INC Instruction needs an argument at \"inc\""
+        );
+    }
+}
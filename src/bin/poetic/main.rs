@@ -0,0 +1,566 @@
+//! The `poetic` command line tool: runs, checks, optimizes, assembles and compiles programs.
+//!
+//! Programs are read either as poems or as bytecode written by `opt --output` or `asm`.
+
+mod asm;
+mod diagnostics;
+
+use clap::{Args, Subcommand, ValueEnum};
+use poetic::{
+    annotation,
+    brainfuck::Brainfuck,
+    bytecode::{Program, MAGIC},
+    compiler::{CCompiler, MemoryModel, RustCompiler},
+    format::Formatter,
+    instruction::Instruction,
+    interpreter::{BoundedMemory, DynamicMemory, Interpreter, Memory},
+    lint::{Lint, Linter},
+    optimizer::{Optimize, Optimizer},
+    parser::Parser,
+    repl::Response,
+    synonyms::Rewriter,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{
+    cell::Cell,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    rc::Rc,
+};
+
+#[derive(clap::Parser)]
+#[command(
+    name = "poetic",
+    version,
+    about = "Run, check and compile poetic programs"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Runs a program
+    Run {
+        /// Poem or bytecode file
+        file: PathBuf,
+        #[command(flatten)]
+        options: RunOptions,
+    },
+    /// Parses poems and reports errors with their position
+    Check {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Warns about poems that parse but probably don't do what was meant
+    Lint {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Turns a lint off, can be repeated
+        #[arg(short = 'A', long, value_name = "LINT")]
+        allow: Vec<Lint>,
+    },
+    /// Prints a poem with the digits and instructions of every word beneath each line
+    Annotate { file: PathBuf },
+    /// Suggests synonyms of the same length for the words of a poem
+    Synonyms {
+        file: PathBuf,
+        /// Word list with comma separated synonyms on every line
+        #[arg(short, long)]
+        words: PathBuf,
+        /// Prints the poem with every word replaced by its first synonym instead
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Rewraps a poem and normalizes its whitespace without changing its instructions
+    Format {
+        file: PathBuf,
+        /// The maximum number of characters per line
+        #[arg(long, default_value_t = 80)]
+        width: usize,
+        /// Indents lines by this many spaces per open loop
+        #[arg(long, value_name = "SPACES")]
+        indent: Option<usize>,
+        /// Rewrites the file instead of printing the formatted poem
+        #[arg(short, long)]
+        write: bool,
+    },
+    /// Prints the optimized instructions of a program
+    Opt {
+        /// Poem or bytecode file
+        file: PathBuf,
+        /// Writes the optimized program as bytecode instead
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Assembles instructions in text form into bytecode
+    Asm {
+        /// Instructions in text form, one per line
+        file: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Stores a fixed memory size with the program
+        #[arg(long)]
+        memory_size: Option<usize>,
+    },
+    /// Prints the instructions of a program in text form
+    Disasm {
+        /// Poem or bytecode file
+        file: PathBuf,
+    },
+    /// Runs a program, printing every executed instruction to stderr
+    Trace {
+        /// Poem or bytecode file
+        file: PathBuf,
+        #[command(flatten)]
+        options: RunOptions,
+    },
+    /// Runs poems line by line on the same tape
+    Repl {
+        #[command(flatten)]
+        options: RunOptions,
+    },
+    /// Compiles a program to another language
+    Compile {
+        /// Poem or bytecode file
+        file: PathBuf,
+        #[arg(short, long, value_enum)]
+        target: Target,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Uses a fixed number of cells that wrap around instead of a growing tape
+        #[arg(long)]
+        memory_size: Option<usize>,
+        /// Optimizes the program before compiling it
+        #[arg(short = 'O', long)]
+        optimize: bool,
+    },
+}
+
+#[derive(Args)]
+struct RunOptions {
+    /// Uses a fixed number of cells that wrap around instead of a growing tape
+    #[arg(long)]
+    memory_size: Option<usize>,
+    /// Bits per memory cell, only 8 is supported
+    #[arg(long, default_value_t = 8)]
+    cell_width: u8,
+    /// What `IN` stores once the input is exhausted
+    #[arg(long, value_enum, default_value_t = Eof::Unchanged)]
+    eof: Eof,
+    /// Seeds the random number generator used by `RND`
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Stops with an error after this many instructions
+    #[arg(long)]
+    step_limit: Option<u64>,
+    /// Optimizes the program before running it
+    #[arg(short = 'O', long)]
+    optimize: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Eof {
+    /// Leaves the cell untouched
+    Unchanged,
+    /// Stores 0
+    Zero,
+    /// Stores 255
+    Max,
+}
+
+impl Eof {
+    const fn value(self) -> Option<u8> {
+        match self {
+            Eof::Unchanged => None,
+            Eof::Zero => Some(0),
+            Eof::Max => Some(u8::MAX),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Target {
+    C,
+    Rust,
+    Bf,
+}
+
+fn main() -> ExitCode {
+    let cli = <Cli as clap::Parser>::parse();
+    match execute(cli.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn execute(command: Command) -> Result<(), String> {
+    match command {
+        Command::Run { file, options } => run(&file, &options, false),
+        Command::Trace { file, options } => run(&file, &options, true),
+        Command::Repl { options } => repl(&options),
+        Command::Check { files } => check(&files),
+        Command::Lint { files, allow } => lint(&files, &allow),
+        Command::Opt { file, output } => {
+            let program = load(&file)?;
+            let program = Program {
+                instructions: Optimizer::new()
+                    .with_memory(program.memory)
+                    .optimize(&program.instructions),
+                source_map: None,
+                ..program
+            };
+            match output {
+                Some(output) => write(&output, &program.to_bytes()),
+                None => {
+                    print!("{}", asm::to_asm(&program.instructions));
+                    Ok(())
+                }
+            }
+        }
+        Command::Asm {
+            file,
+            output,
+            memory_size,
+        } => {
+            let source = read_to_string(&file)?;
+            let instructions = asm::parse_asm(&source)
+                .map_err(|error| format!("{}: error: {}", file.display(), error))?;
+            let program = Program::new(instructions).with_memory(memory_model(memory_size)?);
+            // validated the same way it is validated when loading
+            let bytes = program.to_bytes();
+            Program::from_bytes(&bytes)
+                .map_err(|error| format!("{}: error: {}", file.display(), error))?;
+            write(&output, &bytes)
+        }
+        Command::Annotate { file } => {
+            let source = fs::read_to_string(&file)
+                .map_err(|error| format!("{}: error: {}", file.display(), error))?;
+            print!("{}", annotation::render(&source));
+            Ok(())
+        }
+        Command::Synonyms { file, words, apply } => {
+            let source = read_to_string(&file)?;
+            let rewriter = Rewriter::new().with_word_list(&read_to_string(&words)?);
+            if apply {
+                print!("{}", rewriter.rewrite(&source, |_| Some(0)));
+            } else {
+                let path = file.display().to_string();
+                for suggestion in rewriter.suggest(&source) {
+                    println!(
+                        "{}",
+                        diagnostics::report_suggestion(&path, &source, &suggestion)
+                    );
+                }
+            }
+            Ok(())
+        }
+        Command::Format {
+            file,
+            width,
+            indent,
+            write: in_place,
+        } => {
+            let source = fs::read_to_string(&file)
+                .map_err(|error| format!("{}: error: {}", file.display(), error))?;
+            let formatter = Formatter::new().with_width(width);
+            let formatter = match indent {
+                Some(spaces) => formatter.with_indent(spaces),
+                None => formatter,
+            };
+            let formatted = formatter.format(&source);
+            if in_place {
+                write(&file, formatted.as_bytes())
+            } else {
+                print!("{}", formatted);
+                Ok(())
+            }
+        }
+        Command::Disasm { file } => {
+            let program = load(&file)?;
+            match program.memory {
+                MemoryModel::Dynamic => println!("; memory: dynamic"),
+                MemoryModel::Fixed(size) => println!("; memory: {}", size),
+            }
+            print!("{}", asm::to_asm(&program.instructions));
+            Ok(())
+        }
+        Command::Compile {
+            file,
+            target,
+            output,
+            memory_size,
+            optimize,
+        } => {
+            let mut program = load(&file)?;
+            let memory = match memory_size {
+                Some(_) => memory_model(memory_size)?,
+                None => program.memory,
+            };
+            if optimize {
+                program.instructions = Optimizer::new()
+                    .with_memory(memory)
+                    .optimize(&program.instructions);
+            }
+
+            let code = match target {
+                Target::C => CCompiler::new()
+                    .with_memory(memory)
+                    .compile(&program.instructions)
+                    .map_err(|error| format!("{}: error: {}", file.display(), error))?,
+                Target::Rust => RustCompiler::new()
+                    .with_memory(memory)
+                    .compile(&program.instructions)
+                    .map_err(|error| format!("{}: error: {}", file.display(), error))?,
+                Target::Bf => Brainfuck::new()
+                    .to_brainfuck(&program.instructions)
+                    .map_err(|error| format!("{}: error: {}", file.display(), error))?,
+            };
+            match output {
+                Some(output) => write(&output, code.as_bytes()),
+                None => {
+                    print!("{}", code);
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+fn check(files: &[PathBuf]) -> Result<(), String> {
+    let mut failed = 0;
+    for file in files {
+        match load(file) {
+            Ok(program) => println!(
+                "{}: ok, {} instructions",
+                file.display(),
+                program.instructions.len()
+            ),
+            Err(message) => {
+                println!("{}", message);
+                failed += 1;
+            }
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        1 => Err("1 file has errors".to_string()),
+        n => Err(format!("{} files have errors", n)),
+    }
+}
+
+fn lint(files: &[PathBuf], allow: &[Lint]) -> Result<(), String> {
+    let linter = allow
+        .iter()
+        .fold(Linter::new(), |linter, lint| linter.with_lint(*lint, false));
+    let mut count = 0;
+    for file in files {
+        let source = fs::read_to_string(file)
+            .map_err(|error| format!("{}: error: {}", file.display(), error))?;
+        let path = file.display().to_string();
+        for warning in linter.lint(&source) {
+            println!("{}", diagnostics::report_warning(&path, &source, &warning));
+            count += 1;
+        }
+    }
+
+    match count {
+        0 => Ok(()),
+        1 => Err("1 warning".to_string()),
+        n => Err(format!("{} warnings", n)),
+    }
+}
+
+fn run(file: &Path, options: &RunOptions, trace: bool) -> Result<(), String> {
+    check_cell_width(options.cell_width)?;
+
+    let mut program = load(file)?;
+    let memory = match options.memory_size {
+        Some(_) => memory_model(options.memory_size)?,
+        None => program.memory,
+    };
+    if options.optimize {
+        program.instructions = Optimizer::new()
+            .with_memory(memory)
+            .optimize(&program.instructions);
+    }
+
+    match memory {
+        MemoryModel::Dynamic => interpret(
+            program.instructions,
+            DynamicMemory::default(),
+            options,
+            trace,
+        ),
+        MemoryModel::Fixed(size) => interpret(
+            program.instructions,
+            BoundedMemory::new(size.get()),
+            options,
+            trace,
+        ),
+    }
+}
+
+fn interpret<M: Memory>(
+    instructions: Vec<Instruction>,
+    memory: M,
+    options: &RunOptions,
+    trace: bool,
+) -> Result<(), String> {
+    let output = |s: String| {
+        let _ = io::stdout().lock().write_all(s.as_bytes());
+    };
+    let mut interpreter = Interpreter::new(instructions)
+        .map_err(|error| format!("error: {}", error))?
+        .with_memory(memory)
+        .with_io(input(options.eof), output)
+        .with_rng(rng(options.seed));
+
+    let mut steps = 0;
+    let result = loop {
+        if interpreter.has_ended() {
+            break Ok(());
+        }
+        if let Some(limit) = options.step_limit.filter(|limit| steps >= *limit) {
+            break Err(format!("error: step limit of {} reached", limit));
+        }
+
+        if trace {
+            let instruction = interpreter.instructions[interpreter.instruction_pointer];
+            eprintln!(
+                "{:>6}  {:<8} pointer {:<6} cell {}",
+                interpreter.instruction_pointer,
+                asm::format_instruction(instruction),
+                interpreter.memory.get_memory_pointer(),
+                interpreter.memory.get_memory_value()
+            );
+        }
+        interpreter.step();
+        steps += 1;
+    };
+
+    let _ = io::stdout().flush();
+    result
+}
+
+fn repl(options: &RunOptions) -> Result<(), String> {
+    check_cell_width(options.cell_width)?;
+    match memory_model(options.memory_size)? {
+        MemoryModel::Dynamic => repl_with_memory(DynamicMemory::default(), options),
+        MemoryModel::Fixed(size) => repl_with_memory(BoundedMemory::new(size.get()), options),
+    }
+}
+
+fn repl_with_memory<M: Memory>(memory: M, options: &RunOptions) -> Result<(), String> {
+    // the prompt goes on a fresh line after output without a trailing newline
+    let needs_newline = Rc::new(Cell::new(false));
+    let output = {
+        let needs_newline = needs_newline.clone();
+        move |s: String| {
+            let _ = io::stdout().lock().write_all(s.as_bytes());
+            needs_newline.set(!s.ends_with('\n'));
+        }
+    };
+    let interpreter = Interpreter::new(Vec::new())
+        .map_err(|error| format!("error: {}", error))?
+        .with_memory(memory)
+        .with_io(input(options.eof), output)
+        .with_rng(rng(options.seed));
+    let mut repl = poetic::repl::Repl::new(interpreter).with_optimizer(options.optimize);
+    if let Some(limit) = options.step_limit {
+        repl = repl.with_step_limit(limit);
+    }
+
+    println!("poetic {}, :help for commands", env!("CARGO_PKG_VERSION"));
+    let mut line = String::new();
+    loop {
+        if needs_newline.replace(false) {
+            println!();
+        }
+        print!("{}", if repl.is_incomplete() { "... " } else { "> " });
+        let _ = io::stdout().flush();
+
+        line.clear();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(error) => return Err(format!("error: {}", error)),
+        }
+
+        match repl.eval(line.trim_end_matches(['\n', '\r'])) {
+            Ok(Response::Message(message)) => println!("{}", message),
+            Ok(Response::Quit) => return Ok(()),
+            Ok(Response::Ran | Response::Incomplete) => {}
+            Err(error) => eprintln!("error: {}", error),
+        }
+    }
+}
+
+/// Reads single bytes from stdin, returning `eof` once it is exhausted.
+fn input(eof: Eof) -> impl FnMut() -> Option<u8> {
+    move || {
+        // prompts without a trailing newline have to be visible before blocking
+        let _ = io::stdout().flush();
+        let mut byte = [0];
+        match io::stdin().read(&mut byte) {
+            Ok(1) => Some(byte[0]),
+            _ => eof.value(),
+        }
+    }
+}
+
+fn rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+fn check_cell_width(cell_width: u8) -> Result<(), String> {
+    if cell_width != 8 {
+        return Err(format!(
+            "error: unsupported cell width {}, only 8 bit cells are supported",
+            cell_width
+        ));
+    }
+
+    Ok(())
+}
+
+fn memory_model(memory_size: Option<usize>) -> Result<MemoryModel, String> {
+    match memory_size {
+        None => Ok(MemoryModel::Dynamic),
+        Some(size) => MemoryModel::fixed(size)
+            .ok_or_else(|| "error: memory size has to be at least 1".to_string()),
+    }
+}
+
+/// Reads bytecode or parses a poem, depending on the magic number.
+fn load(file: &Path) -> Result<Program, String> {
+    let bytes = fs::read(file).map_err(|error| format!("{}: error: {}", file.display(), error))?;
+    if bytes.starts_with(&MAGIC) {
+        return Program::from_bytes(&bytes)
+            .map_err(|error| format!("{}: error: {}", file.display(), error));
+    }
+
+    let source = String::from_utf8(bytes)
+        .map_err(|error| format!("{}: error: {}", file.display(), error))?;
+    Parser::parse(&source)
+        .map(Program::new)
+        .map_err(|error| diagnostics::report(&file.display().to_string(), &source, &error))
+}
+
+fn read_to_string(file: &Path) -> Result<String, String> {
+    fs::read_to_string(file).map_err(|error| format!("{}: error: {}", file.display(), error))
+}
+
+fn write(file: &Path, bytes: &[u8]) -> Result<(), String> {
+    fs::write(file, bytes).map_err(|error| format!("{}: error: {}", file.display(), error))
+}
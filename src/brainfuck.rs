@@ -0,0 +1,329 @@
+//! Conversion between instructions and [Brainfuck](https://esolangs.org/wiki/Brainfuck).
+//!
+//! Most instructions map directly: `INC`/`DEC` become runs of `+`/`-`, `FWD`/`BAK` runs of
+//! `>`/`<`, `IF`/`EIF` are `[`/`]`, `OUT` is `.` and `IN` is `,`. Optimized programs are
+//! supported as long as their jumps form loops, `SCF`/`SCB`/`SET` are expanded into their loop
+//! equivalents.
+//!
+//! Brainfuck has no way to stop a program early or to draw a random number:
+//! - `END` is dropped if nothing but other `END`s follow it, anywhere else it is an error
+//! - `RND` is an error, unless a command for it is set with
+//!   [`Brainfuck::with_random_command`], as some Brainfuck extensions use `?`
+//! - `PUT` is an error, it would need a scratch cell
+//!
+//! Differences in the semantics of the languages are not translated: the tape of the interpreter
+//! grows to the right and wraps around on the left, and `IN` leaves the cell untouched at the end
+//! of input.
+
+use crate::{instruction::Instruction, interpreter::build_jump_table, parser::ParseError};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BrainfuckError {
+    /// The program has mismatched `IF`/`EIF`.
+    InvalidProgram(ParseError),
+    /// The instruction at the index has no Brainfuck equivalent.
+    Unsupported(usize, Instruction),
+    /// The `[` at the character index has no matching `]`.
+    UnmatchedOpen(usize),
+    /// The `]` at the character index has no matching `[`.
+    UnmatchedClose(usize),
+}
+
+impl Display for BrainfuckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrainfuckError::InvalidProgram(error) => write!(f, "Invalid program: {}", error),
+            BrainfuckError::Unsupported(index, instruction) => write!(
+                f,
+                "{:?} at instruction {} has no Brainfuck equivalent",
+                instruction, index
+            ),
+            BrainfuckError::UnmatchedOpen(index) => {
+                write!(f, "Unmatched '[' at character {}", index)
+            }
+            BrainfuckError::UnmatchedClose(index) => {
+                write!(f, "Unmatched ']' at character {}", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BrainfuckError {}
+
+pub struct Brainfuck {
+    random_command: Option<char>,
+}
+
+impl Brainfuck {
+    pub fn new() -> Self {
+        Self {
+            random_command: None,
+        }
+    }
+
+    /// Uses `command` for `RND` in both directions.
+    pub fn with_random_command(self, command: char) -> Self {
+        Self {
+            random_command: Some(command),
+        }
+    }
+
+    /// Writes the instructions as Brainfuck.
+    pub fn to_brainfuck(&self, instructions: &[Instruction]) -> Result<String, BrainfuckError> {
+        build_jump_table(instructions).map_err(BrainfuckError::InvalidProgram)?;
+        let structured = crate::compiler::restore_loops(instructions)
+            .map_err(|i| BrainfuckError::Unsupported(i, instructions[i]))?;
+
+        let mut result = String::new();
+        for (i, instruction) in structured.iter().enumerate() {
+            match *instruction {
+                Instruction::END => {
+                    if structured[i..].iter().any(|x| *x != Instruction::END) {
+                        return Err(BrainfuckError::Unsupported(i, Instruction::END));
+                    }
+                }
+                Instruction::IF => result.push('['),
+                Instruction::EIF => result.push(']'),
+                Instruction::INC(n) => push_change(&mut result, n),
+                Instruction::DEC(n) => push_change(&mut result, n.wrapping_neg()),
+                Instruction::FWD(n) => push_repeated(&mut result, '>', n),
+                Instruction::BAK(n) => push_repeated(&mut result, '<', n),
+                Instruction::OUT => result.push('.'),
+                Instruction::IN => result.push(','),
+                Instruction::RND => match self.random_command {
+                    Some(command) => result.push(command),
+                    None => return Err(BrainfuckError::Unsupported(i, Instruction::RND)),
+                },
+                Instruction::SCF(n) => {
+                    result.push('[');
+                    push_repeated(&mut result, '>', n);
+                    result.push(']');
+                }
+                Instruction::SCB(n) => {
+                    result.push('[');
+                    push_repeated(&mut result, '<', n);
+                    result.push(']');
+                }
+                Instruction::SET(n) => {
+                    result.push_str("[-]");
+                    push_change(&mut result, n);
+                }
+                // jumps were replaced by restore_loops
+                instruction => return Err(BrainfuckError::Unsupported(i, instruction)),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Reads Brainfuck, ignoring every character that is not a command. Runs of `+`/`-` are
+    /// merged into a single `INC` or `DEC`, runs of `>` or `<` into a single `FWD` or `BAK`,
+    /// with arguments of at most 255.
+    pub fn from_brainfuck(&self, source: &str) -> Result<Vec<Instruction>, BrainfuckError> {
+        let mut result = Vec::new();
+        let mut open = Vec::new();
+        let mut change = 0u8;
+
+        for (index, c) in source.chars().enumerate() {
+            match c {
+                '+' => {
+                    change = change.wrapping_add(1);
+                    continue;
+                }
+                '-' => {
+                    change = change.wrapping_sub(1);
+                    continue;
+                }
+                _ if !self.is_command(c) => continue,
+                _ => {}
+            }
+
+            push_cell_change(&mut result, std::mem::take(&mut change));
+            match c {
+                '>' => push_move(&mut result, Instruction::FWD),
+                '<' => push_move(&mut result, Instruction::BAK),
+                '.' => result.push(Instruction::OUT),
+                ',' => result.push(Instruction::IN),
+                '[' => {
+                    open.push(index);
+                    result.push(Instruction::IF);
+                }
+                ']' => {
+                    open.pop().ok_or(BrainfuckError::UnmatchedClose(index))?;
+                    result.push(Instruction::EIF);
+                }
+                _ => result.push(Instruction::RND),
+            }
+        }
+        push_cell_change(&mut result, change);
+
+        match open.pop() {
+            Some(index) => Err(BrainfuckError::UnmatchedOpen(index)),
+            None => Ok(result),
+        }
+    }
+
+    fn is_command(&self, c: char) -> bool {
+        matches!(c, '>' | '<' | '.' | ',' | '[' | ']') || Some(c) == self.random_command
+    }
+}
+
+impl Default for Brainfuck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn push_repeated(result: &mut String, c: char, n: u8) {
+    result.extend(std::iter::repeat_n(c, n as usize));
+}
+
+/// Adds `change` to the cell with the shorter of `+` or `-`.
+fn push_change(result: &mut String, change: u8) {
+    if change <= 128 {
+        push_repeated(result, '+', change);
+    } else {
+        push_repeated(result, '-', change.wrapping_neg());
+    }
+}
+
+fn push_cell_change(result: &mut Vec<Instruction>, change: u8) {
+    match change {
+        0 => {}
+        1..=128 => result.push(Instruction::INC(change)),
+        _ => result.push(Instruction::DEC(change.wrapping_neg())),
+    }
+}
+
+/// Extends the previous move in the same direction, as long as its argument does not overflow.
+fn push_move(result: &mut Vec<Instruction>, instruction: fn(u8) -> Instruction) {
+    if let Some(last) = result.last_mut() {
+        let n = match (*last, instruction(1)) {
+            (Instruction::FWD(n), Instruction::FWD(_))
+            | (Instruction::BAK(n), Instruction::BAK(_)) => Some(n),
+            _ => None,
+        };
+        if let Some(n) = n.filter(|n| *n < u8::MAX) {
+            *last = instruction(n + 1);
+            return;
+        }
+    }
+
+    result.push(instruction(1));
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Brainfuck, BrainfuckError};
+    use crate::{
+        instruction::Instruction,
+        interpreter::Interpreter,
+        optimizer::{Optimize, Optimizer},
+        samples::HELLO_WORLD_SHORTEST,
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+    fn run(instructions: Vec<Instruction>) -> String {
+        let output = Rc::new(RefCell::new(String::new()));
+        let output_clone = output.clone();
+        let mut interpreter = Interpreter::new(instructions)
+            .unwrap()
+            .with_fixed_size_memory::<100>()
+            .with_output(move |s: String| output_clone.borrow_mut().push_str(&s));
+        interpreter.run();
+        let result = output.borrow().clone();
+        result
+    }
+
+    #[test]
+    fn test_from_brainfuck() {
+        let instructions = Brainfuck::new().from_brainfuck(HELLO_WORLD).unwrap();
+        assert_eq!(
+            &instructions[..3],
+            &[Instruction::INC(8), Instruction::IF, Instruction::FWD(1)]
+        );
+        assert_eq!(run(instructions), "Hello World!\n");
+    }
+
+    #[test]
+    fn test_from_brainfuck_merges_runs() {
+        let instructions = Brainfuck::new()
+            .from_brainfuck("+-- comment >>> <")
+            .unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::DEC(1),
+                Instruction::FWD(3),
+                Instruction::BAK(1)
+            ]
+        );
+
+        let instructions = Brainfuck::new().from_brainfuck(&">".repeat(300)).unwrap();
+        assert_eq!(
+            instructions,
+            vec![Instruction::FWD(255), Instruction::FWD(45)]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let instructions = crate::parser::Parser::parse(HELLO_WORLD_SHORTEST).unwrap();
+        let brainfuck = Brainfuck::new().to_brainfuck(&instructions).unwrap();
+        let converted = Brainfuck::new().from_brainfuck(&brainfuck).unwrap();
+        assert_eq!(run(converted), "Hello, World!");
+
+        let optimized = Optimizer::new().optimize(&instructions);
+        let brainfuck = Brainfuck::new().to_brainfuck(&optimized).unwrap();
+        let converted = Brainfuck::new().from_brainfuck(&brainfuck).unwrap();
+        assert_eq!(run(converted), "Hello, World!");
+    }
+
+    #[test]
+    fn test_end_and_random() {
+        let instructions = vec![Instruction::RND, Instruction::OUT, Instruction::END];
+        assert_eq!(
+            Brainfuck::new().to_brainfuck(&instructions),
+            Err(BrainfuckError::Unsupported(0, Instruction::RND))
+        );
+        assert_eq!(
+            Brainfuck::new()
+                .with_random_command('?')
+                .to_brainfuck(&instructions),
+            Ok("?.".to_string())
+        );
+        assert_eq!(
+            Brainfuck::new()
+                .with_random_command('?')
+                .from_brainfuck("?."),
+            Ok(vec![Instruction::RND, Instruction::OUT])
+        );
+
+        let instructions = vec![Instruction::END, Instruction::OUT];
+        assert_eq!(
+            Brainfuck::new().to_brainfuck(&instructions),
+            Err(BrainfuckError::Unsupported(0, Instruction::END))
+        );
+
+        let instructions = vec![Instruction::OUT, Instruction::JMP(0)];
+        assert_eq!(
+            Brainfuck::new().to_brainfuck(&instructions),
+            Err(BrainfuckError::Unsupported(1, Instruction::JMP(0)))
+        );
+    }
+
+    #[test]
+    fn test_unmatched_brackets() {
+        assert_eq!(
+            Brainfuck::new().from_brainfuck("+[[]"),
+            Err(BrainfuckError::UnmatchedOpen(1))
+        );
+        assert_eq!(
+            Brainfuck::new().from_brainfuck("+]"),
+            Err(BrainfuckError::UnmatchedClose(1))
+        );
+    }
+}
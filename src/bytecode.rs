@@ -0,0 +1,494 @@
+//! A compact binary format for parsed and optimized programs, so they can be shipped and loaded
+//! without running the parser and optimizer again.
+//!
+//! All integers are little endian, counts, sizes and jump targets are unsigned LEB128.
+//!
+//! | Field             | Size      |                                                   |
+//! |-------------------|-----------|---------------------------------------------------|
+//! | magic             | 4         | `PTCB`                                            |
+//! | version           | 2         | [`VERSION`]                                       |
+//! | flags             | 2         | [`FLAG_SOURCE_MAP`]                               |
+//! | cell width        | 1         | bits per memory cell, always 8                    |
+//! | memory size       | LEB128    | `0` for [`MemoryModel::Dynamic`]                  |
+//! | instruction count | LEB128    |                                                   |
+//! | instructions      |           | opcode byte, followed by the argument if any      |
+//! | source map        | optional  | start and length of every instruction's source    |
+
+use crate::{
+    compiler::MemoryModel,
+    instruction::Instruction,
+    interpreter::build_jump_table,
+    parser::{ParseError, Parser, ParserOptions},
+};
+use std::{fmt::Display, ops::Range};
+
+pub const MAGIC: [u8; 4] = *b"PTCB";
+pub const VERSION: u16 = 1;
+/// The oldest version that can be read, no bytecode was ever written with version 0.
+const FIRST_VERSION: u16 = 1;
+/// A source map section follows the instructions.
+pub const FLAG_SOURCE_MAP: u16 = 1;
+const CELL_WIDTH: u8 = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BytecodeError {
+    InvalidMagic,
+    /// The bytecode was written by a newer version or its version is 0.
+    UnsupportedVersion(u16),
+    UnknownFlags(u16),
+    UnsupportedCellWidth(u8),
+    UnexpectedEnd,
+    /// An integer does not fit into a `usize`.
+    Overflow,
+    /// The opcode at the byte offset is unknown.
+    UnknownOpcode(usize, u8),
+    /// The jump at the index targets past the end of the program.
+    InvalidJumpTarget(usize, usize),
+    /// The program has mismatched `IF`/`EIF`.
+    InvalidProgram(ParseError),
+    /// The bytecode continues after the byte offset.
+    TrailingBytes(usize),
+}
+
+impl Display for BytecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BytecodeError::InvalidMagic => write!(f, "Not poetic bytecode"),
+            BytecodeError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported bytecode version: {}", version)
+            }
+            BytecodeError::UnknownFlags(flags) => write!(f, "Unknown flags: {:#06x}", flags),
+            BytecodeError::UnsupportedCellWidth(width) => {
+                write!(f, "Unsupported cell width: {} bits", width)
+            }
+            BytecodeError::UnexpectedEnd => write!(f, "Unexpected end of bytecode"),
+            BytecodeError::Overflow => write!(f, "Integer overflow"),
+            BytecodeError::UnknownOpcode(offset, opcode) => {
+                write!(f, "Unknown opcode {} at byte {}", opcode, offset)
+            }
+            BytecodeError::InvalidJumpTarget(index, target) => write!(
+                f,
+                "Jump at instruction {} targets {} past the end of the program",
+                index, target
+            ),
+            BytecodeError::InvalidProgram(error) => write!(f, "Invalid program: {}", error),
+            BytecodeError::TrailingBytes(offset) => {
+                write!(f, "Trailing bytes after byte {}", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BytecodeError {}
+
+/// Byte ranges of the source every instruction was parsed from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    pub spans: Vec<Range<usize>>,
+}
+
+impl SourceMap {
+    /// Maps the instructions [`Parser::parse`](crate::parser::Parser::parse) reads from
+    /// `source`, spanning from an instruction's first word to the end of its argument. Words
+    /// longer than 10 letters hold several digits, all instructions from them share the word.
+    pub fn from_source(source: &str) -> Self {
+        let digits = Parser::word_spans(source, &ParserOptions::default())
+            .into_iter()
+            .flat_map(|(span, letters)| {
+                Parser::word_digits(letters)
+                    .into_iter()
+                    .map(move |digit| (span.clone(), digit))
+            })
+            .collect::<Vec<_>>();
+
+        let mut spans = Vec::new();
+        let mut i = 0;
+        while i < digits.len() {
+            let (span, digit) = &digits[i];
+            match (digit, digits.get(i + 1)) {
+                (3..=6, Some((argument, _))) => {
+                    spans.push(span.start..argument.end);
+                    i += 2;
+                }
+                _ => {
+                    spans.push(span.clone());
+                    i += 1;
+                }
+            }
+        }
+
+        Self { spans }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    pub memory: MemoryModel,
+    pub source_map: Option<SourceMap>,
+}
+
+impl Program {
+    pub fn new(instructions: Vec<Instruction>) -> Self {
+        Self {
+            instructions,
+            memory: MemoryModel::Dynamic,
+            source_map: None,
+        }
+    }
+
+    pub fn with_memory(self, memory: MemoryModel) -> Self {
+        Self { memory, ..self }
+    }
+
+    pub fn with_source_map(self, source_map: SourceMap) -> Self {
+        Self {
+            source_map: Some(source_map),
+            ..self
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(MAGIC);
+        bytes.extend(VERSION.to_le_bytes());
+        let flags = if self.source_map.is_some() {
+            FLAG_SOURCE_MAP
+        } else {
+            0
+        };
+        bytes.extend(flags.to_le_bytes());
+        bytes.push(CELL_WIDTH);
+        write_leb128(
+            &mut bytes,
+            match self.memory {
+                MemoryModel::Dynamic => 0,
+                MemoryModel::Fixed(size) => size.get(),
+            },
+        );
+
+        write_leb128(&mut bytes, self.instructions.len());
+        for instruction in &self.instructions {
+            bytes.push(opcode(instruction));
+            match *instruction {
+                Instruction::INC(n)
+                | Instruction::DEC(n)
+                | Instruction::FWD(n)
+                | Instruction::BAK(n)
+                | Instruction::SCF(n)
+                | Instruction::SCB(n)
+                | Instruction::PUT(n)
+                | Instruction::SET(n) => bytes.push(n),
+                Instruction::JIZ(n) | Instruction::JNZ(n) | Instruction::JMP(n) => {
+                    write_leb128(&mut bytes, n)
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(source_map) = &self.source_map {
+            write_leb128(&mut bytes, source_map.spans.len());
+            for span in &source_map.spans {
+                write_leb128(&mut bytes, span.start);
+                write_leb128(&mut bytes, span.end.saturating_sub(span.start));
+            }
+        }
+
+        bytes
+    }
+
+    /// Loads and validates bytecode written by [`Program::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BytecodeError> {
+        let mut reader = Reader { bytes, offset: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(BytecodeError::InvalidMagic);
+        }
+
+        let version = reader.u16()?;
+        if !(FIRST_VERSION..=VERSION).contains(&version) {
+            return Err(BytecodeError::UnsupportedVersion(version));
+        }
+
+        let flags = reader.u16()?;
+        if flags & !FLAG_SOURCE_MAP != 0 {
+            return Err(BytecodeError::UnknownFlags(flags));
+        }
+
+        let cell_width = reader.u8()?;
+        if cell_width != CELL_WIDTH {
+            return Err(BytecodeError::UnsupportedCellWidth(cell_width));
+        }
+
+        // a size of 0 marks a dynamic memory
+        let memory = MemoryModel::fixed(reader.leb128()?).unwrap_or(MemoryModel::Dynamic);
+
+        let count = reader.leb128()?;
+        // every instruction takes at least one byte
+        let mut instructions = Vec::with_capacity(count.min(bytes.len()));
+        for _ in 0..count {
+            let offset = reader.offset;
+            let instruction = match reader.u8()? {
+                0 => Instruction::END,
+                1 => Instruction::IF,
+                2 => Instruction::EIF,
+                3 => Instruction::INC(reader.u8()?),
+                4 => Instruction::DEC(reader.u8()?),
+                5 => Instruction::FWD(reader.u8()?),
+                6 => Instruction::BAK(reader.u8()?),
+                7 => Instruction::OUT,
+                8 => Instruction::IN,
+                9 => Instruction::RND,
+                10 => Instruction::JIZ(reader.leb128()?),
+                11 => Instruction::JNZ(reader.leb128()?),
+                12 => Instruction::JMP(reader.leb128()?),
+                13 => Instruction::SCF(reader.u8()?),
+                14 => Instruction::SCB(reader.u8()?),
+                15 => Instruction::PUT(reader.u8()?),
+                16 => Instruction::SET(reader.u8()?),
+                opcode => return Err(BytecodeError::UnknownOpcode(offset, opcode)),
+            };
+            instructions.push(instruction);
+        }
+
+        for (i, instruction) in instructions.iter().enumerate() {
+            if let Instruction::JIZ(n) | Instruction::JNZ(n) | Instruction::JMP(n) = *instruction {
+                if n > instructions.len() {
+                    return Err(BytecodeError::InvalidJumpTarget(i, n));
+                }
+            }
+        }
+        build_jump_table(&instructions).map_err(BytecodeError::InvalidProgram)?;
+
+        let source_map = if flags & FLAG_SOURCE_MAP != 0 {
+            let count = reader.leb128()?;
+            let mut spans = Vec::with_capacity(count.min(bytes.len()));
+            for _ in 0..count {
+                let start = reader.leb128()?;
+                let end = start
+                    .checked_add(reader.leb128()?)
+                    .ok_or(BytecodeError::Overflow)?;
+                spans.push(start..end);
+            }
+            Some(SourceMap { spans })
+        } else {
+            None
+        };
+
+        if reader.offset != bytes.len() {
+            return Err(BytecodeError::TrailingBytes(reader.offset));
+        }
+
+        Ok(Self {
+            instructions,
+            memory,
+            source_map,
+        })
+    }
+}
+
+const fn opcode(instruction: &Instruction) -> u8 {
+    match instruction {
+        Instruction::END => 0,
+        Instruction::IF => 1,
+        Instruction::EIF => 2,
+        Instruction::INC(_) => 3,
+        Instruction::DEC(_) => 4,
+        Instruction::FWD(_) => 5,
+        Instruction::BAK(_) => 6,
+        Instruction::OUT => 7,
+        Instruction::IN => 8,
+        Instruction::RND => 9,
+        Instruction::JIZ(_) => 10,
+        Instruction::JNZ(_) => 11,
+        Instruction::JMP(_) => 12,
+        Instruction::SCF(_) => 13,
+        Instruction::SCB(_) => 14,
+        Instruction::PUT(_) => 15,
+        Instruction::SET(_) => 16,
+    }
+}
+
+fn write_leb128(bytes: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], BytecodeError> {
+        let slice = self
+            .bytes
+            .get(self.offset..self.offset + n)
+            .ok_or(BytecodeError::UnexpectedEnd)?;
+        self.offset += n;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, BytecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, BytecodeError> {
+        Ok(u16::from_le_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn leb128(&mut self) -> Result<usize, BytecodeError> {
+        let mut result = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            let value = (byte & 0x7F) as usize;
+            if shift >= usize::BITS || (value << shift) >> shift != value {
+                return Err(BytecodeError::Overflow);
+            }
+            result |= value << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BytecodeError, Program, SourceMap};
+    use crate::{
+        compiler::MemoryModel,
+        instruction::Instruction,
+        optimizer::{Optimize, Optimizer},
+        parser::{ParseError, Parser},
+        samples::HELLO_WORLD_SHORTEST,
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let instructions = Parser::parse(HELLO_WORLD_SHORTEST).unwrap();
+        let optimized = Optimizer::new().optimize(&instructions);
+        assert!(optimized
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::JIZ(_))));
+
+        let program = Program::new(optimized).with_memory(MemoryModel::fixed(100).unwrap());
+        assert_eq!(Program::from_bytes(&program.to_bytes()), Ok(program));
+
+        // multi byte jump targets
+        let mut instructions = vec![Instruction::JMP(300), Instruction::SET(255)];
+        instructions.resize(300, Instruction::OUT);
+        let program = Program::new(instructions);
+        assert_eq!(Program::from_bytes(&program.to_bytes()), Ok(program));
+    }
+
+    #[test]
+    fn test_source_map() {
+        let source = "inc a a ei can't supercalifragilisticexpialidocious aaaa";
+        let instructions = Parser::parse(source).unwrap();
+        let source_map = SourceMap::from_source(source);
+        assert_eq!(source_map.spans.len(), instructions.len());
+        let spans = source_map
+            .spans
+            .iter()
+            .map(|span| &source[span.clone()])
+            .collect::<Vec<_>>();
+        // the 34 letter word is the argument of the first DEC and the second DEC itself
+        assert_eq!(
+            spans,
+            vec![
+                "inc a",
+                "a",
+                "ei",
+                "can't supercalifragilisticexpialidocious",
+                "supercalifragilisticexpialidocious aaaa",
+            ]
+        );
+
+        let program = Program::new(instructions).with_source_map(source_map);
+        assert_eq!(Program::from_bytes(&program.to_bytes()), Ok(program));
+    }
+
+    #[test]
+    fn test_header_validated() {
+        let bytes = Program::new(vec![Instruction::OUT]).to_bytes();
+
+        let mut invalid = bytes.clone();
+        invalid[0] = b'X';
+        assert_eq!(
+            Program::from_bytes(&invalid),
+            Err(BytecodeError::InvalidMagic)
+        );
+
+        let mut invalid = bytes.clone();
+        invalid[4] = 2;
+        assert_eq!(
+            Program::from_bytes(&invalid),
+            Err(BytecodeError::UnsupportedVersion(2))
+        );
+
+        let mut invalid = bytes.clone();
+        invalid[4] = 0;
+        assert_eq!(
+            Program::from_bytes(&invalid),
+            Err(BytecodeError::UnsupportedVersion(0))
+        );
+
+        let mut invalid = bytes.clone();
+        invalid[6] = 2;
+        assert_eq!(
+            Program::from_bytes(&invalid),
+            Err(BytecodeError::UnknownFlags(2))
+        );
+
+        let mut invalid = bytes.clone();
+        invalid[8] = 16;
+        assert_eq!(
+            Program::from_bytes(&invalid),
+            Err(BytecodeError::UnsupportedCellWidth(16))
+        );
+
+        assert_eq!(
+            Program::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(BytecodeError::UnexpectedEnd)
+        );
+
+        let mut invalid = bytes.clone();
+        invalid.push(0);
+        assert_eq!(
+            Program::from_bytes(&invalid),
+            Err(BytecodeError::TrailingBytes(bytes.len()))
+        );
+    }
+
+    #[test]
+    fn test_instructions_validated() {
+        let mut bytes = Program::new(vec![Instruction::OUT]).to_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] = 17;
+        assert_eq!(
+            Program::from_bytes(&bytes),
+            Err(BytecodeError::UnknownOpcode(last, 17))
+        );
+
+        let bytes = Program::new(vec![Instruction::JIZ(2)]).to_bytes();
+        assert_eq!(
+            Program::from_bytes(&bytes),
+            Err(BytecodeError::InvalidJumpTarget(0, 2))
+        );
+
+        let bytes = Program::new(vec![Instruction::IF]).to_bytes();
+        assert_eq!(
+            Program::from_bytes(&bytes),
+            Err(BytecodeError::InvalidProgram(ParseError::MissingEif))
+        );
+    }
+}
@@ -0,0 +1,332 @@
+use super::{find_jump_targets, MemoryModel};
+use crate::{
+    instruction::Instruction,
+    interpreter::{build_jump_table, DEFAULT_MEMORY_SIZE},
+    parser::ParseError,
+};
+use std::fmt::Write;
+
+const PRELUDE: &str = "#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+";
+
+const DYNAMIC_MEMORY: &str = "
+static unsigned char *tape;
+static size_t size = DEFAULT_MEMORY_SIZE;
+static size_t p = 0;
+
+static void init(void) {
+    tape = calloc(size, 1);
+}
+
+static void fwd(size_t n) {
+    p += n;
+    while (p >= size) {
+        tape = realloc(tape, size * 2);
+        memset(tape + size, 0, size);
+        size *= 2;
+    }
+}
+";
+
+const FIXED_MEMORY: &str = "
+static unsigned char tape[MEMORY_SIZE];
+static const size_t size = MEMORY_SIZE;
+static size_t p = 0;
+
+static void init(void) {
+}
+
+static void fwd(size_t n) {
+    p = (p + n % size) % size;
+}
+";
+
+/// Writing and reading cells on `stdout` and `stdin`, also used by the runtime of the
+/// Cranelift compiler.
+pub(crate) const IO: &str = "
+/* cells are printed as unicode code points, just like the interpreter does */
+static void put_cell(unsigned char c) {
+    if (c < 0x80) {
+        putchar(c);
+    } else {
+        putchar(0xC0 | (c >> 6));
+        putchar(0x80 | (c & 0x3F));
+    }
+}
+
+/* end of input, NUL and line breaks read as -1, which leaves the cell untouched */
+static int get_cell(void) {
+    int c = getchar();
+    if (c == EOF || c == 0 || c == '\\n' || c == '\\r') {
+        return -1;
+    }
+    return c;
+}
+";
+
+const RUNTIME: &str = "
+static void bak(size_t n) {
+    p = (p + size - n % size) % size;
+}
+
+static void input(void) {
+    int c = get_cell();
+    if (c >= 0) {
+        tape[p] = (unsigned char)c;
+    }
+}
+
+int main(void) {
+    init();
+    srand((unsigned)time(NULL));
+";
+
+/// Compiles instructions into a standalone C program.
+///
+/// The program reads from `stdin` and writes to `stdout` with the same rules as
+/// [`default_input_stream`](crate::interpreter::default_input_stream) and
+/// [`default_output_stream`](crate::interpreter::default_output_stream), and uses `rand()` for
+/// `RND`.
+pub struct CCompiler {
+    memory: MemoryModel,
+}
+
+impl CCompiler {
+    pub fn new() -> Self {
+        Self {
+            memory: MemoryModel::Dynamic,
+        }
+    }
+
+    pub fn with_memory(self, memory: MemoryModel) -> Self {
+        Self { memory }
+    }
+
+    /// Fails if an `IF` or `EIF` has no match.
+    pub fn compile(&self, instructions: &[Instruction]) -> Result<String, ParseError> {
+        build_jump_table(instructions)?;
+
+        let mut result = String::from(PRELUDE);
+        match self.memory {
+            MemoryModel::Dynamic => result.push_str(
+                &DYNAMIC_MEMORY.replace("DEFAULT_MEMORY_SIZE", &DEFAULT_MEMORY_SIZE.to_string()),
+            ),
+            MemoryModel::Fixed(size) => {
+                result.push_str(&FIXED_MEMORY.replace("MEMORY_SIZE", &size.to_string()))
+            }
+        }
+        result.push_str(IO);
+        result.push_str(RUNTIME);
+
+        let targets = find_jump_targets(instructions);
+        let label = |n: usize| format!("l{}", n.min(instructions.len()));
+
+        let mut depth = 1;
+        for (i, instruction) in instructions.iter().enumerate() {
+            if targets[i] {
+                writeln!(result, "{}:;", label(i)).unwrap();
+            }
+
+            if *instruction == Instruction::EIF {
+                depth -= 1;
+            }
+            let indent = "    ".repeat(depth);
+
+            let _ = match instruction {
+                Instruction::END => writeln!(result, "{}return 0;", indent),
+                Instruction::IF => {
+                    depth += 1;
+                    writeln!(result, "{}while (tape[p]) {{", indent)
+                }
+                Instruction::EIF => writeln!(result, "{}}}", indent),
+                Instruction::INC(n) => writeln!(result, "{}tape[p] += {};", indent, n),
+                Instruction::DEC(n) => writeln!(result, "{}tape[p] -= {};", indent, n),
+                Instruction::FWD(n) => writeln!(result, "{}fwd({});", indent, n),
+                Instruction::BAK(n) => writeln!(result, "{}bak({});", indent, n),
+                Instruction::OUT => writeln!(result, "{}put_cell(tape[p]);", indent),
+                Instruction::IN => writeln!(result, "{}input();", indent),
+                Instruction::RND => {
+                    writeln!(result, "{}tape[p] = (unsigned char)rand();", indent)
+                }
+                Instruction::JIZ(n) => {
+                    writeln!(result, "{}if (!tape[p]) goto {};", indent, label(*n))
+                }
+                Instruction::JNZ(n) => {
+                    writeln!(result, "{}if (tape[p]) goto {};", indent, label(*n))
+                }
+                Instruction::JMP(n) => writeln!(result, "{}goto {};", indent, label(*n)),
+                Instruction::SCF(n) => writeln!(result, "{}while (tape[p]) fwd({});", indent, n),
+                Instruction::SCB(n) => writeln!(result, "{}while (tape[p]) bak({});", indent, n),
+                Instruction::PUT(n) => writeln!(result, "{}put_cell({});", indent, n),
+                Instruction::SET(n) => writeln!(result, "{}tape[p] = {};", indent, n),
+            };
+        }
+
+        if targets[instructions.len()] {
+            writeln!(result, "{}:;", label(instructions.len())).unwrap();
+        }
+        result.push_str("    return 0;\n}\n");
+
+        Ok(result)
+    }
+}
+
+impl Default for CCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CCompiler;
+    use crate::{
+        compiler::MemoryModel,
+        instruction::Instruction,
+        interpreter::{BoundedMemory, Interpreter},
+        optimizer::{Optimize, Optimizer},
+        parser::{ParseError, Parser},
+        samples::{HELLO_WORLD, HELLO_WORLD_SHORTEST},
+    };
+    use std::{
+        io::Write,
+        path::PathBuf,
+        process::{Command, Stdio},
+    };
+
+    /// Compiles the C program with `cc` and runs it.
+    fn compile_and_run(source: &str, name: &str, input: &[u8]) -> Vec<u8> {
+        let directory = std::env::temp_dir().join(format!("poetic_c_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let source_path = directory.join(format!("{}.c", name));
+        let binary_path: PathBuf = directory.join(name);
+        std::fs::write(&source_path, source).unwrap();
+
+        let status = Command::new("cc")
+            .arg("-O2")
+            .arg("-o")
+            .arg(&binary_path)
+            .arg(&source_path)
+            .status()
+            .expect("cc is needed to run this test");
+        assert!(
+            status.success(),
+            "generated C failed to compile:\n{}",
+            source
+        );
+
+        let mut child = Command::new(&binary_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        output.stdout
+    }
+
+    fn interpret(instructions: Vec<Instruction>, memory: MemoryModel, input: &[u8]) -> Vec<u8> {
+        let mut input = input.to_vec();
+        let mut output = String::new();
+        let get_input = move || match input.is_empty() {
+            true => None,
+            false => match input.remove(0) {
+                0 | b'\n' | b'\r' => None,
+                c => Some(c),
+            },
+        };
+        let put_output = |s: String| output.push_str(&s);
+        let interpreter = Interpreter::new(instructions).unwrap();
+        match memory {
+            MemoryModel::Dynamic => interpreter.with_io(get_input, put_output).run(),
+            MemoryModel::Fixed(size) => interpreter
+                .with_memory(BoundedMemory::new(size.get()))
+                .with_io(get_input, put_output)
+                .run(),
+        }
+
+        output.into_bytes()
+    }
+
+    fn assert_same_output(name: &str, source: &str, memory: MemoryModel, input: &[u8]) {
+        let instructions = Parser::parse(source).unwrap();
+        let expected = interpret(instructions.clone(), memory, input);
+
+        for (suffix, program) in [
+            ("plain", instructions.clone()),
+            ("optimized", Optimizer::new().optimize(&instructions)),
+        ] {
+            let c = CCompiler::new()
+                .with_memory(memory)
+                .compile(&program)
+                .unwrap();
+            let actual = compile_and_run(&c, &format!("{}_{}", name, suffix), input);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a C compiler"]
+    fn hello_world() {
+        assert_same_output("hello_world", HELLO_WORLD, MemoryModel::Dynamic, b"");
+    }
+
+    #[test]
+    #[ignore = "needs a C compiler"]
+    fn hello_world_shortest_fixed_memory() {
+        assert_same_output(
+            "hello_world_shortest",
+            HELLO_WORLD_SHORTEST,
+            MemoryModel::fixed(100).unwrap(),
+            b"",
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a C compiler"]
+    fn echo_until_line_break() {
+        let instructions = vec![
+            Instruction::INC(1),
+            Instruction::IF,
+            Instruction::SET(0),
+            Instruction::IN,
+            Instruction::OUT,
+            Instruction::EIF,
+            Instruction::PUT(0xE9),
+        ];
+        let expected = interpret(instructions.clone(), MemoryModel::Dynamic, b"poem\nrest");
+        assert_eq!(expected, "poem\0\u{e9}".as_bytes());
+
+        let c = CCompiler::new().compile(&instructions).unwrap();
+        assert_eq!(compile_and_run(&c, "echo", b"poem\nrest"), expected);
+    }
+
+    #[test]
+    fn jumps_use_labels() {
+        let instructions = vec![
+            Instruction::JIZ(2),
+            Instruction::JNZ(0),
+            Instruction::JMP(5),
+        ];
+        let c = CCompiler::new().compile(&instructions).unwrap();
+        assert!(c.contains("l0:;\n    if (!tape[p]) goto l2;"));
+        assert!(c.contains("l2:;\n    goto l3;\nl3:;\n    return 0;"));
+    }
+
+    #[test]
+    fn unbalanced_loops_rejected() {
+        let compiler = CCompiler::new();
+        assert_eq!(
+            compiler.compile(&[Instruction::EIF]),
+            Err(ParseError::MissingIf)
+        );
+        assert_eq!(
+            compiler.compile(&[Instruction::IF, Instruction::OUT]),
+            Err(ParseError::MissingEif)
+        );
+    }
+}
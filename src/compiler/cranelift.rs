@@ -0,0 +1,740 @@
+use super::{c::IO, MemoryModel};
+use crate::{
+    instruction::Instruction,
+    interpreter::{build_jump_table, raw::RawMemory, Interpreter, DEFAULT_MEMORY_SIZE},
+    native::{self, Context},
+    parser::ParseError,
+};
+use cranelift_codegen::{
+    entity::EntityRef,
+    ir::{condcodes::IntCC, types, AbiParam, InstBuilder, MemFlags, Signature, Type, Value},
+    isa::OwnedTargetIsa,
+    settings::{self, Configurable},
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Switch, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module, ModuleError};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+use rand::RngCore;
+
+const C_RUNTIME: &str = "#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+struct context {
+    unsigned char *base;
+    size_t pointer;
+    size_t length;
+    void *data;
+    void (*output)(struct context *, uint32_t);
+    int32_t (*input)(struct context *);
+    uint32_t (*random)(struct context *);
+    void (*seek)(struct context *, intptr_t);
+};
+
+extern size_t FUNCTION_NAME(struct context *context, size_t entry);
+";
+
+const CALLBACKS: &str = "
+static void output(struct context *context, uint32_t c) {
+    put_cell((unsigned char)c);
+}
+
+static int32_t input(struct context *context) {
+    return get_cell();
+}
+
+static uint32_t random_byte(struct context *context) {
+    return rand() & 0xFF;
+}
+
+static void bak(struct context *context, size_t n) {
+    context->pointer = (context->pointer + context->length - n % context->length) % context->length;
+}
+";
+
+const DYNAMIC_SEEK: &str = "
+static void seek(struct context *context, intptr_t delta) {
+    if (delta < 0) {
+        bak(context, (size_t)-delta);
+        return;
+    }
+
+    context->pointer += (size_t)delta;
+    while (context->pointer >= context->length) {
+        context->base = realloc(context->base, context->length * 2);
+        memset(context->base + context->length, 0, context->length);
+        context->length *= 2;
+    }
+}
+";
+
+const FIXED_SEEK: &str = "
+static void seek(struct context *context, intptr_t delta) {
+    if (delta < 0) {
+        bak(context, (size_t)-delta);
+        return;
+    }
+
+    context->pointer = (context->pointer + (size_t)delta % context->length) % context->length;
+}
+";
+
+const MAIN: &str = "
+int main(void) {
+    struct context context = {
+        calloc(MEMORY_SIZE, 1), 0, MEMORY_SIZE, NULL, output, input, random_byte, seek,
+    };
+    srand((unsigned)time(NULL));
+    FUNCTION_NAME(&context, 0);
+    return 0;
+}
+";
+
+#[derive(Debug)]
+pub enum CraneliftError {
+    InvalidProgram(ParseError),
+    Isa(String),
+    Module(Box<ModuleError>),
+    Object(String),
+}
+
+impl std::fmt::Display for CraneliftError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CraneliftError::InvalidProgram(error) => write!(f, "Invalid program: {}", error),
+            CraneliftError::Isa(error) => write!(f, "Unsupported target: {}", error),
+            CraneliftError::Module(error) => write!(f, "Code generation failed: {}", error),
+            CraneliftError::Object(error) => write!(f, "Failed to write object file: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for CraneliftError {}
+
+impl From<ModuleError> for CraneliftError {
+    fn from(error: ModuleError) -> Self {
+        CraneliftError::Module(Box::new(error))
+    }
+}
+
+/// Lowers instructions to Cranelift IR, which is either compiled in memory and run on an
+/// [`Interpreter`], or written to an object file.
+///
+/// The generated function has the signature
+/// `size_t run(struct context *context, size_t entry)`: it starts at instruction `entry` and
+/// returns the index of the instruction it stopped at. The context holds the tape and the
+/// callbacks for input, output, `RND` and for moving the pointer off the tape, see
+/// [`c_runtime`](Self::c_runtime) for its layout.
+pub struct CraneliftCompiler {
+    memory: MemoryModel,
+    function_name: String,
+}
+
+/// A program compiled in memory by [`CraneliftCompiler::jit`].
+pub struct CraneliftProgram {
+    module: Option<JITModule>,
+    function: *const u8,
+    len: usize,
+}
+
+impl CraneliftCompiler {
+    pub fn new() -> Self {
+        Self {
+            memory: MemoryModel::Dynamic,
+            function_name: "poem_run".to_string(),
+        }
+    }
+
+    /// The memory model of the tape set up by [`c_runtime`](Self::c_runtime), programs compiled
+    /// with [`jit`](Self::jit) use the memory of the interpreter they are run on.
+    pub fn with_memory(self, memory: MemoryModel) -> Self {
+        Self { memory, ..self }
+    }
+
+    /// The symbol the function is exported as in object files.
+    pub fn with_function_name(self, function_name: &str) -> Self {
+        Self {
+            function_name: function_name.to_string(),
+            ..self
+        }
+    }
+
+    pub fn jit(&self, instructions: &[Instruction]) -> Result<CraneliftProgram, CraneliftError> {
+        let isa = native_isa(false)?;
+        let mut module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+        let id = define_function(&mut module, &self.function_name, instructions)?;
+        module.finalize_definitions()?;
+
+        Ok(CraneliftProgram {
+            function: module.get_finalized_function(id),
+            module: Some(module),
+            len: instructions.len(),
+        })
+    }
+
+    /// Compiles the instructions into a relocatable object file for the host, exporting the
+    /// function under the configured name.
+    pub fn compile_object(&self, instructions: &[Instruction]) -> Result<Vec<u8>, CraneliftError> {
+        let isa = native_isa(true)?;
+        let builder = ObjectBuilder::new(isa, "poem", default_libcall_names())?;
+        let mut module = ObjectModule::new(builder);
+        define_function(&mut module, &self.function_name, instructions)?;
+
+        module
+            .finish()
+            .emit()
+            .map_err(|error| CraneliftError::Object(error.to_string()))
+    }
+
+    /// C source of a `main` function setting up the context with a tape following the memory
+    /// model and `stdin`, `stdout` and `rand()` for I/O, with the same rules as
+    /// [`CCompiler`](super::CCompiler). Compiling it together with the object file gives a
+    /// native executable.
+    pub fn c_runtime(&self) -> String {
+        let (seek, size) = match self.memory {
+            MemoryModel::Dynamic => (DYNAMIC_SEEK, DEFAULT_MEMORY_SIZE),
+            MemoryModel::Fixed(size) => (FIXED_SEEK, size.get()),
+        };
+
+        [C_RUNTIME, IO, CALLBACKS, seek, MAIN]
+            .concat()
+            .replace("FUNCTION_NAME", &self.function_name)
+            .replace("MEMORY_SIZE", &size.to_string())
+    }
+}
+
+impl Default for CraneliftCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CraneliftProgram {
+    /// Runs the program with the memory, input, output and random number generator of the
+    /// interpreter, starting at its instruction pointer. The interpreter's own instructions are
+    /// not looked at, it is up to the caller that they match the compiled ones.
+    pub fn run<I, O, M, R>(&self, interpreter: &mut Interpreter<I, O, M, R>)
+    where
+        I: FnMut() -> Option<u8>,
+        O: FnMut(String),
+        M: RawMemory,
+        R: RngCore,
+    {
+        if interpreter.reached_end() {
+            return;
+        }
+
+        let entry = interpreter.instruction_pointer.min(self.len);
+        let mut context = Context::new(interpreter);
+
+        // SAFETY: the function was compiled for the host with the default calling convention,
+        // it only touches the tape inside the bounds stored in the context and reloads them
+        // after every callback that may have reallocated the memory
+        let stop = unsafe {
+            let function: unsafe extern "C" fn(*mut Context, usize) -> usize =
+                std::mem::transmute(self.function);
+            function(&mut context, entry)
+        };
+
+        context.finish(interpreter, stop);
+    }
+}
+
+impl Drop for CraneliftProgram {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            // SAFETY: the only pointer into the module is the function owned by this program
+            unsafe { module.free_memory() };
+        }
+    }
+}
+
+fn native_isa(pic: bool) -> Result<OwnedTargetIsa, CraneliftError> {
+    let mut flags = settings::builder();
+    let set = |flags: &mut settings::Builder, name, value| {
+        flags
+            .set(name, value)
+            .map_err(|error| CraneliftError::Isa(error.to_string()))
+    };
+    set(&mut flags, "opt_level", "speed")?;
+    set(&mut flags, "is_pic", if pic { "true" } else { "false" })?;
+
+    cranelift_native::builder()
+        .map_err(|error| CraneliftError::Isa(error.to_string()))?
+        .finish(settings::Flags::new(flags))
+        .map_err(|error| CraneliftError::Isa(error.to_string()))
+}
+
+fn define_function<T: Module>(
+    module: &mut T,
+    name: &str,
+    instructions: &[Instruction],
+) -> Result<FuncId, CraneliftError> {
+    let jump_table = build_jump_table(instructions).map_err(CraneliftError::InvalidProgram)?;
+    let pointer_type = module.target_config().pointer_type();
+
+    let mut signature = module.make_signature();
+    signature.params.push(AbiParam::new(pointer_type));
+    signature.params.push(AbiParam::new(pointer_type));
+    signature.returns.push(AbiParam::new(pointer_type));
+    let id = module.declare_function(name, Linkage::Export, &signature)?;
+
+    let mut context = module.make_context();
+    context.func.signature = signature;
+    let mut function_context = FunctionBuilderContext::new();
+    let builder = FunctionBuilder::new(&mut context.func, &mut function_context);
+    let call_conv = module.isa().default_call_conv();
+    FunctionTranslator::new(builder, pointer_type, call_conv).translate(instructions, &jump_table);
+
+    module.define_function(id, &mut context)?;
+    module.clear_context(&mut context);
+
+    Ok(id)
+}
+
+struct FunctionTranslator<'a> {
+    builder: FunctionBuilder<'a>,
+    pointer_type: Type,
+    call_conv: cranelift_codegen::isa::CallConv,
+    context: Value,
+    base: Variable,
+    pointer: Variable,
+    length: Variable,
+}
+
+impl<'a> FunctionTranslator<'a> {
+    fn new(
+        mut builder: FunctionBuilder<'a>,
+        pointer_type: Type,
+        call_conv: cranelift_codegen::isa::CallConv,
+    ) -> Self {
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        let context = builder.block_params(entry)[0];
+
+        let [base, pointer, length] = [0, 1, 2].map(Variable::new);
+        for variable in [base, pointer, length] {
+            builder.declare_var(variable, pointer_type);
+        }
+
+        let mut translator = Self {
+            builder,
+            pointer_type,
+            call_conv,
+            context,
+            base,
+            pointer,
+            length,
+        };
+        translator.load_tape();
+
+        translator
+    }
+
+    fn translate(mut self, instructions: &[Instruction], jump_table: &[usize]) {
+        let entry = self.builder.current_block().expect("entry block");
+        let entry_index = self.builder.block_params(entry)[1];
+
+        // one block per instruction and one for the end of the program
+        let blocks: Vec<_> = (0..=instructions.len())
+            .map(|_| self.builder.create_block())
+            .collect();
+        let target = |n: usize| blocks[n.min(instructions.len())];
+
+        let mut switch = Switch::new();
+        for (i, block) in blocks.iter().enumerate() {
+            switch.set_entry(i as u128, *block);
+        }
+        switch.emit(&mut self.builder, entry_index, blocks[instructions.len()]);
+
+        for (i, instruction) in instructions.iter().enumerate() {
+            self.builder.switch_to_block(blocks[i]);
+            let next = blocks[i + 1];
+            match *instruction {
+                Instruction::INC(n) => {
+                    let value = self.load_cell();
+                    let value = self.builder.ins().iadd_imm(value, n as i64);
+                    self.store_cell(value);
+                }
+                Instruction::DEC(n) => {
+                    let value = self.load_cell();
+                    let value = self.builder.ins().iadd_imm(value, -(n as i64));
+                    self.store_cell(value);
+                }
+                Instruction::SET(n) => {
+                    let value = self.builder.ins().iconst(types::I32, n as i64);
+                    self.store_cell(value);
+                }
+                Instruction::FWD(n) => self.move_pointer(n as i64),
+                Instruction::BAK(n) => self.move_pointer(-(n as i64)),
+                Instruction::SCF(n) | Instruction::SCB(n) => {
+                    let delta = match instruction {
+                        Instruction::SCF(_) => n as i64,
+                        _ => -(n as i64),
+                    };
+                    let body = self.builder.create_block();
+                    let value = self.load_cell();
+                    self.builder.ins().brif(value, body, &[], next, &[]);
+                    self.builder.switch_to_block(body);
+                    self.move_pointer(delta);
+                    self.builder.ins().jump(blocks[i], &[]);
+                    continue;
+                }
+                Instruction::OUT => {
+                    let value = self.load_cell();
+                    self.call(native::OUTPUT, &[value], false);
+                }
+                Instruction::PUT(n) => {
+                    let value = self.builder.ins().iconst(types::I32, n as i64);
+                    self.call(native::OUTPUT, &[value], false);
+                }
+                Instruction::IN => {
+                    let value = self.call(native::INPUT, &[], true).unwrap();
+                    let store = self.builder.create_block();
+                    let skip = self.builder.ins().icmp_imm(IntCC::SignedLessThan, value, 0);
+                    self.builder.ins().brif(skip, next, &[], store, &[]);
+                    self.builder.switch_to_block(store);
+                    self.store_cell(value);
+                }
+                Instruction::RND => {
+                    let value = self.call(native::RANDOM, &[], true).unwrap();
+                    self.store_cell(value);
+                }
+                Instruction::IF | Instruction::JIZ(_) => {
+                    let n = match *instruction {
+                        Instruction::JIZ(n) => n,
+                        _ => jump_table[i],
+                    };
+                    let value = self.load_cell();
+                    self.builder.ins().brif(value, next, &[], target(n), &[]);
+                    continue;
+                }
+                Instruction::EIF | Instruction::JNZ(_) => {
+                    let n = match *instruction {
+                        Instruction::JNZ(n) => n,
+                        _ => jump_table[i],
+                    };
+                    let value = self.load_cell();
+                    self.builder.ins().brif(value, target(n), &[], next, &[]);
+                    continue;
+                }
+                Instruction::JMP(n) => {
+                    self.builder.ins().jump(target(n), &[]);
+                    continue;
+                }
+                Instruction::END => {
+                    self.stop(i);
+                    continue;
+                }
+            }
+
+            self.builder.ins().jump(next, &[]);
+        }
+
+        self.builder.switch_to_block(blocks[instructions.len()]);
+        self.stop(instructions.len());
+
+        self.builder.seal_all_blocks();
+        self.builder.finalize();
+    }
+
+    fn flags() -> MemFlags {
+        MemFlags::trusted()
+    }
+
+    fn load_tape(&mut self) {
+        for (variable, offset) in [
+            (self.base, native::BASE),
+            (self.pointer, native::POINTER),
+            (self.length, native::LENGTH),
+        ] {
+            let value = self.builder.ins().load(
+                self.pointer_type,
+                Self::flags(),
+                self.context,
+                offset as i32,
+            );
+            self.builder.def_var(variable, value);
+        }
+    }
+
+    fn store_pointer(&mut self) {
+        let pointer = self.builder.use_var(self.pointer);
+        self.builder
+            .ins()
+            .store(Self::flags(), pointer, self.context, native::POINTER as i32);
+    }
+
+    fn cell_address(&mut self) -> Value {
+        let base = self.builder.use_var(self.base);
+        let pointer = self.builder.use_var(self.pointer);
+        self.builder.ins().iadd(base, pointer)
+    }
+
+    fn load_cell(&mut self) -> Value {
+        let address = self.cell_address();
+        self.builder
+            .ins()
+            .uload8(types::I32, Self::flags(), address, 0)
+    }
+
+    fn store_cell(&mut self, value: Value) {
+        let address = self.cell_address();
+        self.builder.ins().istore8(Self::flags(), value, address, 0);
+    }
+
+    /// Calls one of the callbacks of the context, passing the context as first argument.
+    fn call(&mut self, offset: usize, arguments: &[Value], returns: bool) -> Option<Value> {
+        let mut signature = Signature::new(self.call_conv);
+        signature.params.push(AbiParam::new(self.pointer_type));
+        for argument in arguments {
+            let ty = self.builder.func.dfg.value_type(*argument);
+            signature.params.push(AbiParam::new(ty));
+        }
+        if returns {
+            signature.returns.push(AbiParam::new(types::I32));
+        }
+        let signature = self.builder.import_signature(signature);
+
+        let callee = self.builder.ins().load(
+            self.pointer_type,
+            Self::flags(),
+            self.context,
+            offset as i32,
+        );
+        let mut call_arguments = vec![self.context];
+        call_arguments.extend_from_slice(arguments);
+        let call = self
+            .builder
+            .ins()
+            .call_indirect(signature, callee, &call_arguments);
+
+        self.builder.inst_results(call).first().copied()
+    }
+
+    /// Moves the pointer inline while it stays on the tape and through the seek callback
+    /// otherwise.
+    fn move_pointer(&mut self, delta: i64) {
+        let fast = self.builder.create_block();
+        let slow = self.builder.create_block();
+        let resume = self.builder.create_block();
+
+        let pointer = self.builder.use_var(self.pointer);
+        let moved = self.builder.ins().iadd_imm(pointer, delta);
+        let in_bounds = if delta >= 0 {
+            let length = self.builder.use_var(self.length);
+            self.builder
+                .ins()
+                .icmp(IntCC::UnsignedLessThan, moved, length)
+        } else {
+            self.builder
+                .ins()
+                .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, pointer, -delta)
+        };
+        self.builder.ins().brif(in_bounds, fast, &[], slow, &[]);
+
+        self.builder.switch_to_block(fast);
+        self.builder.def_var(self.pointer, moved);
+        self.builder.ins().jump(resume, &[]);
+
+        self.builder.switch_to_block(slow);
+        self.store_pointer();
+        let delta = self.builder.ins().iconst(self.pointer_type, delta);
+        self.call(native::SEEK, &[delta], false);
+        self.load_tape();
+        self.builder.ins().jump(resume, &[]);
+
+        self.builder.switch_to_block(resume);
+    }
+
+    fn stop(&mut self, index: usize) {
+        self.store_pointer();
+        let index = self.builder.ins().iconst(self.pointer_type, index as i64);
+        self.builder.ins().return_(&[index]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CraneliftCompiler;
+    use crate::{
+        compiler::MemoryModel,
+        instruction::Instruction,
+        interpreter::{FixedMemory, Interpreter, Memory},
+        optimizer::{Optimize, Optimizer, PartialEvaluator},
+        parser::{ParseError, Parser},
+        samples::HELLO_WORLD_SHORTEST,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    /// Runs the program with the interpreter and the compiled function on fixed memory,
+    /// returns the output after checking that both agree on it and on the memory.
+    fn run_both(instructions: &[Instruction], input: &[u8]) -> String {
+        let program = CraneliftCompiler::new().jit(instructions).unwrap();
+
+        let mut results = Vec::new();
+        for compiled in [false, true] {
+            let mut output = String::new();
+            let mut input = input.to_vec();
+            let mut interpreter = Interpreter::new(instructions.to_vec())
+                .unwrap()
+                .with_fixed_size_memory::<100>()
+                .with_io(
+                    || match input.is_empty() {
+                        true => None,
+                        false => Some(input.remove(0)),
+                    },
+                    |s: String| output.push_str(&s),
+                )
+                .with_rng(StdRng::seed_from_u64(5));
+            match compiled {
+                true => program.run(&mut interpreter),
+                false => interpreter.run(),
+            }
+            assert!(interpreter.has_ended());
+            let memory = interpreter.memory.raw_memory().to_vec();
+            let pointer = interpreter.memory.get_memory_pointer();
+            drop(interpreter);
+
+            results.push((output, memory, pointer));
+        }
+
+        assert_eq!(results[0], results[1]);
+        results.remove(0).0
+    }
+
+    #[test]
+    fn jit_hello_world_shortest() {
+        let instructions = Parser::parse(HELLO_WORLD_SHORTEST).unwrap();
+        for program in [
+            instructions.clone(),
+            Optimizer::new().optimize(&instructions),
+            PartialEvaluator::<FixedMemory<100>>::new().optimize(&instructions),
+        ] {
+            assert_eq!(run_both(&program, b""), "Hello, World!");
+        }
+    }
+
+    #[test]
+    fn jit_input_random_and_wrapping() {
+        let instructions = vec![
+            Instruction::IN,
+            Instruction::IF,
+            Instruction::OUT,
+            Instruction::BAK(7),
+            Instruction::RND,
+            Instruction::FWD(7),
+            Instruction::SET(0),
+            Instruction::IN,
+            Instruction::EIF,
+            Instruction::BAK(7),
+            Instruction::OUT,
+            Instruction::SCB(3),
+            Instruction::PUT(0xE9),
+            Instruction::END,
+            Instruction::OUT,
+        ];
+
+        assert!(run_both(&instructions, b"poem").starts_with("poem"));
+    }
+
+    #[test]
+    fn jit_dynamic_memory_grows() {
+        let mut instructions = vec![Instruction::INC(66)];
+        instructions.extend([Instruction::FWD(250); 40]);
+        instructions.extend([Instruction::INC(65), Instruction::OUT, Instruction::SCF(1)]);
+        instructions.extend([Instruction::BAK(250); 40]);
+        instructions.extend([Instruction::OUT, Instruction::BAK(1), Instruction::OUT]);
+        let program = CraneliftCompiler::new().jit(&instructions).unwrap();
+
+        let mut output = String::new();
+        let mut interpreter = Interpreter::new(instructions)
+            .unwrap()
+            .with_output(|s: String| output.push_str(&s));
+        program.run(&mut interpreter);
+        assert_eq!(interpreter.memory.get_memory_size(), 16384);
+        drop(interpreter);
+
+        assert_eq!(output, "A\0B");
+    }
+
+    #[test]
+    fn invalid_program_rejected() {
+        assert!(matches!(
+            CraneliftCompiler::new().jit(&[Instruction::EIF]),
+            Err(super::CraneliftError::InvalidProgram(ParseError::MissingIf))
+        ));
+    }
+
+    /// Links the object file with the C runtime using `cc` and runs it.
+    fn link_and_run(compiler: &CraneliftCompiler, object: &[u8], input: &[u8]) -> Vec<u8> {
+        let directory =
+            std::env::temp_dir().join(format!("poetic_cranelift_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let object_path = directory.join("poem.o");
+        let runtime_path = directory.join("runtime.c");
+        let binary_path = directory.join("poem");
+        std::fs::write(&object_path, object).unwrap();
+        std::fs::write(&runtime_path, compiler.c_runtime()).unwrap();
+
+        let output = Command::new("cc")
+            .arg("-o")
+            .arg(&binary_path)
+            .arg(&runtime_path)
+            .arg(&object_path)
+            .output()
+            .expect("cc is needed to run this test");
+        assert!(
+            output.status.success(),
+            "linking failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let mut child = Command::new(&binary_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        output.stdout
+    }
+
+    #[test]
+    #[ignore = "needs a C compiler"]
+    fn object_file_links_into_executable() {
+        let instructions = Parser::parse(HELLO_WORLD_SHORTEST).unwrap();
+        let compiler = CraneliftCompiler::new().with_memory(MemoryModel::fixed(100).unwrap());
+        let object = compiler.compile_object(&instructions).unwrap();
+
+        assert_eq!(link_and_run(&compiler, &object, b""), b"Hello, World!");
+
+        // echoes input until a line break, on memory growing past its initial size
+        let instructions = vec![
+            Instruction::FWD(200),
+            Instruction::INC(1),
+            Instruction::IF,
+            Instruction::SET(0),
+            Instruction::IN,
+            Instruction::OUT,
+            Instruction::EIF,
+            Instruction::PUT(0xE9),
+        ];
+        let compiler = CraneliftCompiler::new().with_function_name("echo");
+        let object = compiler.compile_object(&instructions).unwrap();
+
+        assert_eq!(
+            link_and_run(&compiler, &object, b"poem\nrest"),
+            "poem\0é".as_bytes()
+        );
+    }
+}
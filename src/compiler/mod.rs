@@ -0,0 +1,164 @@
+use crate::instruction::Instruction;
+use std::num::NonZeroUsize;
+
+mod c;
+#[cfg(feature = "cranelift")]
+mod cranelift;
+mod rust;
+#[cfg(feature = "wasm-compiler")]
+mod wasm;
+
+#[cfg(feature = "cranelift")]
+pub use self::cranelift::{CraneliftCompiler, CraneliftError, CraneliftProgram};
+pub use self::{c::CCompiler, rust::RustCompiler};
+#[cfg(feature = "wasm-compiler")]
+pub use self::wasm::WasmCompiler;
+
+/// Memory layout of a compiled program, mirroring the interpreter's
+/// [`DynamicMemory`](crate::interpreter::DynamicMemory) and
+/// [`FixedMemory`](crate::interpreter::FixedMemory).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MemoryModel {
+    /// Starts with 32 cells and doubles whenever the pointer moves past the end.
+    #[default]
+    Dynamic,
+    /// A fixed number of cells, the pointer wraps around at both ends.
+    Fixed(NonZeroUsize),
+}
+
+impl MemoryModel {
+    /// A fixed memory of `size` cells, `None` if `size` is zero.
+    pub fn fixed(size: usize) -> Option<Self> {
+        NonZeroUsize::new(size).map(Self::Fixed)
+    }
+}
+
+/// Marks every instruction that is the target of a `JIZ`, `JNZ` or `JMP`, with one extra entry
+/// for jumps past the end of the program.
+pub(crate) fn find_jump_targets(instructions: &[Instruction]) -> Vec<bool> {
+    let mut targets = vec![false; instructions.len() + 1];
+    for instruction in instructions {
+        if let Instruction::JIZ(n) | Instruction::JNZ(n) | Instruction::JMP(n) = instruction {
+            targets[(*n).min(instructions.len())] = true;
+        }
+    }
+
+    targets
+}
+
+/// Marks the first instruction of every basic block: jump targets and every instruction
+/// following a jump, with one extra entry for the end of the program. `jump_table` is the
+/// interpreter's table resolving `IF`/`EIF`.
+pub(crate) fn find_block_leaders(instructions: &[Instruction], jump_table: &[usize]) -> Vec<bool> {
+    let mut leaders = vec![false; instructions.len() + 1];
+    leaders[0] = true;
+    leaders[instructions.len()] = true;
+    for (i, instruction) in instructions.iter().enumerate() {
+        match *instruction {
+            Instruction::JIZ(n) | Instruction::JNZ(n) | Instruction::JMP(n) => {
+                leaders[n.min(instructions.len())] = true;
+                leaders[i + 1] = true;
+            }
+            Instruction::IF | Instruction::EIF => {
+                leaders[jump_table[i]] = true;
+                leaders[i + 1] = true;
+            }
+            Instruction::END => leaders[i + 1] = true,
+            _ => {}
+        }
+    }
+
+    leaders
+}
+
+/// Turns `JIZ`/`JNZ` pairs produced by the optimizer back into `IF`/`EIF` loops, so backends
+/// without `goto` can emit structured loops. Fails with the index of the first jump that does
+/// not form a properly nested loop.
+pub(crate) fn restore_loops(instructions: &[Instruction]) -> Result<Vec<Instruction>, usize> {
+    let mut result = Vec::with_capacity(instructions.len());
+    let mut open = Vec::new();
+    for (i, instruction) in instructions.iter().enumerate() {
+        match *instruction {
+            Instruction::IF => {
+                open.push(i);
+                result.push(Instruction::IF);
+            }
+            Instruction::EIF => {
+                let start = open.pop().ok_or(i)?;
+                if instructions[start] != Instruction::IF {
+                    return Err(start);
+                }
+                result.push(Instruction::EIF);
+            }
+            Instruction::JIZ(n) => {
+                if instructions.get(n) != Some(&Instruction::JNZ(i)) {
+                    return Err(i);
+                }
+                open.push(i);
+                result.push(Instruction::IF);
+            }
+            Instruction::JNZ(n) => {
+                if open.pop() != Some(n) || instructions[n] != Instruction::JIZ(i) {
+                    return Err(i);
+                }
+                result.push(Instruction::EIF);
+            }
+            Instruction::JMP(_) => return Err(i),
+            instruction => result.push(instruction),
+        }
+    }
+
+    match open.first() {
+        Some(&start) => Err(start),
+        None => Ok(result),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MemoryModel;
+    use crate::instruction::Instruction;
+
+    #[test]
+    fn test_fixed_memory_needs_a_cell() {
+        assert_eq!(MemoryModel::fixed(0), None);
+        assert!(matches!(
+            MemoryModel::fixed(100),
+            Some(MemoryModel::Fixed(size)) if size.get() == 100
+        ));
+
+        #[cfg(feature = "serde")]
+        assert!(serde_json::from_str::<MemoryModel>(r#"{"Fixed":0}"#).is_err());
+    }
+
+    #[test]
+    fn test_restore_loops() {
+        let instructions = vec![
+            Instruction::JIZ(4),
+            Instruction::JIZ(3),
+            Instruction::OUT,
+            Instruction::JNZ(1),
+            Instruction::JNZ(0),
+        ];
+        assert_eq!(
+            super::restore_loops(&instructions),
+            Ok(vec![
+                Instruction::IF,
+                Instruction::IF,
+                Instruction::OUT,
+                Instruction::EIF,
+                Instruction::EIF,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_restore_loops_rejects_other_jumps() {
+        let instructions = vec![Instruction::JIZ(2), Instruction::OUT, Instruction::OUT];
+        assert_eq!(super::restore_loops(&instructions), Err(0));
+
+        let instructions = vec![Instruction::OUT, Instruction::JMP(1), Instruction::OUT];
+        assert_eq!(super::restore_loops(&instructions), Err(1));
+    }
+}
@@ -0,0 +1,428 @@
+use super::{find_block_leaders, restore_loops, MemoryModel};
+use crate::{
+    instruction::Instruction,
+    interpreter::{build_jump_table, DEFAULT_MEMORY_SIZE},
+    parser::ParseError,
+};
+use std::fmt::Write;
+
+const DYNAMIC_MEMORY: &str = "
+    struct Tape {
+        cells: Vec<u8>,
+        pointer: usize,
+    }
+
+    impl Tape {
+        fn new() -> Self {
+            Self {
+                cells: vec![0; DEFAULT_MEMORY_SIZE],
+                pointer: 0,
+            }
+        }
+
+        fn fwd(&mut self, n: usize) {
+            self.pointer += n;
+            while self.pointer >= self.cells.len() {
+                self.cells.resize(self.cells.len() * 2, 0);
+            }
+        }
+";
+
+const FIXED_MEMORY: &str = "
+    struct Tape {
+        cells: Vec<u8>,
+        pointer: usize,
+    }
+
+    impl Tape {
+        fn new() -> Self {
+            Self {
+                cells: vec![0; MEMORY_SIZE],
+                pointer: 0,
+            }
+        }
+
+        fn fwd(&mut self, n: usize) {
+            self.pointer = (self.pointer + n) % self.cells.len();
+        }
+";
+
+const RUNTIME: &str = "
+        fn bak(&mut self, n: usize) {
+            let len = self.cells.len();
+            self.pointer = (self.pointer + len - n % len) % len;
+        }
+
+        fn get(&self) -> u8 {
+            self.cells[self.pointer]
+        }
+
+        fn set(&mut self, value: u8) {
+            self.cells[self.pointer] = value;
+        }
+
+        fn inc(&mut self, value: u8) {
+            self.cells[self.pointer] = self.cells[self.pointer].wrapping_add(value);
+        }
+
+        fn dec(&mut self, value: u8) {
+            self.cells[self.pointer] = self.cells[self.pointer].wrapping_sub(value);
+        }
+    }
+
+    /// End of input, NUL and line breaks leave the cell untouched.
+    fn read(input: &mut impl Read, tape: &mut Tape) {
+        let mut byte = [0];
+        if let Ok(1) = input.read(&mut byte) {
+            if !matches!(byte[0], 0 | b'\\n' | b'\\r') {
+                tape.set(byte[0]);
+            }
+        }
+    }
+
+    /// Cells are written as unicode characters, just like the interpreter does.
+    fn write(output: &mut impl Write, value: u8) -> io::Result<()> {
+        write!(output, \"{}\", value as char)
+    }
+
+    /// Runs the program, using a xorshift generator seeded from the system time for `RND`.
+    pub fn run(input: impl Read, output: impl Write) -> io::Result<()> {
+        let mut state = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0x2545_f491, |d| d.as_nanos() as u32)
+            | 1;
+        run_with_random(input, output, move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+    }
+
+    pub fn run_with_random(
+        mut input: impl Read,
+        output: impl Write,
+        mut random: impl FnMut() -> u8,
+    ) -> io::Result<()> {
+        let mut output = BufWriter::new(output);
+        let mut tape = Tape::new();
+";
+
+/// Compiles instructions into the source of a Rust module exposing
+/// `run(input: impl Read, output: impl Write) -> io::Result<()>`, meant to be generated by a
+/// `build.rs` and pulled in with `include!`.
+///
+/// Loops become `while` loops. Programs with jumps that do not form loops, like the output of
+/// the [`PartialEvaluator`](crate::optimizer::PartialEvaluator), are compiled into a state
+/// machine over their basic blocks instead.
+pub struct RustCompiler {
+    memory: MemoryModel,
+    module_name: String,
+}
+
+impl RustCompiler {
+    pub fn new() -> Self {
+        Self {
+            memory: MemoryModel::Dynamic,
+            module_name: "poem".to_string(),
+        }
+    }
+
+    pub fn with_memory(self, memory: MemoryModel) -> Self {
+        Self { memory, ..self }
+    }
+
+    pub fn with_module_name(self, module_name: &str) -> Self {
+        Self {
+            module_name: module_name.to_string(),
+            ..self
+        }
+    }
+
+    pub fn compile(&self, instructions: &[Instruction]) -> Result<String, ParseError> {
+        let mut result = format!(
+            "#[allow(dead_code, unreachable_code, unused_mut, unused_variables)]\npub mod {} {{\n    use std::io::{{self, BufWriter, Read, Write}};\n    use std::time::{{SystemTime, UNIX_EPOCH}};\n",
+            self.module_name
+        );
+        match self.memory {
+            MemoryModel::Dynamic => result.push_str(
+                &DYNAMIC_MEMORY.replace("DEFAULT_MEMORY_SIZE", &DEFAULT_MEMORY_SIZE.to_string()),
+            ),
+            MemoryModel::Fixed(size) => {
+                result.push_str(&FIXED_MEMORY.replace("MEMORY_SIZE", &size.to_string()))
+            }
+        }
+        result.push_str(RUNTIME);
+
+        match restore_loops(instructions) {
+            Ok(structured) => compile_structured(&mut result, &structured),
+            Err(_) => compile_state_machine(&mut result, instructions)?,
+        }
+
+        result.push_str("        output.flush()\n    }\n}\n");
+
+        Ok(result)
+    }
+}
+
+impl Default for RustCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Emits instructions that do not change the control flow, returns `false` for all others.
+fn compile_straight(result: &mut String, instruction: Instruction, indent: &str) -> bool {
+    let _ = match instruction {
+        Instruction::INC(n) => writeln!(result, "{}tape.inc({});", indent, n),
+        Instruction::DEC(n) => writeln!(result, "{}tape.dec({});", indent, n),
+        Instruction::FWD(n) => writeln!(result, "{}tape.fwd({});", indent, n),
+        Instruction::BAK(n) => writeln!(result, "{}tape.bak({});", indent, n),
+        Instruction::OUT => writeln!(result, "{}write(&mut output, tape.get())?;", indent),
+        Instruction::IN => writeln!(result, "{}read(&mut input, &mut tape);", indent),
+        Instruction::RND => writeln!(result, "{}tape.set(random());", indent),
+        Instruction::SCF(n) => writeln!(
+            result,
+            "{}while tape.get() != 0 {{\n{}    tape.fwd({});\n{}}}",
+            indent, indent, n, indent
+        ),
+        Instruction::SCB(n) => writeln!(
+            result,
+            "{}while tape.get() != 0 {{\n{}    tape.bak({});\n{}}}",
+            indent, indent, n, indent
+        ),
+        Instruction::PUT(n) => writeln!(result, "{}write(&mut output, {})?;", indent, n),
+        Instruction::SET(n) => writeln!(result, "{}tape.set({});", indent, n),
+        Instruction::END
+        | Instruction::IF
+        | Instruction::EIF
+        | Instruction::JIZ(_)
+        | Instruction::JNZ(_)
+        | Instruction::JMP(_) => return false,
+    };
+
+    true
+}
+
+fn compile_structured(result: &mut String, instructions: &[Instruction]) {
+    let mut depth = 2;
+    for instruction in instructions {
+        if *instruction == Instruction::EIF {
+            depth -= 1;
+        }
+        let indent = "    ".repeat(depth);
+
+        if compile_straight(result, *instruction, &indent) {
+            continue;
+        }
+
+        let _ = match instruction {
+            Instruction::IF => {
+                depth += 1;
+                writeln!(result, "{}while tape.get() != 0 {{", indent)
+            }
+            Instruction::EIF => writeln!(result, "{}}}", indent),
+            Instruction::END => writeln!(result, "{}return output.flush();", indent),
+            _ => unreachable!("jumps are restored into loops"),
+        };
+    }
+}
+
+fn compile_state_machine(
+    result: &mut String,
+    instructions: &[Instruction],
+) -> Result<(), ParseError> {
+    let jump_table = build_jump_table(instructions)?;
+    let leaders = find_block_leaders(instructions, &jump_table);
+
+    result.push_str("        let mut block = 0;\n        loop {\n            match block {\n");
+    let indent = "    ".repeat(5);
+    for (i, instruction) in instructions.iter().enumerate() {
+        if leaders[i] {
+            writeln!(result, "                {} => {{", i).unwrap();
+        }
+
+        if !compile_straight(result, *instruction, &indent) {
+            let _ = match *instruction {
+                Instruction::END => writeln!(result, "{}break;", indent),
+                Instruction::JMP(n) => writeln!(result, "{}block = {};", indent, n),
+                Instruction::IF => writeln!(
+                    result,
+                    "{}block = if tape.get() == 0 {{ {} }} else {{ {} }};",
+                    indent,
+                    jump_table[i],
+                    i + 1
+                ),
+                Instruction::EIF => writeln!(
+                    result,
+                    "{}block = if tape.get() != 0 {{ {} }} else {{ {} }};",
+                    indent,
+                    jump_table[i],
+                    i + 1
+                ),
+                Instruction::JIZ(n) => writeln!(
+                    result,
+                    "{}block = if tape.get() == 0 {{ {} }} else {{ {} }};",
+                    indent,
+                    n,
+                    i + 1
+                ),
+                Instruction::JNZ(n) => writeln!(
+                    result,
+                    "{}block = if tape.get() != 0 {{ {} }} else {{ {} }};",
+                    indent,
+                    n,
+                    i + 1
+                ),
+                _ => unreachable!(),
+            };
+        } else if leaders[i + 1] {
+            writeln!(result, "{}block = {};", indent, i + 1).unwrap();
+        }
+
+        if leaders[i + 1] {
+            result.push_str("                }\n");
+        }
+    }
+
+    result.push_str("                _ => break,\n            }\n        }\n");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::RustCompiler;
+    use crate::{
+        compiler::MemoryModel,
+        instruction::Instruction,
+        interpreter::{FixedMemory, Interpreter},
+        optimizer::{Optimize, Optimizer, PartialEvaluator},
+        parser::{ParseError, Parser},
+        samples::HELLO_WORLD_SHORTEST,
+    };
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    /// Compiles the module with a `main` calling it with `rustc` and runs it.
+    fn compile_and_run(module: &str, name: &str, input: &[u8]) -> Vec<u8> {
+        let directory = std::env::temp_dir().join(format!("poetic_rust_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let source_path = directory.join(format!("{}.rs", name));
+        let binary_path = directory.join(name);
+        std::fs::write(
+            &source_path,
+            format!(
+                "{}\nfn main() {{\n    poem::run(std::io::stdin(), std::io::stdout()).unwrap();\n}}\n",
+                module
+            ),
+        )
+        .unwrap();
+
+        let output = Command::new("rustc")
+            .arg("-O")
+            .arg("-o")
+            .arg(&binary_path)
+            .arg(&source_path)
+            .output()
+            .expect("rustc is needed to run this test");
+        assert!(
+            output.status.success(),
+            "generated Rust failed to compile:\n{}\n{}",
+            module,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let mut child = Command::new(&binary_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        output.stdout
+    }
+
+    #[test]
+    #[ignore = "needs rustc"]
+    fn hello_world_shortest_fixed_memory() {
+        let instructions = Parser::parse(HELLO_WORLD_SHORTEST).unwrap();
+        let compiler = RustCompiler::new().with_memory(MemoryModel::fixed(100).unwrap());
+
+        for (name, program) in [
+            ("structured", Optimizer::new().optimize(&instructions)),
+            (
+                "state_machine",
+                PartialEvaluator::<FixedMemory<100>>::new().optimize(&instructions),
+            ),
+        ] {
+            let module = compiler.compile(&program).unwrap();
+            assert_eq!(compile_and_run(&module, name, b""), b"Hello, World!");
+        }
+    }
+
+    /// Reads bytes until a line break and echoes them, entering the loop through a jump.
+    fn echo_with_jump() -> Vec<Instruction> {
+        vec![
+            Instruction::INC(1),
+            Instruction::JMP(4),
+            Instruction::IF,
+            Instruction::SET(0),
+            Instruction::IN,
+            Instruction::OUT,
+            Instruction::EIF,
+            Instruction::PUT(0xE9),
+        ]
+    }
+
+    #[test]
+    fn jumps_into_loops_use_a_state_machine() {
+        let module = RustCompiler::new().compile(&echo_with_jump()).unwrap();
+        assert!(module.contains("let mut block = 0;"));
+    }
+
+    #[test]
+    #[ignore = "needs rustc"]
+    fn input_and_jumps_into_loops() {
+        let instructions = echo_with_jump();
+        let mut expected = String::new();
+        let mut input = b"poem\nrest".to_vec();
+        Interpreter::new(instructions.clone())
+            .unwrap()
+            .with_io(
+                || match input.remove(0) {
+                    b'\n' => None,
+                    c => Some(c),
+                },
+                |s: String| expected.push_str(&s),
+            )
+            .run();
+
+        let module = RustCompiler::new().compile(&instructions).unwrap();
+        assert_eq!(
+            compile_and_run(&module, "jumps", b"poem\nrest"),
+            expected.as_bytes()
+        );
+    }
+
+    #[test]
+    fn module_name_is_used() {
+        let module = RustCompiler::new()
+            .with_module_name("hello")
+            .compile(&[Instruction::OUT])
+            .unwrap();
+        assert!(module.contains("pub mod hello {"));
+    }
+
+    #[test]
+    fn unbalanced_jumps_are_rejected() {
+        let instructions = vec![Instruction::JMP(0), Instruction::IF];
+        assert_eq!(
+            RustCompiler::new().compile(&instructions),
+            Err(ParseError::MissingEif)
+        );
+    }
+}
@@ -0,0 +1,632 @@
+use super::{find_block_leaders, restore_loops, MemoryModel};
+use crate::{
+    instruction::Instruction,
+    interpreter::{build_jump_table, DEFAULT_MEMORY_SIZE},
+    parser::ParseError,
+};
+use wasm_encoder::{
+    BlockType, CodeSection, ConstExpr, EntityType, ExportKind, ExportSection, Function,
+    FunctionSection, GlobalSection, GlobalType, ImportSection, Instruction as Wasm, MemArg,
+    MemorySection, MemoryType, Module, TypeSection, ValType,
+};
+
+const PAGE_SIZE: usize = 65536;
+
+// type indices
+const READ_TYPE: u32 = 0;
+const WRITE_TYPE: u32 = 1;
+const RUN_TYPE: u32 = 2;
+
+// function indices, imported functions come first
+const READ_BYTE: u32 = 0;
+const WRITE_BYTE: u32 = 1;
+const RANDOM: u32 = 2;
+const FWD: u32 = 3;
+const BAK: u32 = 4;
+const READ: u32 = 5;
+const WRITE: u32 = 6;
+const RUN: u32 = 7;
+
+// global indices
+const POINTER: u32 = 0;
+const LENGTH: u32 = 1;
+
+const CELL: MemArg = MemArg {
+    offset: 0,
+    align: 0,
+    memory_index: 0,
+};
+
+/// Compiles instructions into a WebAssembly module using its linear memory as the tape.
+///
+/// The module imports three functions from `env`:
+/// - `read_byte: () -> i32` returning the next input byte or a negative value at the end of
+///   input; end of input, NUL and line breaks leave the cell untouched, just like in the
+///   interpreter
+/// - `write_byte: (i32) -> ()` receiving the output as UTF-8 encoded bytes
+/// - `random: () -> i32` of which only the lowest byte is used
+///
+/// It exports the `run: () -> ()` function, the `memory` holding the tape and the `pointer`
+/// and `length` globals describing it.
+pub struct WasmCompiler {
+    memory: MemoryModel,
+}
+
+impl WasmCompiler {
+    pub fn new() -> Self {
+        Self {
+            memory: MemoryModel::Dynamic,
+        }
+    }
+
+    pub fn with_memory(self, memory: MemoryModel) -> Self {
+        Self { memory }
+    }
+
+    pub fn compile(&self, instructions: &[Instruction]) -> Result<Vec<u8>, ParseError> {
+        let (length, pages) = match self.memory {
+            MemoryModel::Dynamic => (DEFAULT_MEMORY_SIZE, 1),
+            MemoryModel::Fixed(size) => (size.get(), size.get().div_ceil(PAGE_SIZE)),
+        };
+
+        let mut types = TypeSection::new();
+        types.ty().function([], [ValType::I32]);
+        types.ty().function([ValType::I32], []);
+        types.ty().function([], []);
+
+        let mut imports = ImportSection::new();
+        imports.import("env", "read_byte", EntityType::Function(READ_TYPE));
+        imports.import("env", "write_byte", EntityType::Function(WRITE_TYPE));
+        imports.import("env", "random", EntityType::Function(READ_TYPE));
+
+        let mut functions = FunctionSection::new();
+        for ty in [WRITE_TYPE, WRITE_TYPE, RUN_TYPE, WRITE_TYPE, RUN_TYPE] {
+            functions.function(ty);
+        }
+
+        let mut memories = MemorySection::new();
+        memories.memory(MemoryType {
+            minimum: pages as u64,
+            maximum: None,
+            memory64: false,
+            shared: false,
+            page_size_log2: None,
+        });
+
+        let mut globals = GlobalSection::new();
+        for value in [0, length as i32] {
+            globals.global(
+                GlobalType {
+                    val_type: ValType::I32,
+                    mutable: true,
+                    shared: false,
+                },
+                &ConstExpr::i32_const(value),
+            );
+        }
+
+        let mut exports = ExportSection::new();
+        exports.export("run", ExportKind::Func, RUN);
+        exports.export("memory", ExportKind::Memory, 0);
+        exports.export("pointer", ExportKind::Global, POINTER);
+        exports.export("length", ExportKind::Global, LENGTH);
+
+        let mut run = Function::new([(1, ValType::I32)]);
+        match restore_loops(instructions) {
+            Ok(structured) => compile_structured(&mut run, &structured),
+            Err(_) => compile_state_machine(&mut run, instructions)?,
+        }
+        run.instruction(&Wasm::End);
+
+        let mut code = CodeSection::new();
+        code.function(&match self.memory {
+            MemoryModel::Dynamic => dynamic_fwd(),
+            MemoryModel::Fixed(_) => fixed_fwd(),
+        });
+        code.function(&bak());
+        code.function(&read());
+        code.function(&write());
+        code.function(&run);
+
+        let mut module = Module::new();
+        module
+            .section(&types)
+            .section(&imports)
+            .section(&functions)
+            .section(&memories)
+            .section(&globals)
+            .section(&exports)
+            .section(&code);
+
+        Ok(module.finish())
+    }
+}
+
+impl Default for WasmCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Moves the pointer forward, doubling the tape and growing the memory when it moves past the
+/// end.
+fn dynamic_fwd() -> Function {
+    let mut f = Function::new([]);
+    for instruction in [
+        Wasm::GlobalGet(POINTER),
+        Wasm::LocalGet(0),
+        Wasm::I32Add,
+        Wasm::GlobalSet(POINTER),
+        Wasm::Block(BlockType::Empty),
+        Wasm::Loop(BlockType::Empty),
+        Wasm::GlobalGet(POINTER),
+        Wasm::GlobalGet(LENGTH),
+        Wasm::I32LtU,
+        Wasm::BrIf(1),
+        Wasm::GlobalGet(LENGTH),
+        Wasm::I32Const(1),
+        Wasm::I32Shl,
+        Wasm::GlobalSet(LENGTH),
+        // grown memory is zeroed, the tape never shrinks so new cells are always zero
+        Wasm::GlobalGet(LENGTH),
+        Wasm::MemorySize(0),
+        Wasm::I32Const(16),
+        Wasm::I32Shl,
+        Wasm::I32GtU,
+        Wasm::If(BlockType::Empty),
+        Wasm::MemorySize(0),
+        Wasm::MemoryGrow(0),
+        Wasm::I32Const(-1),
+        Wasm::I32Eq,
+        Wasm::If(BlockType::Empty),
+        Wasm::Unreachable,
+        Wasm::End,
+        Wasm::End,
+        Wasm::Br(0),
+        Wasm::End,
+        Wasm::End,
+        Wasm::End,
+    ] {
+        f.instruction(&instruction);
+    }
+
+    f
+}
+
+fn fixed_fwd() -> Function {
+    let mut f = Function::new([]);
+    for instruction in [
+        Wasm::GlobalGet(POINTER),
+        Wasm::LocalGet(0),
+        Wasm::I32Add,
+        Wasm::GlobalGet(LENGTH),
+        Wasm::I32RemU,
+        Wasm::GlobalSet(POINTER),
+        Wasm::End,
+    ] {
+        f.instruction(&instruction);
+    }
+
+    f
+}
+
+/// Moves the pointer back, wrapping around at the start of the tape.
+fn bak() -> Function {
+    let mut f = Function::new([]);
+    for instruction in [
+        Wasm::GlobalGet(POINTER),
+        Wasm::GlobalGet(LENGTH),
+        Wasm::I32Add,
+        Wasm::LocalGet(0),
+        Wasm::GlobalGet(LENGTH),
+        Wasm::I32RemU,
+        Wasm::I32Sub,
+        Wasm::GlobalGet(LENGTH),
+        Wasm::I32RemU,
+        Wasm::GlobalSet(POINTER),
+        Wasm::End,
+    ] {
+        f.instruction(&instruction);
+    }
+
+    f
+}
+
+/// Stores the next input byte, unless the input ended or it is a NUL or line break.
+fn read() -> Function {
+    let mut f = Function::new([(1, ValType::I32)]);
+    for instruction in [
+        Wasm::Call(READ_BYTE),
+        Wasm::LocalTee(0),
+        Wasm::I32Const(0),
+        Wasm::I32GtS,
+        Wasm::LocalGet(0),
+        Wasm::I32Const(b'\n' as i32),
+        Wasm::I32Ne,
+        Wasm::I32And,
+        Wasm::LocalGet(0),
+        Wasm::I32Const(b'\r' as i32),
+        Wasm::I32Ne,
+        Wasm::I32And,
+        Wasm::If(BlockType::Empty),
+        Wasm::GlobalGet(POINTER),
+        Wasm::LocalGet(0),
+        Wasm::I32Store8(CELL),
+        Wasm::End,
+        Wasm::End,
+    ] {
+        f.instruction(&instruction);
+    }
+
+    f
+}
+
+/// Writes a cell as the UTF-8 encoding of the unicode character it represents, just like the
+/// interpreter does.
+fn write() -> Function {
+    let mut f = Function::new([]);
+    for instruction in [
+        Wasm::LocalGet(0),
+        Wasm::I32Const(0x80),
+        Wasm::I32LtU,
+        Wasm::If(BlockType::Empty),
+        Wasm::LocalGet(0),
+        Wasm::Call(WRITE_BYTE),
+        Wasm::Return,
+        Wasm::End,
+        Wasm::LocalGet(0),
+        Wasm::I32Const(6),
+        Wasm::I32ShrU,
+        Wasm::I32Const(0xC0),
+        Wasm::I32Or,
+        Wasm::Call(WRITE_BYTE),
+        Wasm::LocalGet(0),
+        Wasm::I32Const(0x3F),
+        Wasm::I32And,
+        Wasm::I32Const(0x80),
+        Wasm::I32Or,
+        Wasm::Call(WRITE_BYTE),
+        Wasm::End,
+    ] {
+        f.instruction(&instruction);
+    }
+
+    f
+}
+
+fn load_cell(f: &mut Function) {
+    f.instruction(&Wasm::GlobalGet(POINTER));
+    f.instruction(&Wasm::I32Load8U(CELL));
+}
+
+/// Emits instructions that do not change the control flow, returns `false` for all others.
+fn compile_straight(f: &mut Function, instruction: Instruction) -> bool {
+    match instruction {
+        Instruction::INC(n) | Instruction::DEC(n) => {
+            f.instruction(&Wasm::GlobalGet(POINTER));
+            load_cell(f);
+            f.instruction(&Wasm::I32Const(n as i32));
+            f.instruction(&match instruction {
+                Instruction::INC(_) => Wasm::I32Add,
+                _ => Wasm::I32Sub,
+            });
+            f.instruction(&Wasm::I32Store8(CELL));
+        }
+        Instruction::FWD(n) => {
+            f.instruction(&Wasm::I32Const(n as i32));
+            f.instruction(&Wasm::Call(FWD));
+        }
+        Instruction::BAK(n) => {
+            f.instruction(&Wasm::I32Const(n as i32));
+            f.instruction(&Wasm::Call(BAK));
+        }
+        Instruction::OUT => {
+            load_cell(f);
+            f.instruction(&Wasm::Call(WRITE));
+        }
+        Instruction::IN => {
+            f.instruction(&Wasm::Call(READ));
+        }
+        Instruction::RND => {
+            f.instruction(&Wasm::GlobalGet(POINTER));
+            f.instruction(&Wasm::Call(RANDOM));
+            f.instruction(&Wasm::I32Store8(CELL));
+        }
+        Instruction::SCF(n) | Instruction::SCB(n) => {
+            f.instruction(&Wasm::Block(BlockType::Empty));
+            f.instruction(&Wasm::Loop(BlockType::Empty));
+            load_cell(f);
+            f.instruction(&Wasm::I32Eqz);
+            f.instruction(&Wasm::BrIf(1));
+            f.instruction(&Wasm::I32Const(n as i32));
+            f.instruction(&Wasm::Call(match instruction {
+                Instruction::SCF(_) => FWD,
+                _ => BAK,
+            }));
+            f.instruction(&Wasm::Br(0));
+            f.instruction(&Wasm::End);
+            f.instruction(&Wasm::End);
+        }
+        Instruction::PUT(n) => {
+            f.instruction(&Wasm::I32Const(n as i32));
+            f.instruction(&Wasm::Call(WRITE));
+        }
+        Instruction::SET(n) => {
+            f.instruction(&Wasm::GlobalGet(POINTER));
+            f.instruction(&Wasm::I32Const(n as i32));
+            f.instruction(&Wasm::I32Store8(CELL));
+        }
+        Instruction::END
+        | Instruction::IF
+        | Instruction::EIF
+        | Instruction::JIZ(_)
+        | Instruction::JNZ(_)
+        | Instruction::JMP(_) => return false,
+    }
+
+    true
+}
+
+fn compile_structured(f: &mut Function, instructions: &[Instruction]) {
+    for instruction in instructions {
+        if compile_straight(f, *instruction) {
+            continue;
+        }
+
+        match instruction {
+            Instruction::IF => {
+                f.instruction(&Wasm::Block(BlockType::Empty));
+                load_cell(f);
+                f.instruction(&Wasm::I32Eqz);
+                f.instruction(&Wasm::BrIf(0));
+                f.instruction(&Wasm::Loop(BlockType::Empty));
+            }
+            Instruction::EIF => {
+                load_cell(f);
+                f.instruction(&Wasm::BrIf(0));
+                f.instruction(&Wasm::End);
+                f.instruction(&Wasm::End);
+            }
+            Instruction::END => {
+                f.instruction(&Wasm::Return);
+            }
+            _ => unreachable!("jumps are restored into loops"),
+        }
+    }
+}
+
+/// Compiles the basic blocks into a loop dispatching on the current block with a `br_table`,
+/// the block index is kept in local 0.
+fn compile_state_machine(f: &mut Function, instructions: &[Instruction]) -> Result<(), ParseError> {
+    let jump_table = build_jump_table(instructions)?;
+    let leaders = find_block_leaders(instructions, &jump_table);
+
+    let starts: Vec<usize> = (0..instructions.len()).filter(|i| leaders[*i]).collect();
+    let blocks = starts.len() as u32;
+    // jumps past the end of the program select the exit
+    let block_of = |n: usize| {
+        starts
+            .binary_search(&n)
+            .map_or(blocks, |block| block as u32)
+    };
+
+    f.instruction(&Wasm::Loop(BlockType::Empty));
+    f.instruction(&Wasm::Block(BlockType::Empty));
+    for _ in 0..blocks {
+        f.instruction(&Wasm::Block(BlockType::Empty));
+    }
+    f.instruction(&Wasm::LocalGet(0));
+    f.instruction(&Wasm::BrTable((0..blocks).collect(), blocks));
+
+    for (block, start) in starts.iter().enumerate() {
+        f.instruction(&Wasm::End);
+
+        // the dispatch loop surrounds the remaining blocks and the exit
+        let dispatch = blocks - block as u32;
+        let mut i = *start;
+        loop {
+            let instruction = instructions[i];
+            if compile_straight(f, instruction) {
+                if leaders[i + 1] {
+                    f.instruction(&Wasm::I32Const(block_of(i + 1) as i32));
+                    f.instruction(&Wasm::LocalSet(0));
+                    f.instruction(&Wasm::Br(dispatch));
+                    break;
+                }
+
+                i += 1;
+                continue;
+            }
+
+            let (target, condition) = match instruction {
+                Instruction::END => {
+                    f.instruction(&Wasm::Return);
+                    break;
+                }
+                Instruction::JMP(n) => (n, None),
+                Instruction::IF => (jump_table[i], Some(true)),
+                Instruction::EIF => (jump_table[i], Some(false)),
+                Instruction::JIZ(n) => (n, Some(true)),
+                Instruction::JNZ(n) => (n, Some(false)),
+                _ => unreachable!(),
+            };
+
+            f.instruction(&Wasm::I32Const(block_of(target) as i32));
+            if let Some(if_zero) = condition {
+                f.instruction(&Wasm::I32Const(block_of(i + 1) as i32));
+                load_cell(f);
+                if if_zero {
+                    f.instruction(&Wasm::I32Eqz);
+                }
+                f.instruction(&Wasm::Select);
+            }
+            f.instruction(&Wasm::LocalSet(0));
+            f.instruction(&Wasm::Br(dispatch));
+            break;
+        }
+    }
+
+    f.instruction(&Wasm::End);
+    f.instruction(&Wasm::End);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::WasmCompiler;
+    use crate::{
+        compiler::MemoryModel,
+        instruction::Instruction,
+        interpreter::{FixedMemory, Interpreter},
+        optimizer::{Optimize, Optimizer, PartialEvaluator},
+        parser::{ParseError, Parser},
+        samples::HELLO_WORLD_SHORTEST,
+    };
+    use wasmi::{Caller, Engine, Linker, Module, Store};
+
+    struct Host {
+        input: Vec<u8>,
+        output: Vec<u8>,
+        random: u8,
+    }
+
+    /// Instantiates the module with `wasmi`, runs it and returns its output.
+    fn run(module: &[u8], input: &[u8], random: u8) -> Vec<u8> {
+        let engine = Engine::default();
+        let module = Module::new(&engine, module).unwrap();
+        let mut store = Store::new(
+            &engine,
+            Host {
+                input: input.to_vec(),
+                output: Vec::new(),
+                random,
+            },
+        );
+
+        let mut linker = Linker::<Host>::new(&engine);
+        linker
+            .func_wrap("env", "read_byte", |mut caller: Caller<'_, Host>| {
+                let input = &mut caller.data_mut().input;
+                match input.is_empty() {
+                    true => -1,
+                    false => input.remove(0) as i32,
+                }
+            })
+            .unwrap();
+        linker
+            .func_wrap(
+                "env",
+                "write_byte",
+                |mut caller: Caller<'_, Host>, byte: i32| {
+                    caller.data_mut().output.push(byte as u8);
+                },
+            )
+            .unwrap();
+        linker
+            .func_wrap("env", "random", |caller: Caller<'_, Host>| {
+                caller.data().random as i32
+            })
+            .unwrap();
+
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .unwrap()
+            .start(&mut store)
+            .unwrap();
+        instance
+            .get_typed_func::<(), ()>(&store, "run")
+            .unwrap()
+            .call(&mut store, ())
+            .unwrap();
+
+        store.into_data().output
+    }
+
+    fn interpret(instructions: &[Instruction], input: &[u8]) -> String {
+        let mut output = String::new();
+        let mut input = input.to_vec();
+        let mut interpreter = Interpreter::new(instructions.to_vec()).unwrap().with_io(
+            || match input.is_empty() {
+                true => None,
+                false => Some(input.remove(0)).filter(|c| *c != b'\n'),
+            },
+            |s: String| output.push_str(&s),
+        );
+        interpreter.run();
+        drop(interpreter);
+
+        output
+    }
+
+    #[test]
+    fn hello_world_shortest_fixed_memory() {
+        let instructions = Parser::parse(HELLO_WORLD_SHORTEST).unwrap();
+        let compiler = WasmCompiler::new().with_memory(MemoryModel::fixed(100).unwrap());
+
+        for program in [
+            instructions.clone(),
+            Optimizer::new().optimize(&instructions),
+            PartialEvaluator::<FixedMemory<100>>::new().optimize(&instructions),
+        ] {
+            let module = compiler.compile(&program).unwrap();
+            assert_eq!(run(&module, b"", 0), b"Hello, World!");
+        }
+    }
+
+    #[test]
+    fn dynamic_memory_grows_past_first_page() {
+        let mut instructions = vec![Instruction::INC(66)];
+        instructions.extend([Instruction::FWD(250); 300]);
+        instructions.extend([Instruction::INC(65), Instruction::OUT]);
+        instructions.extend([Instruction::BAK(250); 300]);
+        instructions.extend([Instruction::OUT, Instruction::BAK(1), Instruction::OUT]);
+
+        let module = WasmCompiler::new().compile(&instructions).unwrap();
+        let output = String::from_utf8(run(&module, b"", 0)).unwrap();
+        assert_eq!(output, interpret(&instructions, b""));
+        assert_eq!(output, "AB\0");
+    }
+
+    #[test]
+    fn input_and_jumps_into_loops() {
+        // reads bytes until a line break and echoes them, entering the loop through a jump
+        let instructions = vec![
+            Instruction::INC(1),
+            Instruction::JMP(4),
+            Instruction::IF,
+            Instruction::SET(0),
+            Instruction::IN,
+            Instruction::OUT,
+            Instruction::EIF,
+            Instruction::PUT(0xE9),
+        ];
+
+        let module = WasmCompiler::new().compile(&instructions).unwrap();
+        let output = String::from_utf8(run(&module, b"poem\nrest", 0)).unwrap();
+        assert_eq!(output, interpret(&instructions, b"poem\nrest"));
+        assert_eq!(output, "poem\0é");
+    }
+
+    #[test]
+    fn random_and_end() {
+        let instructions = vec![
+            Instruction::RND,
+            Instruction::OUT,
+            Instruction::END,
+            Instruction::OUT,
+        ];
+
+        let module = WasmCompiler::new().compile(&instructions).unwrap();
+        assert_eq!(run(&module, b"", b'x'), b"x");
+    }
+
+    #[test]
+    fn unbalanced_jumps_are_rejected() {
+        let instructions = vec![Instruction::JMP(0), Instruction::IF];
+        assert_eq!(
+            WasmCompiler::new().compile(&instructions),
+            Err(ParseError::MissingEif)
+        );
+    }
+}
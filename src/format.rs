@@ -0,0 +1,237 @@
+//! Lays out poems consistently without changing what they do. The words are rewrapped to fit
+//! the line width, runs of whitespace become single spaces, punctuation is attached to the
+//! word it follows and stanzas are separated by exactly one blank line. Lines can be indented
+//! by how many loops are open at their start.
+//!
+//! Only whitespace is added or removed, and only next to characters that already end a word,
+//! so [`Parser::parse_intermediate`](crate::parser::Parser::parse_intermediate) reads the same
+//! digits from the formatted poem.
+
+use crate::annotation::Annotation;
+use std::ops::Range;
+
+/// Punctuation written directly after the previous word.
+const CLOSING: [char; 7] = [',', '.', ';', ':', '!', '?', ')'];
+
+pub struct Formatter {
+    width: usize,
+    indent: Option<usize>,
+}
+
+impl Formatter {
+    /// Wraps lines at 80 characters without indenting them.
+    pub fn new() -> Self {
+        Self {
+            width: 80,
+            indent: None,
+        }
+    }
+
+    /// The number of characters a line may have, longer words get a line of their own.
+    pub fn with_width(self, width: usize) -> Self {
+        Self { width, ..self }
+    }
+
+    /// Indents every line by `spaces` per loop open at its start. A line starting with an
+    /// `EIF` belongs to the loop it closes.
+    pub fn with_indent(self, spaces: usize) -> Self {
+        Self {
+            indent: Some(spaces),
+            ..self
+        }
+    }
+
+    pub fn format(&self, source: &str) -> String {
+        let stanzas = stanzas(source);
+
+        // the poem on as few lines as possible, to find the loops each piece is in
+        let mut normalized = String::new();
+        let mut spans = Vec::new();
+        for stanza in &stanzas {
+            if !normalized.is_empty() {
+                normalized.push_str("\n\n");
+            }
+            for (i, piece) in stanza.iter().enumerate() {
+                if i > 0 {
+                    normalized.push(' ');
+                }
+                spans.push(normalized.len()..normalized.len() + piece.len());
+                normalized.push_str(piece);
+            }
+        }
+        let mut depths = match self.indent {
+            Some(_) => depths(&Annotation::new(&normalized), &spans),
+            None => vec![0; spans.len()],
+        }
+        .into_iter();
+
+        let mut result = String::new();
+        for stanza in &stanzas {
+            if !result.is_empty() {
+                result.push('\n');
+            }
+            let mut length = 0;
+            for (i, piece) in stanza.iter().enumerate() {
+                let depth = depths.next().unwrap_or_default();
+                let width = piece.chars().count();
+                if i > 0 && length + 1 + width <= self.width {
+                    result.push(' ');
+                    length += 1;
+                } else {
+                    if i > 0 {
+                        result.push('\n');
+                    }
+                    let indent = depth * self.indent.unwrap_or_default();
+                    result.extend(std::iter::repeat_n(' ', indent));
+                    length = indent;
+                }
+                result.push_str(piece);
+                length += width;
+            }
+            result.push('\n');
+        }
+
+        result
+    }
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The pieces of text between whitespace of every stanza, with punctuation attached to the
+/// piece before it. Stanzas are separated by blank lines.
+fn stanzas(source: &str) -> Vec<Vec<String>> {
+    let mut stanzas = Vec::new();
+    let mut stanza: Vec<String> = Vec::new();
+    for line in source.lines() {
+        if line.trim().is_empty() {
+            if !stanza.is_empty() {
+                stanzas.push(std::mem::take(&mut stanza));
+            }
+            continue;
+        }
+
+        for mut token in line.split_whitespace() {
+            // punctuation ends words already, so no word is joined or split
+            if let Some(last) = stanza.last_mut() {
+                if last.ends_with('(') {
+                    last.push_str(token);
+                    continue;
+                }
+                let rest = token.trim_start_matches(CLOSING);
+                last.push_str(&token[..token.len() - rest.len()]);
+                token = rest;
+            }
+            if !token.is_empty() {
+                stanza.push(token.to_string());
+            }
+        }
+    }
+    if !stanza.is_empty() {
+        stanzas.push(stanza);
+    }
+
+    stanzas
+}
+
+/// The number of loops open at the start of every piece, one less if it starts with an `EIF`.
+fn depths(annotation: &Annotation, spans: &[Range<usize>]) -> Vec<usize> {
+    let mut depth = 0usize;
+    let mut operation = 0;
+    let mut depths = Vec::new();
+    for span in spans {
+        let mut first = None;
+        while operation < annotation.operations.len()
+            && annotation.operation_span(operation).start < span.end
+        {
+            match annotation.operations[operation].opcode {
+                1 => {
+                    first.get_or_insert(depth);
+                    depth += 1;
+                }
+                2 => {
+                    depth = depth.saturating_sub(1);
+                    first.get_or_insert(depth);
+                }
+                _ => {
+                    first.get_or_insert(depth);
+                }
+            }
+            operation += 1;
+        }
+        depths.push(first.unwrap_or(depth));
+    }
+
+    depths
+}
+
+#[cfg(test)]
+mod test {
+    use super::Formatter;
+    use crate::parser::Parser;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            Formatter::new().format("  inc   a ,\tdecc( aa ),a\n\n\n\n outputa !  \n\n"),
+            "inc a, decc(aa), a\n\noutputa!\n"
+        );
+        // an apostrophe is part of the word
+        assert_eq!(Formatter::new().format("don 't"), "don 't\n");
+        assert_eq!(Formatter::new().format(" \n "), "");
+    }
+
+    #[test]
+    fn test_wrap() {
+        let formatter = Formatter::new().with_width(10);
+        assert_eq!(
+            formatter.format("inc a inc aa outputa\nendprogram extraordinarily a"),
+            "inc a inc\naa outputa\nendprogram\nextraordinarily\na\n"
+        );
+    }
+
+    #[test]
+    fn test_indent() {
+        let formatter = Formatter::new().with_width(12).with_indent(2);
+        assert_eq!(
+            formatter.format("inc aa a outputa decc a a fwdfw a ei ei outputa"),
+            concat!(
+                "inc aa a\n",
+                "  outputa\n",
+                "  decc a a\n",
+                "    fwdfw a\n",
+                "  ei ei\n",
+                "outputa\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_keeps_digits() {
+        let mut rng = StdRng::seed_from_u64(49);
+        let pieces = [
+            "a", "aaa", "aaaaaaaaaaaa", "'", "(", ")", ",", ".", "!", "-", " ", "  ", "\n", "\t",
+            "ü",
+        ];
+        for _ in 0..500 {
+            let source = (0..rng.gen_range(0..40))
+                .map(|_| pieces[rng.gen_range(0..pieces.len())])
+                .collect::<String>();
+            let formatter = Formatter::new()
+                .with_width(rng.gen_range(1..20))
+                .with_indent(2);
+            let formatted = formatter.format(&source);
+            assert_eq!(
+                Parser::parse_intermediate(&formatted),
+                Parser::parse_intermediate(&source),
+                "{:?}",
+                source
+            );
+            assert_eq!(formatter.format(&formatted), formatted);
+        }
+    }
+}
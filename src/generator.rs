@@ -0,0 +1,241 @@
+//! Writes instructions back out as a poem, choosing a word with the right number of letters for
+//! every digit. Together with [`Brainfuck::from_brainfuck`](crate::brainfuck::Brainfuck) this
+//! turns Brainfuck programs into poems.
+//!
+//! Instructions the parser never produces are expanded: arguments above 10 are split over
+//! several instructions, `SCF`/`SCB`/`SET` become their loops and `JIZ`/`JNZ` pairs become
+//! `IF`/`EIF`. `PUT` and jumps that do not form loops have no equivalent.
+
+use crate::{instruction::Instruction, interpreter::build_jump_table, parser::ParseError};
+use std::fmt::Display;
+
+/// Words to pick from, indexed by their number of letters minus one.
+const WORDS: [&[&str]; 10] = [
+    &["a", "i", "o"],
+    &["in", "of", "to", "my", "we"],
+    &["the", "sea", "and", "sky", "old"],
+    &["moon", "rain", "wind", "with", "slow"],
+    &["light", "quiet", "river", "stone", "dream"],
+    &["silent", "winter", "shadow", "golden", "gentle"],
+    &["morning", "whisper", "endless", "harbour", "distant"],
+    &["evenings", "wandered", "mountain", "midnight", "blossoms"],
+    &[
+        "wandering",
+        "starlight",
+        "yesterday",
+        "moonlight",
+        "nightfall",
+    ],
+    &["everything", "whispering", "wilderness", "lighthouse"],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeneratorError {
+    /// The program has mismatched `IF`/`EIF`.
+    InvalidProgram(ParseError),
+    /// The instruction at the index can not be written as a poem.
+    Unsupported(usize, Instruction),
+}
+
+impl Display for GeneratorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeneratorError::InvalidProgram(error) => write!(f, "Invalid program: {}", error),
+            GeneratorError::Unsupported(index, instruction) => write!(
+                f,
+                "{:?} at instruction {} can not be written as a poem",
+                instruction, index
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+pub struct Generator {
+    words_per_line: usize,
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Self { words_per_line: 8 }
+    }
+
+    /// Breaks the poem into lines of `words_per_line` words, `0` keeps it on a single line.
+    pub fn with_words_per_line(self, words_per_line: usize) -> Self {
+        Self { words_per_line }
+    }
+
+    pub fn generate(&self, instructions: &[Instruction]) -> Result<String, GeneratorError> {
+        let digits = Self::to_digits(instructions)?;
+
+        let mut used = [0; 10];
+        let mut result = String::new();
+        for (i, digit) in digits.iter().enumerate() {
+            let length = match digit {
+                0 => 10,
+                d => *d as usize,
+            };
+            let words = WORDS[length - 1];
+            let word = words[used[length - 1] % words.len()];
+            used[length - 1] += 1;
+
+            let line_start = i == 0 || (self.words_per_line != 0 && i % self.words_per_line == 0);
+            if i > 0 {
+                result.push(if line_start { '\n' } else { ' ' });
+            }
+            if line_start {
+                let mut chars = word.chars();
+                result.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                result.extend(chars);
+            } else {
+                result.push_str(word);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// The digits the parser reads back as `instructions`.
+    fn to_digits(instructions: &[Instruction]) -> Result<Vec<u8>, GeneratorError> {
+        build_jump_table(instructions).map_err(GeneratorError::InvalidProgram)?;
+        let structured = crate::compiler::restore_loops(instructions)
+            .map_err(|i| GeneratorError::Unsupported(i, instructions[i]))?;
+
+        let mut result = Vec::new();
+        for (i, instruction) in structured.iter().enumerate() {
+            match *instruction {
+                Instruction::END => result.push(0),
+                Instruction::IF => result.push(1),
+                Instruction::EIF => result.push(2),
+                Instruction::INC(n) => push_with_argument(&mut result, 3, n),
+                Instruction::DEC(n) => push_with_argument(&mut result, 4, n),
+                Instruction::FWD(n) => push_with_argument(&mut result, 5, n),
+                Instruction::BAK(n) => push_with_argument(&mut result, 6, n),
+                Instruction::OUT => result.push(7),
+                Instruction::IN => result.push(8),
+                Instruction::RND => result.push(9),
+                Instruction::SCF(n) => {
+                    result.push(1);
+                    push_with_argument(&mut result, 5, n);
+                    result.push(2);
+                }
+                Instruction::SCB(n) => {
+                    result.push(1);
+                    push_with_argument(&mut result, 6, n);
+                    result.push(2);
+                }
+                Instruction::SET(n) => {
+                    result.extend([1, 4, 1, 2]);
+                    push_with_argument(&mut result, 3, n);
+                }
+                // jumps were replaced by restore_loops
+                instruction => return Err(GeneratorError::Unsupported(i, instruction)),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Pushes `instruction` as often as needed for arguments of at most 10, written as `0`.
+fn push_with_argument(result: &mut Vec<u8>, instruction: u8, mut argument: u8) {
+    while argument > 0 {
+        let n = argument.min(10);
+        result.extend([instruction, n % 10]);
+        argument -= n;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Generator, GeneratorError, WORDS};
+    use crate::{
+        brainfuck::Brainfuck, instruction::Instruction, parser::Parser,
+        samples::HELLO_WORLD_SHORTEST,
+    };
+
+    #[test]
+    fn test_word_lengths() {
+        for (i, words) in WORDS.iter().enumerate() {
+            for word in *words {
+                assert_eq!(word.len(), i + 1, "{}", word);
+                assert!(word.chars().all(|c| c.is_ascii_lowercase()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let instructions = vec![Instruction::INC(10), Instruction::OUT, Instruction::END];
+        assert_eq!(
+            Generator::new().generate(&instructions),
+            Ok("The everything morning whispering".to_string())
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let instructions = Parser::parse(HELLO_WORLD_SHORTEST).unwrap();
+        let poem = Generator::new().generate(&instructions).unwrap();
+        assert_eq!(Parser::parse(&poem), Ok(instructions));
+    }
+
+    #[test]
+    fn test_expanded_instructions() {
+        let instructions = vec![
+            Instruction::INC(25),
+            Instruction::SET(3),
+            Instruction::SCF(12),
+            Instruction::JIZ(5),
+            Instruction::OUT,
+            Instruction::JNZ(3),
+        ];
+        let poem = Generator::new()
+            .with_words_per_line(0)
+            .generate(&instructions);
+        assert_eq!(
+            Parser::parse(&poem.unwrap()),
+            Ok(vec![
+                Instruction::INC(10),
+                Instruction::INC(10),
+                Instruction::INC(5),
+                Instruction::IF,
+                Instruction::DEC(1),
+                Instruction::EIF,
+                Instruction::INC(3),
+                Instruction::IF,
+                Instruction::FWD(10),
+                Instruction::FWD(2),
+                Instruction::EIF,
+                Instruction::IF,
+                Instruction::OUT,
+                Instruction::EIF,
+            ])
+        );
+
+        assert_eq!(
+            Generator::new().generate(&[Instruction::PUT(65)]),
+            Err(GeneratorError::Unsupported(0, Instruction::PUT(65)))
+        );
+        assert_eq!(
+            Generator::new().generate(&[Instruction::OUT, Instruction::JMP(0)]),
+            Err(GeneratorError::Unsupported(1, Instruction::JMP(0)))
+        );
+    }
+
+    #[test]
+    fn test_brainfuck_to_poem() {
+        let brainfuck = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+        let instructions = Brainfuck::new().from_brainfuck(brainfuck).unwrap();
+        let poem = Generator::new().generate(&instructions).unwrap();
+        let parsed = Parser::parse(&poem).unwrap();
+        assert_eq!(parsed, instructions);
+    }
+}
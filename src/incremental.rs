@@ -0,0 +1,425 @@
+//! Keeps the instructions of a poem up to date while it is edited. An edit only reads the words
+//! between the breaks around it again and decodes instructions from there until they line up
+//! with the old ones, instead of parsing the whole poem. The result is always the one
+//! [`Parser::parse_with`] gives for the whole source.
+//!
+//! Edits still take time linear in the length of the poem: the spans, digits, opcodes and
+//! matches after the edit are shifted, and finding the loops an edit may have unbalanced can
+//! walk back to the start. Only the parsing itself is limited to the edited words.
+//!
+//! ```
+//! use poetic::{incremental::IncrementalParser, instruction::Instruction};
+//!
+//! let mut parser = IncrementalParser::new("inc a outputa");
+//! parser.edit(4..5, "aa");
+//! assert_eq!(parser.result(), Ok(vec![Instruction::INC(2), Instruction::OUT]));
+//! ```
+
+use crate::{
+    instruction::Instruction,
+    parser::{decode, takes_argument, Letters, ParseError, Parser, ParserOptions, Segment},
+};
+use std::ops::Range;
+
+pub struct IncrementalParser {
+    source: String,
+    options: ParserOptions,
+    /// Span and letter count of every word.
+    words: Vec<(Range<usize>, usize)>,
+    /// Index of the first digit of every word, followed by the number of digits.
+    word_digits: Vec<usize>,
+    /// The digits of all words, none for a word too long for the options.
+    digits: Vec<u8>,
+    instructions: Vec<Instruction>,
+    /// Index of the opcode digit of every instruction.
+    opcodes: Vec<usize>,
+    /// The matching `EIF` of every `IF` and the other way round.
+    matches: Vec<Option<usize>>,
+}
+
+impl IncrementalParser {
+    pub fn new(source: &str) -> Self {
+        Self::with_options(source, &ParserOptions::default())
+    }
+
+    /// Parses `source` with `options`, which also apply to every later edit.
+    pub fn with_options(source: &str, options: &ParserOptions) -> Self {
+        let mut parser = Self {
+            source: String::new(),
+            options: options.clone(),
+            words: Vec::new(),
+            word_digits: vec![0],
+            digits: Vec::new(),
+            instructions: Vec::new(),
+            opcodes: Vec::new(),
+            matches: Vec::new(),
+        };
+        parser.edit(0..0, source);
+        parser
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn options(&self) -> &ParserOptions {
+        &self.options
+    }
+
+    /// The instructions as far as they can be read, even if the poem does not parse.
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// The index of the `EIF` matching an `IF` and the other way round.
+    pub fn matching(&self, instruction: usize) -> Option<usize> {
+        self.matches[instruction]
+    }
+
+    /// What [`Parser::parse_with`] returns for the current source.
+    pub fn result(&self) -> Result<Vec<Instruction>, ParseError> {
+        // errors in the order the parser finds them
+        if let Some(error) = self
+            .words
+            .iter()
+            .filter(|(_, letters)| *letters > 10)
+            .find_map(|(_, letters)| self.options.digits(*letters).err())
+        {
+            return Err(error);
+        }
+        if let Some(&opcode) = self.digits.get(self.instructions_end()) {
+            return Err(ParseError::NeedsArgument(opcode));
+        }
+        for (instruction, matching) in self.instructions.iter().zip(&self.matches) {
+            match (instruction, matching) {
+                (Instruction::IF, None) => return Err(ParseError::MissingEif),
+                (Instruction::EIF, None) => return Err(ParseError::MissingIf),
+                _ => {}
+            }
+        }
+
+        Ok(self.instructions.clone())
+    }
+
+    /// Replaces the byte range of the source with `text` and returns the range of instructions
+    /// that were decoded again, all others are only moved.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds or not on character boundaries, like
+    /// [`String::replace_range`].
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Range<usize> {
+        let Range { start, end } = range;
+        // the words between the breaks around the edit are read again
+        let low = self.source[..start]
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| self.is_resync(c))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let high = self.source[end..]
+            .char_indices()
+            .find(|&(_, c)| self.is_resync(c))
+            .map_or(self.source.len(), |(i, _)| end + i);
+        self.source.replace_range(start..end, text);
+        let new_high = high + text.len() - (end - start);
+
+        let first_word = self.words.partition_point(|(span, _)| span.start < low);
+        let last_word = self.words.partition_point(|(span, _)| span.start < high);
+        let words = Parser::word_spans(&self.source[low..new_high], &self.options)
+            .into_iter()
+            .map(|(span, letters)| (span.start + low..span.end + low, letters))
+            .collect::<Vec<_>>();
+
+        let first_digit = self.word_digits[first_word];
+        let last_digit = self.word_digits[last_word];
+        let mut digits = Vec::new();
+        let mut word_digits = Vec::new();
+        for (_, letters) in &words {
+            word_digits.push(first_digit + digits.len());
+            digits.append(&mut self.options.digits(*letters).unwrap_or_default());
+        }
+        let new_last_digit = first_digit + digits.len();
+
+        for (span, _) in &mut self.words[last_word..] {
+            *span = span.start + new_high - high..span.end + new_high - high;
+        }
+        self.words.splice(first_word..last_word, words);
+        for digit in &mut self.word_digits[last_word..] {
+            *digit = *digit + new_last_digit - last_digit;
+        }
+        self.word_digits.splice(first_word..last_word, word_digits);
+
+        let first = self
+            .opcodes
+            .partition_point(|&digit| digit + self.width(digit) <= first_digit);
+        let old_end = self.instructions_end();
+        self.digits.splice(first_digit..last_digit, digits);
+        self.decode(first, old_end, last_digit, new_last_digit)
+    }
+
+    /// Decodes instructions from the instruction `first` on until they line up with the old
+    /// ones again after the digits that changed, which ended at `last_digit` and now end at
+    /// `new_last_digit`. `old_end` is where the old instructions ended.
+    fn decode(
+        &mut self,
+        first: usize,
+        old_end: usize,
+        last_digit: usize,
+        new_last_digit: usize,
+    ) -> Range<usize> {
+        let mut instructions = Vec::new();
+        let mut opcodes = Vec::new();
+        let mut digit = match first {
+            0 => 0,
+            first => self.opcodes[first - 1] + self.width(self.opcodes[first - 1]),
+        };
+        let last = loop {
+            if digit >= new_last_digit {
+                let old_digit = digit + last_digit - new_last_digit;
+                if old_digit == old_end {
+                    break self.instructions.len();
+                }
+                if let Ok(last) = self.opcodes.binary_search(&old_digit) {
+                    break last;
+                }
+            }
+
+            let Some(&opcode) = self.digits.get(digit) else {
+                break self.instructions.len();
+            };
+            // the opcode is left without its argument at the end
+            let Ok(instruction) = decode(opcode, self.digits.get(digit + 1).copied()) else {
+                break self.instructions.len();
+            };
+            instructions.push(instruction);
+            opcodes.push(digit);
+            digit += self.width(digit);
+        };
+
+        let added = instructions.len();
+        let (open, closing) = self.unmatched_around(first, last);
+        for opcode in &mut self.opcodes[last..] {
+            *opcode = *opcode + new_last_digit - last_digit;
+        }
+        self.opcodes.splice(first..last, opcodes);
+        self.instructions.splice(first..last, instructions);
+        for matching in self.matches[last..].iter_mut().flatten() {
+            if *matching >= last {
+                *matching = *matching + first + added - last;
+            }
+        }
+        self.matches.splice(first..last, vec![None; added]);
+
+        // only loops opened before the new instructions and closed after them can change
+        let closing = closing
+            .into_iter()
+            .map(|i| i + first + added - last)
+            .collect::<Vec<_>>();
+        let mut stack = Vec::new();
+        for i in open.into_iter().chain(first..first + added).chain(closing) {
+            self.matches[i] = None;
+            match self.instructions[i] {
+                Instruction::IF => stack.push(i),
+                Instruction::EIF => {
+                    if let Some(start) = stack.pop() {
+                        self.matches[start] = Some(i);
+                        self.matches[i] = Some(start);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        first..first + added
+    }
+
+    /// The `IF`s before `first` not closed before it and the `EIF`s from `last` on not opened
+    /// from there, skipping the loops in between.
+    fn unmatched_around(&self, first: usize, last: usize) -> (Vec<usize>, Vec<usize>) {
+        let mut open = Vec::new();
+        let mut i = first;
+        while i > 0 {
+            i -= 1;
+            match (self.instructions[i], self.matches[i]) {
+                (Instruction::IF, _) => open.push(i),
+                (Instruction::EIF, Some(start)) => i = start,
+                // every loop before an unmatched EIF is closed before it
+                (Instruction::EIF, None) => break,
+                _ => {}
+            }
+        }
+        open.reverse();
+
+        let mut closing = Vec::new();
+        let mut i = last;
+        while i < self.instructions.len() {
+            match (self.instructions[i], self.matches[i]) {
+                (Instruction::EIF, _) => closing.push(i),
+                (Instruction::IF, Some(end)) => i = end,
+                (Instruction::IF, None) => break,
+                _ => {}
+            }
+            i += 1;
+        }
+
+        (open, closing)
+    }
+
+    /// The number of digits of the instruction with its opcode at the digit.
+    fn width(&self, digit: usize) -> usize {
+        match takes_argument(self.digits[digit]) {
+            true => 2,
+            false => 1,
+        }
+    }
+
+    /// The digit after the last complete instruction.
+    fn instructions_end(&self) -> usize {
+        self.opcodes
+            .last()
+            .map_or(0, |&digit| digit + self.width(digit))
+    }
+
+    /// Whether no word or grapheme cluster can continue across the character, so reading can
+    /// start again after it. Grapheme clusters only reliably end at line breaks.
+    fn is_resync(&self, c: char) -> bool {
+        let boundary = match self.options.letters() {
+            Letters::Ascii => true,
+            #[cfg(feature = "unicode")]
+            Letters::Unicode => c == '\n',
+        };
+        boundary && self.options.segment(c) == Segment::Break
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IncrementalParser;
+    use crate::{
+        instruction::Instruction,
+        parser::{LongWords, ParseError, Parser, ParserOptions},
+        samples::HELLO_WORLD_SHORTEST,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Matches like the interpreter's jump table, `None` for unmatched loops.
+    fn matches(instructions: &[Instruction]) -> Vec<Option<usize>> {
+        let mut matches = vec![None; instructions.len()];
+        let mut stack = Vec::new();
+        for (i, instruction) in instructions.iter().enumerate() {
+            match instruction {
+                Instruction::IF => stack.push(i),
+                Instruction::EIF => {
+                    if let Some(start) = stack.pop() {
+                        matches[start] = Some(i);
+                        matches[i] = Some(start);
+                    }
+                }
+                _ => {}
+            }
+        }
+        matches
+    }
+
+    fn assert_parsed(parser: &IncrementalParser) {
+        let fresh = IncrementalParser::with_options(parser.source(), parser.options());
+        assert_eq!(
+            parser.result(),
+            Parser::parse_with(parser.source(), parser.options()),
+            "{:?}",
+            parser.source()
+        );
+        assert_eq!(parser.instructions(), fresh.instructions());
+        let matching = (0..parser.instructions().len())
+            .map(|i| parser.matching(i))
+            .collect::<Vec<_>>();
+        assert_eq!(matching, matches(parser.instructions()));
+    }
+
+    #[test]
+    fn test_edits() {
+        let mut parser = IncrementalParser::new("inc a outputa");
+        assert_eq!(parser.edit(4..5, "aa"), 0..1);
+        assert_eq!(
+            parser.result(),
+            Ok(vec![Instruction::INC(2), Instruction::OUT])
+        );
+
+        // a new word shifts the pairing of everything after it
+        parser.edit(0..0, "a ");
+        assert_eq!(parser.source(), "a inc aa outputa");
+        assert_eq!(parser.result(), Err(ParseError::MissingEif));
+        assert_eq!(parser.matching(0), None);
+
+        parser.edit(16..16, " ei");
+        assert_eq!(parser.matching(0), Some(3));
+        assert_eq!(parser.matching(3), Some(0));
+        assert_parsed(&parser);
+
+        // joining two words
+        parser.edit(5..6, "");
+        assert_eq!(parser.source(), "a incaa outputa ei");
+        assert_parsed(&parser);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut parser = IncrementalParser::new("inc");
+        assert_eq!(parser.result(), Err(ParseError::NeedsArgument(3)));
+        parser.edit(3..3, " a ei");
+        assert_eq!(parser.result(), Err(ParseError::MissingIf));
+
+        let options = ParserOptions::new().with_long_words(LongWords::Error);
+        let mut parser = IncrementalParser::with_options("inc a", &options);
+        parser.edit(5..5, "aaaaaaaaaaa");
+        assert_eq!(parser.result(), Err(ParseError::WordTooLong(12)));
+        assert_parsed(&parser);
+    }
+
+    #[test]
+    fn test_random_edits() {
+        let mut rng = StdRng::seed_from_u64(48);
+        let pieces = ["a", "aa", "inc ", " ", "ei", "\n", "'", "-", "aaaaaaaaaaa", ", "];
+        let options = [
+            ParserOptions::default(),
+            ParserOptions::new().with_joiners(['-']),
+            ParserOptions::new().with_long_words(LongWords::Error),
+        ];
+        for options in options {
+            let mut parser = IncrementalParser::with_options(HELLO_WORLD_SHORTEST, &options);
+            for _ in 0..500 {
+                let length = parser.source().len();
+                let start = rng.gen_range(0..=length);
+                let end = rng.gen_range(start..=length.min(start + 8));
+                let text = (0..rng.gen_range(0..3))
+                    .map(|_| pieces[rng.gen_range(0..pieces.len())])
+                    .collect::<String>();
+                parser.edit(start..end, &text);
+                assert_parsed(&parser);
+            }
+        }
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_random_unicode_edits() {
+        use crate::parser::Letters;
+
+        let mut rng = StdRng::seed_from_u64(48);
+        let pieces = ["e", "\u{301}", " ", "\n", "\r", "ü", "inc ", "🇩", "🇪", "ei"];
+        let options = ParserOptions::new().with_letters(Letters::Unicode);
+        let mut parser = IncrementalParser::with_options("inc e\u{301}te\nfür a ei", &options);
+        for _ in 0..500 {
+            let boundaries = (0..=parser.source().len())
+                .filter(|&i| parser.source().is_char_boundary(i))
+                .collect::<Vec<_>>();
+            let start = rng.gen_range(0..boundaries.len());
+            let end = rng.gen_range(start..boundaries.len().min(start + 4));
+            let text = (0..rng.gen_range(0..3))
+                .map(|_| pieces[rng.gen_range(0..pieces.len())])
+                .collect::<String>();
+            parser.edit(boundaries[start]..boundaries[end], &text);
+            assert_parsed(&parser);
+        }
+    }
+}
@@ -1,5 +1,6 @@
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, Debug, std::cmp::PartialEq, std::cmp::Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     END,
     IF,
@@ -14,4 +15,8 @@ pub enum Instruction {
     JIZ(usize),
     JNZ(usize),
     JMP(usize),
+    SCF(u8),
+    SCB(u8),
+    PUT(u8),
+    SET(u8),
 }
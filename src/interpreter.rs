@@ -1,21 +1,50 @@
-use crate::instruction::Instruction;
+use crate::{instruction::Instruction, parser::ParseError};
+use memchr::{memchr, memrchr};
 use rand::{rngs::ThreadRng, Rng, RngCore};
-use std::{
-    collections::{hash_map::Entry, HashMap},
-    io::Read,
-};
+use std::io::Read;
 
+/// Wraps a memory pointer into `0..bound`, however far it was moved past either end.
 fn wrap(value: i64, bound: i64) -> i64 {
-    if value < 0 {
-        value + bound
-    } else if value >= bound {
-        value - bound
+    value.rem_euclid(bound)
+}
+
+pub(crate) const DEFAULT_MEMORY_SIZE: usize = 32;
+
+/// Searches `memory` for a zero cell starting at `pointer` and moving by `stride` cells at a time,
+/// wrapping around at the end of `memory`. Returns `None` if every reachable cell is non zero.
+fn scan_wrapping(memory: &[u8], pointer: usize, stride: usize, forward: bool) -> Option<usize> {
+    let len = memory.len();
+    if stride.is_multiple_of(len) {
+        return (memory[pointer] == 0).then_some(pointer);
+    }
+
+    if stride == 1 {
+        return if forward {
+            memchr(0, &memory[pointer..])
+                .map(|i| pointer + i)
+                .or_else(|| memchr(0, &memory[..pointer]))
+        } else {
+            memrchr(0, &memory[..=pointer])
+                .or_else(|| memrchr(0, &memory[pointer + 1..]).map(|i| pointer + 1 + i))
+        };
+    }
+
+    let step = if forward {
+        stride % len
     } else {
-        value
+        len - stride % len
+    };
+    let mut current = pointer;
+    for _ in 0..len {
+        if memory[current] == 0 {
+            return Some(current);
+        }
+
+        current = (current + step) % len;
     }
-}
 
-const DEFAULT_MEMORY_SIZE: usize = 32;
+    None
+}
 
 pub trait Memory {
     fn get_memory_pointer(&self) -> usize;
@@ -30,8 +59,61 @@ pub trait Memory {
 
     fn get_memory_size(&self) -> usize;
     fn raw_memory(&self) -> &[u8];
+
+    /// Moves the memory pointer forward by `stride` until it points at a zero cell.
+    /// Returns `false` and leaves the pointer untouched if no zero cell can be reached.
+    fn scan_forward(&mut self, stride: usize) -> bool {
+        scan_cell_by_cell(self, |memory| memory.inc_memory_pointer(stride))
+    }
+
+    /// Moves the memory pointer backward by `stride` until it points at a zero cell.
+    /// Returns `false` and leaves the pointer untouched if no zero cell can be reached.
+    fn scan_backward(&mut self, stride: usize) -> bool {
+        scan_cell_by_cell(self, |memory| memory.dec_memory_pointer(stride))
+    }
 }
 
+pub(crate) mod raw {
+    /// Mutable access to the whole tape for natively compiled code, which relies on the memory
+    /// pointer staying inside it. Sealed, so only the memory types of this crate can be run
+    /// natively.
+    #[cfg_attr(
+        not(any(
+            all(feature = "jit", target_arch = "x86_64", target_os = "linux"),
+            feature = "cranelift"
+        )),
+        allow(dead_code)
+    )]
+    pub trait RawMemory: super::Memory {
+        fn raw_memory_mut(&mut self) -> &mut [u8];
+    }
+}
+
+/// Steps through memory until the pointer is at a zero cell. Once there were as many steps as
+/// cells, every cell a wrapping memory can reach was visited, while a growing memory has reached
+/// its zeroed new cells before.
+fn scan_cell_by_cell<M: Memory + ?Sized>(memory: &mut M, mut step: impl FnMut(&mut M)) -> bool {
+    let start = memory.get_memory_pointer();
+    let mut steps = 0;
+    while memory.get_memory_value() != 0 {
+        if steps >= memory.get_memory_size() {
+            memory.set_memory_pointer(start);
+            return false;
+        }
+
+        step(memory);
+        steps += 1;
+    }
+
+    true
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "MemoryState", try_from = "MemoryState")
+)]
 pub struct DynamicMemory {
     memory: Vec<u8>,
     memory_pointer: usize,
@@ -44,6 +126,18 @@ impl DynamicMemory {
             memory_pointer: 0,
         }
     }
+
+    fn grow_to_memory_pointer(&mut self) {
+        while self.memory_pointer > self.memory.len() - 1 {
+            self.memory.resize(self.memory.len() * 2, 0);
+        }
+    }
+}
+
+impl Default for DynamicMemory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Memory for DynamicMemory {
@@ -57,9 +151,7 @@ impl Memory for DynamicMemory {
 
     fn inc_memory_pointer(&mut self, value: usize) {
         self.memory_pointer += value;
-        if self.memory_pointer > self.memory.len() - 1 {
-            self.memory.resize(self.memory.len() * 2, 0);
-        }
+        self.grow_to_memory_pointer();
     }
 
     fn dec_memory_pointer(&mut self, value: usize) {
@@ -92,67 +184,285 @@ impl Memory for DynamicMemory {
     fn raw_memory(&self) -> &[u8] {
         &self.memory
     }
-}
 
-pub struct FixedMemory<const SIZE: usize> {
-    memory: [u8; SIZE],
-    memory_pointer: usize,
-}
+    fn scan_forward(&mut self, stride: usize) -> bool {
+        if stride == 0 {
+            return self.get_memory_value() == 0;
+        }
 
-impl<const SIZE: usize> FixedMemory<SIZE> {
-    fn new() -> Self {
-        Self {
-            memory: [0; SIZE],
-            memory_pointer: 0,
+        // everything past the end of the memory is zero, so the scan always terminates
+        if stride == 1 {
+            self.memory_pointer += memchr(0, &self.memory[self.memory_pointer..])
+                .unwrap_or(self.memory.len() - self.memory_pointer);
+        } else {
+            while self.memory_pointer < self.memory.len() && self.memory[self.memory_pointer] != 0 {
+                self.memory_pointer += stride;
+            }
+        }
+
+        self.grow_to_memory_pointer();
+        true
+    }
+
+    fn scan_backward(&mut self, stride: usize) -> bool {
+        match scan_wrapping(&self.memory, self.memory_pointer, stride, false) {
+            Some(pointer) => {
+                self.memory_pointer = pointer;
+                true
+            }
+            None => false,
         }
     }
 }
 
-impl<const SIZE: usize> Memory for FixedMemory<SIZE> {
+impl raw::RawMemory for DynamicMemory {
+    fn raw_memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+}
+
+/// The cells and pointer of a memory that keeps its size, with the pointer wrapping around at
+/// either end. [`FixedMemory`] and [`BoundedMemory`] only differ in where the cells live.
+trait WrappingMemory {
+    fn cells(&self) -> (&[u8], usize);
+    fn cells_mut(&mut self) -> (&mut [u8], &mut usize);
+}
+
+impl<T: WrappingMemory> Memory for T {
     fn get_memory_pointer(&self) -> usize {
-        self.memory_pointer
+        self.cells().1
     }
 
     fn set_memory_pointer(&mut self, pointer: usize) {
-        self.memory_pointer = pointer;
+        *self.cells_mut().1 = pointer;
     }
 
     fn inc_memory_pointer(&mut self, value: usize) {
-        self.memory_pointer = wrap(
-            self.memory_pointer as i64 + value as i64,
-            self.memory.len() as i64,
-        ) as usize;
+        let (memory, pointer) = self.cells_mut();
+        *pointer = wrap(*pointer as i64 + value as i64, memory.len() as i64) as usize;
     }
 
     fn dec_memory_pointer(&mut self, value: usize) {
-        self.memory_pointer = wrap(
-            self.memory_pointer as i64 - value as i64,
-            self.memory.len() as i64,
-        ) as usize;
+        let (memory, pointer) = self.cells_mut();
+        *pointer = wrap(*pointer as i64 - value as i64, memory.len() as i64) as usize;
     }
 
     fn get_memory_value(&self) -> u8 {
-        self.memory[self.memory_pointer]
+        let (memory, pointer) = self.cells();
+        memory[pointer]
     }
 
     fn set_memory_value(&mut self, value: u8) {
-        self.memory[self.memory_pointer] = value;
+        let (memory, pointer) = self.cells_mut();
+        memory[*pointer] = value;
     }
 
     fn inc_memory_value(&mut self, value: u8) {
-        self.memory[self.memory_pointer] = self.memory[self.memory_pointer].wrapping_add(value);
+        let (memory, pointer) = self.cells_mut();
+        memory[*pointer] = memory[*pointer].wrapping_add(value);
     }
 
     fn dec_memory_value(&mut self, value: u8) {
-        self.memory[self.memory_pointer] = self.memory[self.memory_pointer].wrapping_sub(value);
+        let (memory, pointer) = self.cells_mut();
+        memory[*pointer] = memory[*pointer].wrapping_sub(value);
     }
 
     fn get_memory_size(&self) -> usize {
-        self.memory.len()
+        self.cells().0.len()
     }
 
     fn raw_memory(&self) -> &[u8] {
-        &self.memory
+        self.cells().0
+    }
+
+    fn scan_forward(&mut self, stride: usize) -> bool {
+        let (memory, pointer) = self.cells_mut();
+        scan_wrapping(memory, *pointer, stride, true)
+            .map(|zero| *pointer = zero)
+            .is_some()
+    }
+
+    fn scan_backward(&mut self, stride: usize) -> bool {
+        let (memory, pointer) = self.cells_mut();
+        scan_wrapping(memory, *pointer, stride, false)
+            .map(|zero| *pointer = zero)
+            .is_some()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "MemoryState", try_from = "MemoryState")
+)]
+pub struct FixedMemory<const SIZE: usize> {
+    memory: [u8; SIZE],
+    memory_pointer: usize,
+}
+
+impl<const SIZE: usize> FixedMemory<SIZE> {
+    fn new() -> Self {
+        Self {
+            memory: [0; SIZE],
+            memory_pointer: 0,
+        }
+    }
+}
+
+impl<const SIZE: usize> Default for FixedMemory<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> WrappingMemory for FixedMemory<SIZE> {
+    fn cells(&self) -> (&[u8], usize) {
+        (&self.memory, self.memory_pointer)
+    }
+
+    fn cells_mut(&mut self) -> (&mut [u8], &mut usize) {
+        (&mut self.memory, &mut self.memory_pointer)
+    }
+}
+
+impl<const SIZE: usize> raw::RawMemory for FixedMemory<SIZE> {
+    fn raw_memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+}
+
+/// Like [`FixedMemory`], with the number of cells chosen at runtime.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "MemoryState", try_from = "MemoryState")
+)]
+pub struct BoundedMemory {
+    memory: Vec<u8>,
+    memory_pointer: usize,
+}
+
+impl BoundedMemory {
+    /// Panics if `size` is zero.
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "memory needs at least one cell");
+        Self {
+            memory: vec![0; size],
+            memory_pointer: 0,
+        }
+    }
+}
+
+impl WrappingMemory for BoundedMemory {
+    fn cells(&self) -> (&[u8], usize) {
+        (&self.memory, self.memory_pointer)
+    }
+
+    fn cells_mut(&mut self) -> (&mut [u8], &mut usize) {
+        (&mut self.memory, &mut self.memory_pointer)
+    }
+}
+
+impl raw::RawMemory for BoundedMemory {
+    fn raw_memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+}
+
+/// The serialized form of all memory types, checked when deserializing so a memory pointer
+/// never points outside the memory.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct MemoryState {
+    memory: Vec<u8>,
+    memory_pointer: usize,
+}
+
+#[cfg(feature = "serde")]
+impl From<DynamicMemory> for MemoryState {
+    fn from(memory: DynamicMemory) -> Self {
+        Self {
+            memory: memory.memory,
+            memory_pointer: memory.memory_pointer,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<MemoryState> for DynamicMemory {
+    type Error = String;
+
+    fn try_from(state: MemoryState) -> Result<Self, Self::Error> {
+        if state.memory_pointer >= state.memory.len() {
+            return Err(format!(
+                "memory pointer {} outside of memory of size {}",
+                state.memory_pointer,
+                state.memory.len()
+            ));
+        }
+
+        Ok(Self {
+            memory: state.memory,
+            memory_pointer: state.memory_pointer,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<BoundedMemory> for MemoryState {
+    fn from(memory: BoundedMemory) -> Self {
+        Self {
+            memory: memory.memory,
+            memory_pointer: memory.memory_pointer,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<MemoryState> for BoundedMemory {
+    type Error = String;
+
+    fn try_from(state: MemoryState) -> Result<Self, Self::Error> {
+        DynamicMemory::try_from(state).map(|memory| Self {
+            memory: memory.memory,
+            memory_pointer: memory.memory_pointer,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<const SIZE: usize> From<FixedMemory<SIZE>> for MemoryState {
+    fn from(memory: FixedMemory<SIZE>) -> Self {
+        Self {
+            memory: memory.memory.to_vec(),
+            memory_pointer: memory.memory_pointer,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<const SIZE: usize> TryFrom<MemoryState> for FixedMemory<SIZE> {
+    type Error = String;
+
+    fn try_from(state: MemoryState) -> Result<Self, Self::Error> {
+        let len = state.memory.len();
+        let memory = state
+            .memory
+            .try_into()
+            .map_err(|_| format!("expected memory of size {}, got {}", SIZE, len))?;
+        if state.memory_pointer >= SIZE {
+            return Err(format!(
+                "memory pointer {} outside of memory of size {}",
+                state.memory_pointer, SIZE
+            ));
+        }
+
+        Ok(Self {
+            memory,
+            memory_pointer: state.memory_pointer,
+        })
     }
 }
 
@@ -171,11 +481,34 @@ where
     pub input: I,
     pub output: O,
 
-    jump_table: HashMap<usize, usize>,
+    jump_table: Vec<usize>,
     ended: bool,
     rand: R,
 }
 
+/// Resolves the matching `EIF` of every `IF` and vice versa, all other entries are unused.
+pub(crate) fn build_jump_table(instructions: &[Instruction]) -> Result<Vec<usize>, ParseError> {
+    let mut jump_table = vec![0; instructions.len()];
+    let mut open = Vec::new();
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::IF => open.push(i),
+            Instruction::EIF => {
+                let if_index = open.pop().ok_or(ParseError::MissingIf)?;
+                jump_table[if_index] = i;
+                jump_table[i] = if_index;
+            }
+            _ => {}
+        }
+    }
+
+    if open.is_empty() {
+        Ok(jump_table)
+    } else {
+        Err(ParseError::MissingEif)
+    }
+}
+
 pub fn default_input_stream() -> Option<u8> {
     let mut input = vec![0; 1];
     match std::io::stdin().read_exact(&mut input) {
@@ -194,17 +527,17 @@ pub fn default_output_stream(output: String) {
 }
 
 impl Interpreter<fn() -> Option<u8>, fn(String), DynamicMemory, ThreadRng> {
-    pub fn new(instructions: Vec<Instruction>) -> Self {
-        Self {
+    pub fn new(instructions: Vec<Instruction>) -> Result<Self, ParseError> {
+        Ok(Self {
+            jump_table: build_jump_table(&instructions)?,
             instructions,
             instruction_pointer: 0,
             memory: DynamicMemory::new(),
             input: default_input_stream,
             output: default_output_stream,
-            jump_table: HashMap::new(),
             ended: false,
             rand: rand::thread_rng(),
-        }
+        })
     }
 }
 
@@ -213,17 +546,44 @@ where
     I: FnMut() -> Option<u8>,
     O: FnMut(String),
 {
-    pub fn new_io(instructions: Vec<Instruction>, input: I, output: O) -> Self {
-        Self {
+    pub fn new_io(instructions: Vec<Instruction>, input: I, output: O) -> Result<Self, ParseError> {
+        Ok(Self {
+            jump_table: build_jump_table(&instructions)?,
             instructions,
             instruction_pointer: 0,
             memory: DynamicMemory::new(),
             input,
             output,
-            jump_table: HashMap::new(),
             ended: false,
             rand: rand::thread_rng(),
-        }
+        })
+    }
+}
+
+/// The state of an [`Interpreter`] without its input, output and random number generator, so
+/// a program can be stopped and resumed elsewhere.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot<M> {
+    pub instructions: Vec<Instruction>,
+    pub instruction_pointer: usize,
+    pub memory: M,
+    pub ended: bool,
+}
+
+impl<M: Memory> Interpreter<fn() -> Option<u8>, fn(String), M, ThreadRng> {
+    /// Resumes a program from a [`Snapshot`] with the default input and output.
+    pub fn from_snapshot(snapshot: Snapshot<M>) -> Result<Self, ParseError> {
+        Ok(Self {
+            jump_table: build_jump_table(&snapshot.instructions)?,
+            instructions: snapshot.instructions,
+            instruction_pointer: snapshot.instruction_pointer,
+            memory: snapshot.memory,
+            input: default_input_stream,
+            output: default_output_stream,
+            ended: snapshot.ended,
+            rand: rand::thread_rng(),
+        })
     }
 }
 
@@ -249,6 +609,19 @@ where
         }
     }
 
+    pub fn with_memory<MN: Memory>(self, memory: MN) -> Interpreter<I, O, MN, R> {
+        Interpreter::<I, O, MN, R> {
+            instructions: self.instructions,
+            instruction_pointer: self.instruction_pointer,
+            memory,
+            input: self.input,
+            output: self.output,
+            jump_table: self.jump_table,
+            ended: self.ended,
+            rand: self.rand,
+        }
+    }
+
     pub fn with_input<IN: FnMut() -> Option<u8>>(self, input: IN) -> Interpreter<IN, O, M, R> {
         Interpreter::<IN, O, M, R> {
             instructions: self.instructions,
@@ -305,6 +678,16 @@ where
         }
     }
 
+    /// Replaces the program and starts over at its first instruction, keeping the memory, input,
+    /// output and random number generator.
+    pub fn load(&mut self, instructions: Vec<Instruction>) -> Result<(), ParseError> {
+        self.jump_table = build_jump_table(&instructions)?;
+        self.instructions = instructions;
+        self.instruction_pointer = 0;
+        self.ended = false;
+        Ok(())
+    }
+
     pub fn step(&mut self) {
         match self.instructions[self.instruction_pointer] {
             Instruction::JIZ(n) => self.interpret_jiz(n),
@@ -319,10 +702,54 @@ where
             Instruction::IN => self.interpret_in(),
             Instruction::RND => self.interpret_rnd(),
             Instruction::JMP(n) => self.interpret_jmp(n),
+            Instruction::SCF(n) => self.interpret_scf(n),
+            Instruction::SCB(n) => self.interpret_scb(n),
+            Instruction::PUT(n) => self.interpret_put(n),
+            Instruction::SET(n) => self.interpret_set(n),
             Instruction::END => self.interpret_end(),
         }
     }
 
+    pub fn snapshot(&self) -> Snapshot<M>
+    where
+        M: Clone,
+    {
+        Snapshot {
+            instructions: self.instructions.clone(),
+            instruction_pointer: self.instruction_pointer,
+            memory: self.memory.clone(),
+            ended: self.ended,
+        }
+    }
+
+    pub fn has_ended(&self) -> bool {
+        self.ended || self.instruction_pointer >= self.instructions.len()
+    }
+
+    /// Whether an `END` was reached, without looking at the instructions, for native code
+    /// compiled from a different copy of them.
+    #[cfg(any(
+        all(feature = "jit", target_arch = "x86_64", target_os = "linux"),
+        feature = "cranelift"
+    ))]
+    pub(crate) fn reached_end(&self) -> bool {
+        self.ended
+    }
+
+    /// Stops the program at `instruction_pointer` after native code ran it.
+    #[cfg(any(
+        all(feature = "jit", target_arch = "x86_64", target_os = "linux"),
+        feature = "cranelift"
+    ))]
+    pub(crate) fn end_at(&mut self, instruction_pointer: usize) {
+        self.instruction_pointer = instruction_pointer;
+        self.ended = true;
+    }
+
+    pub(crate) fn random_byte(&mut self) -> u8 {
+        self.rand.gen::<u8>()
+    }
+
     pub fn run(&mut self) {
         while !self.ended {
             if self.instruction_pointer >= self.instructions.len() {
@@ -334,8 +761,49 @@ where
         }
     }
 
+    /// Runs instructions while they are parsed, like those of a
+    /// [`StreamParser`](crate::stream::StreamParser). Instructions outside of loops run as soon
+    /// as they arrive, a loop once its `EIF` does. Stops at the first error or `END`.
+    pub fn run_stream<E>(
+        &mut self,
+        instructions: impl IntoIterator<Item = Result<Instruction, E>>,
+    ) -> Result<(), E>
+    where
+        E: From<ParseError>,
+    {
+        let mut block = Vec::new();
+        let mut open_ifs = 0usize;
+        for instruction in instructions {
+            let instruction = instruction?;
+            match instruction {
+                Instruction::IF => open_ifs += 1,
+                Instruction::EIF => {
+                    open_ifs = open_ifs.checked_sub(1).ok_or(ParseError::MissingIf)?
+                }
+                _ => {}
+            }
+            block.push(instruction);
+            if open_ifs > 0 {
+                continue;
+            }
+
+            self.load(std::mem::take(&mut block))?;
+            self.run();
+            // END keeps the instruction pointer on itself
+            if self.instruction_pointer < self.instructions.len() {
+                return Ok(());
+            }
+        }
+
+        if open_ifs > 0 {
+            return Err(ParseError::MissingEif.into());
+        }
+        Ok(())
+    }
+
     fn interpret_rnd(&mut self) {
-        self.memory.set_memory_value(self.rand.gen::<u8>());
+        let value = self.random_byte();
+        self.memory.set_memory_value(value);
         self.instruction_pointer += 1;
     }
 
@@ -351,6 +819,16 @@ where
         self.instruction_pointer += 1;
     }
 
+    fn interpret_put(&mut self, n: u8) {
+        (self.output)(format!("{}", n as char));
+        self.instruction_pointer += 1;
+    }
+
+    fn interpret_set(&mut self, n: u8) {
+        self.memory.set_memory_value(n);
+        self.instruction_pointer += 1;
+    }
+
     fn interpret_bak(&mut self, n: u8) {
         self.memory.dec_memory_pointer(n as usize);
         self.instruction_pointer += 1;
@@ -373,28 +851,7 @@ where
 
     fn interpret_eif(&mut self) {
         if self.memory.get_memory_value() != 0 {
-            match self.jump_table.entry(self.instruction_pointer) {
-                Entry::Vacant(entry) => {
-                    let mut nested = -1;
-                    while nested != 0 {
-                        self.instruction_pointer -= 1;
-                        let nested_instruction = self.instructions[self.instruction_pointer];
-                        match nested_instruction {
-                            Instruction::IF => {
-                                nested += 1;
-                            }
-                            Instruction::EIF => {
-                                nested -= 1;
-                            }
-                            _ => {}
-                        }
-                    }
-                    entry.insert(self.instruction_pointer);
-                }
-                Entry::Occupied(entry) => {
-                    self.instruction_pointer = *entry.get();
-                }
-            };
+            self.instruction_pointer = self.jump_table[self.instruction_pointer];
         } else {
             self.instruction_pointer += 1;
         }
@@ -402,28 +859,7 @@ where
 
     fn interpret_if(&mut self) {
         if self.memory.get_memory_value() == 0 {
-            match self.jump_table.entry(self.instruction_pointer) {
-                Entry::Vacant(entry) => {
-                    let mut nested = 1;
-                    while nested != 0 {
-                        self.instruction_pointer += 1;
-                        let nested_instruction = self.instructions[self.instruction_pointer];
-                        match nested_instruction {
-                            Instruction::IF => {
-                                nested += 1;
-                            }
-                            Instruction::EIF => {
-                                nested -= 1;
-                            }
-                            _ => {}
-                        }
-                    }
-                    entry.insert(self.instruction_pointer);
-                }
-                Entry::Occupied(entry) => {
-                    self.instruction_pointer = *entry.get();
-                }
-            };
+            self.instruction_pointer = self.jump_table[self.instruction_pointer];
         } else {
             self.instruction_pointer += 1;
         }
@@ -452,18 +888,33 @@ where
     fn interpret_jmp(&mut self, n: usize) {
         self.instruction_pointer = n;
     }
+
+    // if no zero cell is reachable the scan loop would never terminate, so the instruction
+    // pointer stays put and the scan is retried on the next step
+    fn interpret_scf(&mut self, n: u8) {
+        if self.memory.scan_forward(n as usize) {
+            self.instruction_pointer += 1;
+        }
+    }
+
+    fn interpret_scb(&mut self, n: u8) {
+        if self.memory.scan_backward(n as usize) {
+            self.instruction_pointer += 1;
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{instruction::Instruction, interpreter::Memory};
-    use std::{cell::RefCell, sync::Arc};
+    use super::{DynamicMemory, FixedMemory};
+    use crate::{instruction::Instruction, interpreter::Memory, parser::ParseError};
+    use std::{cell::RefCell, rc::Rc};
 
     #[test]
     fn test_interpret_inc() {
         for i in 1..10 {
             let instructions = vec![Instruction::INC(i)];
-            let mut interpreter = super::Interpreter::new(instructions);
+            let mut interpreter = super::Interpreter::new(instructions).unwrap();
             interpreter.step();
             interpreter.memory.set_memory_pointer(0);
             assert_eq!(interpreter.memory.get_memory_value(), i);
@@ -473,7 +924,7 @@ mod test {
     #[test]
     fn test_interpret_inc_wrapping() {
         let instructions = vec![Instruction::INC(255), Instruction::INC(1)];
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
         interpreter.run();
         interpreter.memory.set_memory_pointer(0);
         assert_eq!(interpreter.memory.get_memory_value(), 0);
@@ -484,7 +935,7 @@ mod test {
         for i in 1..10 {
             // inc and dec same amount has to be 0
             let instructions = vec![Instruction::INC(i), Instruction::DEC(i)];
-            let mut interpreter = super::Interpreter::new(instructions);
+            let mut interpreter = super::Interpreter::new(instructions).unwrap();
             interpreter.run();
             interpreter.memory.set_memory_pointer(0);
             assert_eq!(interpreter.memory.get_memory_value(), 0);
@@ -494,7 +945,7 @@ mod test {
     #[test]
     fn test_interpret_dec_wrapping() {
         let instructions = vec![Instruction::DEC(1)];
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
         interpreter.run();
         interpreter.memory.set_memory_pointer(0);
         assert_eq!(interpreter.memory.get_memory_value(), 255);
@@ -504,8 +955,9 @@ mod test {
     fn test_interpret_inc_fixed() {
         for i in 1..10 {
             let instructions = vec![Instruction::INC(i)];
-            let mut interpreter =
-                super::Interpreter::new(instructions).with_fixed_size_memory::<10>();
+            let mut interpreter = super::Interpreter::new(instructions)
+                .unwrap()
+                .with_fixed_size_memory::<10>();
             assert_eq!(interpreter.memory.get_memory_size(), 10);
             interpreter.step();
             interpreter.memory.set_memory_pointer(0);
@@ -516,7 +968,9 @@ mod test {
     #[test]
     fn test_interpret_inc_wrapping_fixed() {
         let instructions = vec![Instruction::INC(255), Instruction::INC(1)];
-        let mut interpreter = super::Interpreter::new(instructions).with_fixed_size_memory::<10>();
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_fixed_size_memory::<10>();
         assert_eq!(interpreter.memory.get_memory_size(), 10);
         interpreter.run();
         interpreter.memory.set_memory_pointer(0);
@@ -528,8 +982,9 @@ mod test {
         for i in 1..10 {
             // inc and dec same amount has to be 0
             let instructions = vec![Instruction::INC(i), Instruction::DEC(i)];
-            let mut interpreter =
-                super::Interpreter::new(instructions).with_fixed_size_memory::<10>();
+            let mut interpreter = super::Interpreter::new(instructions)
+                .unwrap()
+                .with_fixed_size_memory::<10>();
             assert_eq!(interpreter.memory.get_memory_size(), 10);
             interpreter.run();
             interpreter.memory.set_memory_pointer(0);
@@ -540,7 +995,9 @@ mod test {
     #[test]
     fn test_interpret_dec_wrapping_fixed() {
         let instructions = vec![Instruction::DEC(1)];
-        let mut interpreter = super::Interpreter::new(instructions).with_fixed_size_memory::<10>();
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_fixed_size_memory::<10>();
         assert_eq!(interpreter.memory.get_memory_size(), 10);
         interpreter.run();
         interpreter.memory.set_memory_pointer(0);
@@ -551,7 +1008,7 @@ mod test {
     fn test_interpret_fwd() {
         for i in 1..250 {
             let instructions = vec![Instruction::FWD(i)];
-            let mut interpreter = super::Interpreter::new(instructions);
+            let mut interpreter = super::Interpreter::new(instructions).unwrap();
             interpreter.run();
             assert_eq!(interpreter.memory.get_memory_pointer(), i as usize);
         }
@@ -561,7 +1018,7 @@ mod test {
     fn test_interpret_bak() {
         for i in 1..250 {
             let instructions = vec![Instruction::FWD(i), Instruction::BAK(i)];
-            let mut interpreter = super::Interpreter::new(instructions);
+            let mut interpreter = super::Interpreter::new(instructions).unwrap();
             interpreter.run();
             assert_eq!(interpreter.memory.get_memory_pointer(), 0);
         }
@@ -571,8 +1028,9 @@ mod test {
     fn test_interpret_fwd_fixed() {
         for i in 1..250 {
             let instructions = vec![Instruction::FWD(i)];
-            let mut interpreter =
-                super::Interpreter::new(instructions).with_fixed_size_memory::<30000>();
+            let mut interpreter = super::Interpreter::new(instructions)
+                .unwrap()
+                .with_fixed_size_memory::<30000>();
             interpreter.run();
             assert_eq!(interpreter.memory.get_memory_pointer(), i as usize);
         }
@@ -582,8 +1040,9 @@ mod test {
     fn test_interpret_bak_fixed() {
         for i in 1..250 {
             let instructions = vec![Instruction::FWD(i), Instruction::BAK(i)];
-            let mut interpreter =
-                super::Interpreter::new(instructions).with_fixed_size_memory::<30000>();
+            let mut interpreter = super::Interpreter::new(instructions)
+                .unwrap()
+                .with_fixed_size_memory::<30000>();
             interpreter.run();
             assert_eq!(interpreter.memory.get_memory_pointer(), 0);
         }
@@ -592,7 +1051,7 @@ mod test {
     #[test]
     fn test_interpret_bak_wrapping() {
         let instructions = vec![Instruction::BAK(1)];
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
         interpreter.run();
         assert_eq!(
             interpreter.memory.get_memory_pointer(),
@@ -603,8 +1062,9 @@ mod test {
     #[test]
     fn test_interpret_bak_wrapping_fixed() {
         let instructions = vec![Instruction::BAK(1)];
-        let mut interpreter =
-            super::Interpreter::new(instructions).with_fixed_size_memory::<20000>();
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_fixed_size_memory::<20000>();
         interpreter.run();
         assert_eq!(
             interpreter.memory.get_memory_pointer(),
@@ -612,34 +1072,53 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_interpret_fwd_bak_wrapping_more_than_memory_size() {
+        // moves of more than the memory size wrap around as often as needed
+        for (instruction, pointer) in [(Instruction::FWD(10), 2), (Instruction::BAK(10), 2)] {
+            let mut interpreter = super::Interpreter::new(vec![instruction, Instruction::INC(1)])
+                .unwrap()
+                .with_fixed_size_memory::<4>();
+            interpreter.run();
+            assert_eq!(interpreter.memory.get_memory_pointer(), pointer);
+            assert_eq!(interpreter.memory.raw_memory()[pointer], 1);
+        }
+    }
+
     #[test]
     #[should_panic]
     fn test_interpret_out_is_called() {
         std::panic::set_hook(Box::new(|_| {}));
 
         let instructions = vec![Instruction::INC(b'H'), Instruction::OUT];
-        let mut interpreter = super::Interpreter::new(instructions).with_output(Box::new(|s| {
-            panic!("{}", s);
-        }));
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_output(Box::new(|s| {
+                panic!("{}", s);
+            }));
         interpreter.run();
     }
 
     #[test]
     fn test_interpret_out() {
         let instructions = vec![Instruction::INC(b'H'), Instruction::OUT];
-        let mut interpreter = super::Interpreter::new(instructions).with_output(Box::new(|s| {
-            assert_eq!(s, "H");
-        }));
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_output(Box::new(|s| {
+                assert_eq!(s, "H");
+            }));
         interpreter.run();
     }
 
     #[test]
     fn test_interpret_out_different() {
         let instructions = vec![Instruction::INC(b'H'), Instruction::OUT];
-        let mut interpreter = super::Interpreter::new(instructions).with_output(Box::new(|s| {
-            assert_eq!(s, "H");
-            assert_ne!(s, "A");
-        }));
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_output(Box::new(|s| {
+                assert_eq!(s, "H");
+                assert_ne!(s, "A");
+            }));
         interpreter.run();
     }
 
@@ -652,10 +1131,11 @@ mod test {
             Instruction::INC(b'A'),
             Instruction::OUT,
         ];
-        let output = Arc::new(RefCell::new(String::new()));
+        let output = Rc::new(RefCell::new(String::new()));
         let output_clone = output.clone();
-        let mut interpreter =
-            super::Interpreter::new(instructions).with_output(Box::new(move |s: String| {
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_output(Box::new(move |s: String| {
                 output_clone.borrow_mut().push_str(s.as_str());
             }));
         interpreter.run();
@@ -681,7 +1161,9 @@ mod test {
             Instruction::FWD(1),
             Instruction::IN,
         ];
-        let mut interpreter = super::Interpreter::new(instructions).with_input(Box::new(get_input));
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_input(Box::new(get_input));
         interpreter.run();
 
         interpreter.memory.set_memory_pointer(0);
@@ -711,6 +1193,7 @@ mod test {
             Instruction::IN,
         ];
         let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
             .with_input(Box::new(get_input))
             .with_fixed_size_memory::<200>();
         interpreter.run();
@@ -726,14 +1209,14 @@ mod test {
     #[test]
     fn test_not_ended() {
         let instructions = vec![Instruction::INC(1)];
-        let interpreter = super::Interpreter::new(instructions);
+        let interpreter = super::Interpreter::new(instructions).unwrap();
         assert!(!interpreter.ended);
     }
 
     #[test]
     fn test_ended_after_run() {
         let instructions = vec![Instruction::INC(1)];
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
         interpreter.run();
         assert!(interpreter.ended);
     }
@@ -741,7 +1224,7 @@ mod test {
     #[test]
     fn test_interpret_end() {
         let instructions = vec![Instruction::END];
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
         interpreter.run();
         assert!(interpreter.ended);
     }
@@ -756,7 +1239,7 @@ mod test {
             Instruction::INC(23),
             Instruction::END,
         ];
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
         interpreter.run();
 
         interpreter.memory.set_memory_pointer(0);
@@ -781,7 +1264,7 @@ mod test {
             Instruction::DEC(1),
             Instruction::EIF,
         ];
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
         assert_eq!(interpreter.instruction_pointer, 0);
         interpreter.step();
         assert_eq!(interpreter.instruction_pointer, 1);
@@ -809,7 +1292,7 @@ mod test {
             Instruction::EIF,
             Instruction::END,
         ];
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
         interpreter.run();
 
         interpreter.memory.set_memory_pointer(0);
@@ -825,7 +1308,7 @@ mod test {
     fn jump_if_zero_should_set_instruction_pointer_on_zero() {
         let instructions = vec![Instruction::JIZ(5)];
 
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
 
         // nothing run yet
         assert_eq!(interpreter.instruction_pointer, 0);
@@ -839,7 +1322,7 @@ mod test {
     fn jump_if_zero_should_not_set_instruction_pointer_on_non_zero_value() {
         let instructions = vec![Instruction::INC(1), Instruction::JIZ(5)];
 
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
 
         // nothing run yet
         assert_eq!(interpreter.instruction_pointer, 0);
@@ -861,7 +1344,7 @@ mod test {
     fn jump_not_zero_should_not_set_instruction_pointer_on_non_zero_value() {
         let instructions = vec![Instruction::JNZ(5)];
 
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
 
         // nothing run yet
         assert_eq!(interpreter.instruction_pointer, 0);
@@ -875,7 +1358,7 @@ mod test {
     fn jump_not_zero_should_set_instruction_pointer_on_zero_value() {
         let instructions = vec![Instruction::INC(1), Instruction::JNZ(5)];
 
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
 
         // nothing run yet
         assert_eq!(interpreter.instruction_pointer, 0);
@@ -899,7 +1382,7 @@ mod test {
         let rng = rand::rngs::mock::StepRng::new(random_value as u64, 0);
         let instructions = vec![Instruction::RND];
 
-        let mut interpreter = super::Interpreter::new(instructions).with_rng(rng);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap().with_rng(rng);
 
         // nothing run yet
         assert_eq!(interpreter.memory.get_memory_value(), 0);
@@ -916,6 +1399,7 @@ mod test {
         let instructions = vec![Instruction::RND];
 
         let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
             .with_rng(rng)
             .with_fixed_size_memory::<200>();
 
@@ -931,7 +1415,7 @@ mod test {
     fn jmp_should_set_instruction_pointer() {
         let instructions = vec![Instruction::JMP(9)];
 
-        let mut interpreter = super::Interpreter::new(instructions);
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
         // nothing run yet
         assert_eq!(interpreter.instruction_pointer, 0);
 
@@ -939,4 +1423,378 @@ mod test {
         // ip should be 1
         assert_eq!(interpreter.instruction_pointer, 9);
     }
+
+    #[test]
+    fn scan_forward_should_stop_at_zero_cell() {
+        let instructions = vec![
+            Instruction::INC(1),
+            Instruction::FWD(1),
+            Instruction::INC(1),
+            Instruction::BAK(1),
+            Instruction::SCF(1),
+        ];
+
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
+        interpreter.run();
+
+        assert_eq!(interpreter.memory.get_memory_pointer(), 2);
+    }
+
+    #[test]
+    fn scan_forward_should_grow_dynamic_memory() {
+        let mut instructions = vec![];
+        for _ in 0..32 {
+            instructions.push(Instruction::INC(1));
+            instructions.push(Instruction::FWD(1));
+        }
+        instructions.push(Instruction::BAK(32));
+        instructions.push(Instruction::SCF(1));
+
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
+        interpreter.run();
+
+        assert_eq!(interpreter.memory.get_memory_pointer(), 32);
+        assert_eq!(interpreter.memory.get_memory_size(), 64);
+        assert_eq!(interpreter.memory.get_memory_value(), 0);
+    }
+
+    #[test]
+    fn scan_forward_with_stride_should_skip_cells() {
+        let instructions = vec![
+            Instruction::INC(1),
+            Instruction::FWD(1),
+            Instruction::INC(1),
+            Instruction::FWD(1),
+            Instruction::INC(1),
+            Instruction::BAK(2),
+            Instruction::SCF(2),
+        ];
+
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
+        interpreter.run();
+
+        assert_eq!(interpreter.memory.get_memory_pointer(), 4);
+    }
+
+    #[test]
+    fn scan_backward_should_wrap_fixed_memory() {
+        let instructions = vec![
+            Instruction::INC(1),
+            Instruction::BAK(1),
+            Instruction::INC(1),
+            Instruction::FWD(1),
+            Instruction::SCB(1),
+        ];
+
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_fixed_size_memory::<10>();
+        interpreter.run();
+
+        assert_eq!(interpreter.memory.get_memory_pointer(), 8);
+    }
+
+    #[test]
+    fn scan_forward_should_wrap_fixed_memory() {
+        let instructions = vec![
+            Instruction::BAK(1),
+            Instruction::INC(1),
+            Instruction::SCF(1),
+        ];
+
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_fixed_size_memory::<10>();
+        interpreter.run();
+
+        assert_eq!(interpreter.memory.get_memory_pointer(), 0);
+    }
+
+    /// Implements only the required methods, so scans use the provided ones.
+    struct PlainMemory<M>(M);
+
+    impl<M: Memory> Memory for PlainMemory<M> {
+        fn get_memory_pointer(&self) -> usize {
+            self.0.get_memory_pointer()
+        }
+        fn set_memory_pointer(&mut self, pointer: usize) {
+            self.0.set_memory_pointer(pointer)
+        }
+        fn inc_memory_pointer(&mut self, value: usize) {
+            self.0.inc_memory_pointer(value)
+        }
+        fn dec_memory_pointer(&mut self, value: usize) {
+            self.0.dec_memory_pointer(value)
+        }
+        fn get_memory_value(&self) -> u8 {
+            self.0.get_memory_value()
+        }
+        fn set_memory_value(&mut self, value: u8) {
+            self.0.set_memory_value(value)
+        }
+        fn inc_memory_value(&mut self, value: u8) {
+            self.0.inc_memory_value(value)
+        }
+        fn dec_memory_value(&mut self, value: u8) {
+            self.0.dec_memory_value(value)
+        }
+        fn get_memory_size(&self) -> usize {
+            self.0.get_memory_size()
+        }
+        fn raw_memory(&self) -> &[u8] {
+            self.0.raw_memory()
+        }
+    }
+
+    #[test]
+    fn provided_scans_should_match_memchr_scans() {
+        for cells in 0u32..64 {
+            for pointer in 0..6 {
+                for stride in 0..8 {
+                    let mut fixed = FixedMemory::<6>::new();
+                    for i in 0..6 {
+                        fixed.memory[i] = (cells >> i & 1) as u8;
+                    }
+                    fixed.memory_pointer = pointer;
+                    let mut plain = PlainMemory(fixed.clone());
+
+                    assert_eq!(
+                        plain.scan_forward(stride),
+                        fixed.clone().scan_forward(stride)
+                    );
+                    let mut scanned = fixed.clone();
+                    scanned.scan_forward(stride);
+                    assert_eq!(plain.get_memory_pointer(), scanned.get_memory_pointer());
+
+                    let mut plain = PlainMemory(fixed.clone());
+                    assert_eq!(plain.scan_backward(stride), fixed.scan_backward(stride));
+                    assert_eq!(plain.get_memory_pointer(), fixed.get_memory_pointer());
+                }
+            }
+        }
+
+        let mut dynamic = DynamicMemory::new();
+        dynamic.memory.fill(1);
+        let mut plain = PlainMemory(dynamic.clone());
+        assert!(plain.scan_forward(3));
+        assert!(dynamic.scan_forward(3));
+        assert_eq!(plain.get_memory_pointer(), dynamic.get_memory_pointer());
+    }
+
+    #[test]
+    fn bounded_memory_should_wrap() {
+        let instructions = vec![
+            Instruction::BAK(1),
+            Instruction::INC(1),
+            Instruction::FWD(2),
+            Instruction::INC(2),
+        ];
+
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_memory(super::BoundedMemory::new(7));
+        interpreter.run();
+
+        assert_eq!(interpreter.memory.get_memory_pointer(), 1);
+        assert_eq!(interpreter.memory.raw_memory(), &[0, 2, 0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn scan_without_zero_cell_should_not_advance() {
+        let instructions = vec![Instruction::INC(1), Instruction::SCF(1)];
+
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_fixed_size_memory::<1>();
+        interpreter.step();
+        interpreter.step();
+
+        assert_eq!(interpreter.instruction_pointer, 1);
+        assert_eq!(interpreter.memory.get_memory_pointer(), 0);
+    }
+
+    #[test]
+    fn scan_should_match_loop() {
+        for stride in 1..6 {
+            for program in [
+                vec![Instruction::IF, Instruction::FWD(stride), Instruction::EIF],
+                vec![Instruction::IF, Instruction::BAK(stride), Instruction::EIF],
+            ] {
+                let mut setup = vec![];
+                for i in 0..12 {
+                    setup.push(Instruction::INC(i % 4));
+                    setup.push(Instruction::FWD(1));
+                }
+                setup.push(Instruction::BAK(5));
+
+                let looped = [setup.clone(), program].concat();
+                let scanned = crate::optimizer::Optimize::optimize(
+                    &crate::optimizer::Optimizer::new(),
+                    &looped,
+                );
+
+                let mut expected = super::Interpreter::new(looped)
+                    .unwrap()
+                    .with_fixed_size_memory::<16>();
+                expected.run();
+                let mut actual = super::Interpreter::new(scanned)
+                    .unwrap()
+                    .with_fixed_size_memory::<16>();
+                actual.run();
+
+                assert_eq!(
+                    expected.memory.get_memory_pointer(),
+                    actual.memory.get_memory_pointer()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn new_should_fail_on_missing_eif() {
+        let instructions = vec![Instruction::IF, Instruction::IF, Instruction::EIF];
+        let interpreter = super::Interpreter::new(instructions);
+        assert_eq!(interpreter.err(), Some(ParseError::MissingEif));
+    }
+
+    #[test]
+    fn new_should_fail_on_missing_if() {
+        let instructions = vec![Instruction::IF, Instruction::EIF, Instruction::EIF];
+        let interpreter = super::Interpreter::new(instructions);
+        assert_eq!(interpreter.err(), Some(ParseError::MissingIf));
+    }
+
+    #[test]
+    fn jump_table_should_match_nested_if_eif() {
+        let instructions = vec![
+            Instruction::IF,
+            Instruction::IF,
+            Instruction::EIF,
+            Instruction::IF,
+            Instruction::EIF,
+            Instruction::EIF,
+        ];
+        let jump_table = super::build_jump_table(&instructions).unwrap();
+        assert_eq!(jump_table, vec![5, 2, 1, 4, 3, 0]);
+    }
+
+    #[test]
+    fn load_should_keep_memory() {
+        let mut interpreter = super::Interpreter::new(vec![Instruction::INC(2), Instruction::END])
+            .unwrap()
+            .with_output(|_| {});
+        interpreter.run();
+        interpreter
+            .load(vec![Instruction::FWD(1), Instruction::INC(1)])
+            .unwrap();
+        interpreter.run();
+
+        assert_eq!(&interpreter.memory.raw_memory()[..2], &[2, 1]);
+        assert_eq!(
+            interpreter.load(vec![Instruction::EIF]),
+            Err(ParseError::MissingIf)
+        );
+    }
+
+    #[test]
+    fn snapshot_should_resume() {
+        let instructions = vec![
+            Instruction::INC(2),
+            Instruction::FWD(40),
+            Instruction::INC(3),
+            Instruction::OUT,
+        ];
+        let mut interpreter = super::Interpreter::new(instructions).unwrap();
+        interpreter.step();
+        interpreter.step();
+        let snapshot = interpreter.snapshot();
+        assert_eq!(snapshot.instruction_pointer, 2);
+        assert_eq!(snapshot.memory.get_memory_size(), 64);
+
+        let output = Rc::new(RefCell::new(String::new()));
+        let output_clone = output.clone();
+        let mut resumed = super::Interpreter::from_snapshot(snapshot)
+            .unwrap()
+            .with_output(move |s: String| output_clone.borrow_mut().push_str(&s));
+        resumed.run();
+        assert_eq!(output.borrow().as_str(), "\u{3}");
+        assert_eq!(resumed.memory.raw_memory()[0], 2);
+    }
+
+    #[test]
+    fn run_stream_should_run_blocks_as_they_arrive() {
+        let output = Rc::new(RefCell::new(String::new()));
+        let output_clone = output.clone();
+        let mut interpreter = super::Interpreter::new(vec![])
+            .unwrap()
+            .with_output(move |s: String| output_clone.borrow_mut().push_str(&s));
+        let instructions = vec![
+            Ok(Instruction::INC(2)),
+            Ok(Instruction::IF),
+            Ok(Instruction::OUT),
+            Ok(Instruction::DEC(1)),
+            Ok(Instruction::EIF),
+            Err(ParseError::MissingIf),
+        ];
+        assert_eq!(
+            interpreter.run_stream(instructions),
+            Err(ParseError::MissingIf)
+        );
+        // the loop ran before the error was read
+        assert_eq!(output.borrow().as_str(), "\u{2}\u{1}");
+
+        // nothing after END is read
+        let instructions = vec![Ok(Instruction::END), Err(ParseError::MissingIf)];
+        assert_eq!(interpreter.run_stream(instructions), Ok(()));
+        let instructions = vec![Ok(Instruction::IF), Ok(Instruction::OUT)];
+        assert_eq!(
+            interpreter.run_stream(instructions),
+            Err(ParseError::MissingEif)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn snapshot_should_round_trip_through_json() {
+        let instructions = vec![Instruction::INC(7), Instruction::JMP(0)];
+        let mut interpreter = super::Interpreter::new(instructions)
+            .unwrap()
+            .with_fixed_size_memory::<4>();
+        interpreter.step();
+        let snapshot = interpreter.snapshot();
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            json,
+            r#"{"instructions":[{"INC":7},{"JMP":0}],"instruction_pointer":1,"memory":{"memory":[7,0,0,0],"memory_pointer":0},"ended":false}"#
+        );
+        let deserialized: super::Snapshot<super::FixedMemory<4>> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, snapshot);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn memory_should_be_validated_when_deserializing() {
+        let memory = serde_json::from_str::<super::FixedMemory<4>>(
+            r#"{"memory":[0,0,0],"memory_pointer":0}"#,
+        );
+        assert!(memory.is_err());
+
+        let memory =
+            serde_json::from_str::<super::DynamicMemory>(r#"{"memory":[0],"memory_pointer":1}"#);
+        assert!(memory.is_err());
+
+        let memory =
+            serde_json::from_str::<super::DynamicMemory>(r#"{"memory":[0],"memory_pointer":0}"#);
+        assert!(memory.is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parse_error_should_round_trip_through_json() {
+        let error = ParseError::NeedsArgument(3);
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(serde_json::from_str::<ParseError>(&json).unwrap(), error);
+    }
 }
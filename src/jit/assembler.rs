@@ -0,0 +1,245 @@
+//! Just enough of an x86-64 encoder for the JIT. Register roles are fixed:
+//! - `rbx` holds the [`Context`](super::Context)
+//! - `r12` holds the base address of the tape
+//! - `r13` holds the memory pointer
+//! - `r14` holds the length of the tape
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Label(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Condition {
+    Equal,
+    NotEqual,
+    Below,
+    AboveOrEqual,
+    Sign,
+}
+
+impl Condition {
+    const fn opcode(self) -> u8 {
+        match self {
+            Condition::Below => 0x82,
+            Condition::AboveOrEqual => 0x83,
+            Condition::Equal => 0x84,
+            Condition::NotEqual => 0x85,
+            Condition::Sign => 0x88,
+        }
+    }
+}
+
+pub(crate) struct Assembler {
+    code: Vec<u8>,
+    labels: Vec<Option<usize>>,
+    /// Positions of 32 bit displacements to patch, relative to the end of the displacement.
+    fixups: Vec<(usize, Label)>,
+}
+
+impl Assembler {
+    pub(crate) fn new() -> Self {
+        Self {
+            code: Vec::new(),
+            labels: Vec::new(),
+            fixups: Vec::new(),
+        }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.code.len()
+    }
+
+    pub(crate) fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    pub(crate) fn bind(&mut self, label: Label) {
+        self.labels[label.0] = Some(self.code.len());
+    }
+
+    pub(crate) fn label_position(&self, label: Label) -> usize {
+        self.labels[label.0].expect("label is bound")
+    }
+
+    pub(crate) fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn emit_u32(&mut self, value: u32) {
+        self.code.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn emit_displacement(&mut self, label: Label) {
+        self.fixups.push((self.code.len(), label));
+        self.emit_u32(0);
+    }
+
+    /// `jmp label`
+    pub(crate) fn jmp(&mut self, label: Label) {
+        self.emit(&[0xE9]);
+        self.emit_displacement(label);
+    }
+
+    /// `jcc label`
+    pub(crate) fn jcc(&mut self, condition: Condition, label: Label) {
+        self.emit(&[0x0F, condition.opcode()]);
+        self.emit_displacement(label);
+    }
+
+    /// `lea rax, [rip + label]`
+    pub(crate) fn lea_rax(&mut self, label: Label) {
+        self.emit(&[0x48, 0x8D, 0x05]);
+        self.emit_displacement(label);
+    }
+
+    /// `cmp byte [r12 + r13], 0`
+    pub(crate) fn cmp_cell_zero(&mut self) {
+        self.emit(&[0x43, 0x80, 0x3C, 0x2C, 0x00]);
+    }
+
+    /// `add byte [r12 + r13], value`
+    pub(crate) fn add_cell(&mut self, value: u8) {
+        self.emit(&[0x43, 0x80, 0x04, 0x2C, value]);
+    }
+
+    /// `sub byte [r12 + r13], value`
+    pub(crate) fn sub_cell(&mut self, value: u8) {
+        self.emit(&[0x43, 0x80, 0x2C, 0x2C, value]);
+    }
+
+    /// `mov byte [r12 + r13], value`
+    pub(crate) fn set_cell(&mut self, value: u8) {
+        self.emit(&[0x43, 0xC6, 0x04, 0x2C, value]);
+    }
+
+    /// `mov byte [r12 + r13], al`
+    pub(crate) fn store_al(&mut self) {
+        self.emit(&[0x43, 0x88, 0x04, 0x2C]);
+    }
+
+    /// `movzx esi, byte [r12 + r13]`
+    pub(crate) fn load_cell_esi(&mut self) {
+        self.emit(&[0x43, 0x0F, 0xB6, 0x34, 0x2C]);
+    }
+
+    /// `mov esi, value`
+    pub(crate) fn mov_esi(&mut self, value: u32) {
+        self.emit(&[0xBE]);
+        self.emit_u32(value);
+    }
+
+    /// `mov rsi, value` sign extended
+    pub(crate) fn mov_rsi(&mut self, value: i32) {
+        self.emit(&[0x48, 0xC7, 0xC6]);
+        self.emit_u32(value as u32);
+    }
+
+    /// `mov eax, value`
+    pub(crate) fn mov_eax(&mut self, value: u32) {
+        self.emit(&[0xB8]);
+        self.emit_u32(value);
+    }
+
+    /// `test eax, eax`
+    pub(crate) fn test_eax(&mut self) {
+        self.emit(&[0x85, 0xC0]);
+    }
+
+    /// `lea rax, [r13 + value]`
+    pub(crate) fn lea_rax_pointer(&mut self, value: u32) {
+        self.emit(&[0x49, 0x8D, 0x85]);
+        self.emit_u32(value);
+    }
+
+    /// `mov rax, r13`
+    pub(crate) fn mov_rax_pointer(&mut self) {
+        self.emit(&[0x4C, 0x89, 0xE8]);
+    }
+
+    /// `sub rax, value`
+    pub(crate) fn sub_rax(&mut self, value: u32) {
+        self.emit(&[0x48, 0x2D]);
+        self.emit_u32(value);
+    }
+
+    /// `cmp rax, r14`
+    pub(crate) fn cmp_rax_length(&mut self) {
+        self.emit(&[0x4C, 0x39, 0xF0]);
+    }
+
+    /// `mov r13, rax`
+    pub(crate) fn mov_pointer_rax(&mut self) {
+        self.emit(&[0x49, 0x89, 0xC5]);
+    }
+
+    /// `mov rdi, rbx` followed by `call [rbx + offset]`
+    pub(crate) fn call_context(&mut self, offset: u8) {
+        self.emit(&[0x48, 0x89, 0xDF, 0xFF, 0x53, offset]);
+    }
+
+    /// Loads `r12`, `r13` and `r14` from the context.
+    pub(crate) fn load_tape(&mut self, base: u8, pointer: u8, length: u8) {
+        self.emit(&[0x4C, 0x8B, 0x63, base]);
+        self.emit(&[0x4C, 0x8B, 0x6B, pointer]);
+        self.emit(&[0x4C, 0x8B, 0x73, length]);
+    }
+
+    /// `mov [rbx + offset], r13`
+    pub(crate) fn store_pointer(&mut self, offset: u8) {
+        self.emit(&[0x4C, 0x89, 0x6B, offset]);
+    }
+
+    /// Saves the callee saved registers and moves the context from `rdi` to `rbx`, leaving the
+    /// stack aligned for calls.
+    pub(crate) fn prologue(&mut self) {
+        self.emit(&[0x53, 0x41, 0x54, 0x41, 0x55, 0x41, 0x56, 0x41, 0x57]);
+        self.emit(&[0x48, 0x89, 0xFB]);
+    }
+
+    pub(crate) fn epilogue(&mut self) {
+        self.emit(&[0x41, 0x5F, 0x41, 0x5E, 0x41, 0x5D, 0x41, 0x5C, 0x5B, 0xC3]);
+    }
+
+    /// Jumps to the entry of the table at `table` selected by `rsi`, entries are 32 bit offsets
+    /// relative to the start of the table.
+    pub(crate) fn jump_through_table(&mut self, table: Label) {
+        self.lea_rax(table);
+        // movsxd rcx, dword [rax + rsi * 4]
+        self.emit(&[0x48, 0x63, 0x0C, 0xB0]);
+        // add rax, rcx
+        self.emit(&[0x48, 0x01, 0xC8]);
+        // jmp rax
+        self.emit(&[0xFF, 0xE0]);
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        for (position, label) in std::mem::take(&mut self.fixups) {
+            let target = self.label_position(label) as i64;
+            let displacement = (target - (position as i64 + 4)) as i32;
+            self.code[position..position + 4].copy_from_slice(&displacement.to_le_bytes());
+        }
+
+        self.code
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Assembler, Condition};
+
+    #[test]
+    fn test_jumps_patched() {
+        let mut assembler = Assembler::new();
+        let start = assembler.new_label();
+        let end = assembler.new_label();
+        assembler.bind(start);
+        assembler.jcc(Condition::Equal, end);
+        assembler.jmp(start);
+        assembler.bind(end);
+
+        assert_eq!(
+            assembler.finish(),
+            vec![0x0F, 0x84, 0x05, 0x00, 0x00, 0x00, 0xE9, 0xF5, 0xFF, 0xFF, 0xFF]
+        );
+    }
+}
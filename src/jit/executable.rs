@@ -0,0 +1,54 @@
+use std::{io, ptr::NonNull};
+
+/// A page aligned mapping holding machine code, writable while it is filled and only
+/// readable and executable afterwards.
+pub(crate) struct ExecutableBuffer {
+    pointer: NonNull<u8>,
+    len: usize,
+}
+
+impl ExecutableBuffer {
+    pub(crate) fn new(code: &[u8]) -> io::Result<Self> {
+        let len = code.len().max(1);
+
+        // SAFETY: an anonymous private mapping does not alias any memory, it is only written
+        // before it is made executable and unmapped exactly once in `drop`
+        unsafe {
+            let pointer = libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if pointer == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+
+            let buffer = Self {
+                pointer: NonNull::new_unchecked(pointer.cast()),
+                len,
+            };
+            std::ptr::copy_nonoverlapping(code.as_ptr(), buffer.pointer.as_ptr(), code.len());
+            if libc::mprotect(pointer, len, libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(buffer)
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const u8 {
+        self.pointer.as_ptr()
+    }
+}
+
+impl Drop for ExecutableBuffer {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was created in `new` with this length and is not used anymore
+        unsafe {
+            libc::munmap(self.pointer.as_ptr().cast(), self.len);
+        }
+    }
+}
@@ -0,0 +1,371 @@
+//! Translates instructions into x86-64 machine code, available with the `jit` feature on
+//! x86-64 Linux. The generated code runs directly on the interpreter's memory and calls back into
+//! Rust for I/O, `RND` and moves leaving the tape.
+
+mod assembler;
+mod executable;
+
+use self::{
+    assembler::{Assembler, Condition, Label},
+    executable::ExecutableBuffer,
+};
+use crate::{
+    instruction::Instruction,
+    interpreter::{build_jump_table, raw::RawMemory, Interpreter},
+    native::{self, Context},
+    parser::ParseError,
+};
+use rand::RngCore;
+
+#[derive(Debug)]
+pub enum JitError {
+    InvalidProgram(ParseError),
+    Mmap(std::io::Error),
+}
+
+impl std::fmt::Display for JitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JitError::InvalidProgram(error) => write!(f, "Invalid program: {}", error),
+            JitError::Mmap(error) => write!(f, "Failed to map executable memory: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for JitError {}
+
+const BASE: u8 = native::BASE as u8;
+const POINTER: u8 = native::POINTER as u8;
+const LENGTH: u8 = native::LENGTH as u8;
+const OUTPUT: u8 = native::OUTPUT as u8;
+const INPUT: u8 = native::INPUT as u8;
+const RANDOM: u8 = native::RANDOM as u8;
+const SEEK: u8 = native::SEEK as u8;
+
+/// Pointer moves leaving the tape, compiled out of line so the common case is a single
+/// compare and branch.
+struct SlowMove {
+    start: Label,
+    delta: i32,
+    resume: Label,
+}
+
+/// Machine code for a program, which can be run any number of times.
+pub struct JitProgram {
+    code: ExecutableBuffer,
+    len: usize,
+}
+
+impl JitProgram {
+    pub fn compile(instructions: &[Instruction]) -> Result<Self, JitError> {
+        let jump_table = build_jump_table(instructions).map_err(JitError::InvalidProgram)?;
+
+        let mut assembler = Assembler::new();
+        let labels: Vec<Label> = (0..=instructions.len())
+            .map(|_| assembler.new_label())
+            .collect();
+        let epilogue = assembler.new_label();
+        let entry_table = assembler.new_label();
+        let mut slow_moves = Vec::new();
+
+        assembler.prologue();
+        assembler.load_tape(BASE, POINTER, LENGTH);
+        assembler.jump_through_table(entry_table);
+
+        let target = |n: usize| labels[n.min(instructions.len())];
+        for (i, instruction) in instructions.iter().enumerate() {
+            assembler.bind(labels[i]);
+            match *instruction {
+                Instruction::INC(n) => assembler.add_cell(n),
+                Instruction::DEC(n) => assembler.sub_cell(n),
+                Instruction::SET(n) => assembler.set_cell(n),
+                Instruction::FWD(n) => compile_move(&mut assembler, &mut slow_moves, n as i32),
+                Instruction::BAK(n) => compile_move(&mut assembler, &mut slow_moves, -(n as i32)),
+                Instruction::SCF(n) | Instruction::SCB(n) => {
+                    let done = assembler.new_label();
+                    assembler.cmp_cell_zero();
+                    assembler.jcc(Condition::Equal, done);
+                    let delta = match instruction {
+                        Instruction::SCF(_) => n as i32,
+                        _ => -(n as i32),
+                    };
+                    compile_move(&mut assembler, &mut slow_moves, delta);
+                    assembler.jmp(labels[i]);
+                    assembler.bind(done);
+                }
+                Instruction::OUT => {
+                    assembler.load_cell_esi();
+                    assembler.call_context(OUTPUT);
+                }
+                Instruction::PUT(n) => {
+                    assembler.mov_esi(n as u32);
+                    assembler.call_context(OUTPUT);
+                }
+                Instruction::IN => {
+                    let skip = assembler.new_label();
+                    assembler.call_context(INPUT);
+                    assembler.test_eax();
+                    assembler.jcc(Condition::Sign, skip);
+                    assembler.store_al();
+                    assembler.bind(skip);
+                }
+                Instruction::RND => {
+                    assembler.call_context(RANDOM);
+                    assembler.store_al();
+                }
+                Instruction::IF | Instruction::JIZ(_) => {
+                    let n = match *instruction {
+                        Instruction::JIZ(n) => n,
+                        _ => jump_table[i],
+                    };
+                    assembler.cmp_cell_zero();
+                    assembler.jcc(Condition::Equal, target(n));
+                }
+                Instruction::EIF | Instruction::JNZ(_) => {
+                    let n = match *instruction {
+                        Instruction::JNZ(n) => n,
+                        _ => jump_table[i],
+                    };
+                    assembler.cmp_cell_zero();
+                    assembler.jcc(Condition::NotEqual, target(n));
+                }
+                Instruction::JMP(n) => assembler.jmp(target(n)),
+                Instruction::END => {
+                    assembler.mov_eax(i as u32);
+                    assembler.jmp(epilogue);
+                }
+            }
+        }
+
+        assembler.bind(labels[instructions.len()]);
+        assembler.mov_eax(instructions.len() as u32);
+        assembler.bind(epilogue);
+        assembler.store_pointer(POINTER);
+        assembler.epilogue();
+
+        for slow_move in slow_moves {
+            assembler.bind(slow_move.start);
+            assembler.store_pointer(POINTER);
+            assembler.mov_rsi(slow_move.delta);
+            assembler.call_context(SEEK);
+            assembler.load_tape(BASE, POINTER, LENGTH);
+            assembler.jmp(slow_move.resume);
+        }
+
+        assembler.bind(entry_table);
+        let table = assembler.position();
+        for label in &labels {
+            let offset = assembler.label_position(*label) as i64 - table as i64;
+            assembler.emit_u32(offset as i32 as u32);
+        }
+
+        Ok(Self {
+            code: ExecutableBuffer::new(&assembler.finish()).map_err(JitError::Mmap)?,
+            len: instructions.len(),
+        })
+    }
+
+    /// Runs the program with the memory, input, output and random number generator of the
+    /// interpreter, starting at its instruction pointer. The interpreter's own instructions are
+    /// not looked at, it is up to the caller that they match the compiled ones.
+    pub fn run<I, O, M, R>(&self, interpreter: &mut Interpreter<I, O, M, R>)
+    where
+        I: FnMut() -> Option<u8>,
+        O: FnMut(String),
+        M: RawMemory,
+        R: RngCore,
+    {
+        if interpreter.reached_end() {
+            return;
+        }
+
+        let entry = interpreter.instruction_pointer.min(self.len);
+        let mut context = Context::new(interpreter);
+
+        // SAFETY: the code was generated for this calling convention, it only touches the tape
+        // inside the bounds stored in the context and the callbacks refresh them whenever the
+        // memory may have been reallocated
+        let stop = unsafe {
+            let code: unsafe extern "C" fn(*mut Context, usize) -> u32 =
+                std::mem::transmute(self.code.as_ptr());
+            code(&mut context, entry)
+        };
+
+        context.finish(interpreter, stop as usize);
+    }
+}
+
+fn compile_move(assembler: &mut Assembler, slow_moves: &mut Vec<SlowMove>, delta: i32) {
+    let slow_move = SlowMove {
+        start: assembler.new_label(),
+        delta,
+        resume: assembler.new_label(),
+    };
+
+    if delta >= 0 {
+        assembler.lea_rax_pointer(delta as u32);
+        assembler.cmp_rax_length();
+        assembler.jcc(Condition::AboveOrEqual, slow_move.start);
+    } else {
+        assembler.mov_rax_pointer();
+        assembler.sub_rax(delta.unsigned_abs());
+        assembler.jcc(Condition::Below, slow_move.start);
+    }
+    assembler.mov_pointer_rax();
+    assembler.bind(slow_move.resume);
+
+    slow_moves.push(slow_move);
+}
+
+impl<I, O, M, R> Interpreter<I, O, M, R>
+where
+    I: FnMut() -> Option<u8>,
+    O: FnMut(String),
+    M: RawMemory,
+    R: RngCore,
+{
+    /// Compiles the instructions to machine code and runs them until the program ends, see
+    /// [`JitProgram`].
+    pub fn run_jit(&mut self) -> Result<(), JitError> {
+        JitProgram::compile(&self.instructions)?.run(self);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::JitProgram;
+    use crate::{
+        instruction::Instruction,
+        interpreter::{FixedMemory, Interpreter, Memory},
+        optimizer::{Optimize, Optimizer, PartialEvaluator},
+        parser::Parser,
+        samples::{HELLO_WORLD, HELLO_WORLD_SHORTEST},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Runs the program with the interpreter and the JIT on fixed memory, returns the output
+    /// after checking that both agree on it and on the memory.
+    fn run_both(instructions: &[Instruction], input: &[u8]) -> String {
+        let mut results = Vec::new();
+        for jit in [false, true] {
+            let mut output = String::new();
+            let mut input = input.to_vec();
+            let mut interpreter = Interpreter::new(instructions.to_vec())
+                .unwrap()
+                .with_fixed_size_memory::<100>()
+                .with_io(
+                    || match input.is_empty() {
+                        true => None,
+                        false => Some(input.remove(0)),
+                    },
+                    |s: String| output.push_str(&s),
+                )
+                .with_rng(StdRng::seed_from_u64(3));
+            match jit {
+                true => interpreter.run_jit().unwrap(),
+                false => interpreter.run(),
+            }
+            assert!(interpreter.has_ended());
+            let memory = interpreter.memory.raw_memory().to_vec();
+            let pointer = interpreter.memory.get_memory_pointer();
+            drop(interpreter);
+
+            results.push((output, memory, pointer));
+        }
+
+        assert_eq!(results[0], results[1]);
+        results.remove(0).0
+    }
+
+    #[test]
+    fn test_hello_world() {
+        let instructions = Parser::parse(HELLO_WORLD).unwrap();
+        assert_eq!(run_both(&instructions, b""), "Hello World!");
+    }
+
+    #[test]
+    fn test_hello_world_shortest_optimized() {
+        let instructions = Parser::parse(HELLO_WORLD_SHORTEST).unwrap();
+        for program in [
+            instructions.clone(),
+            Optimizer::new().optimize(&instructions),
+            PartialEvaluator::<FixedMemory<100>>::new().optimize(&instructions),
+        ] {
+            assert_eq!(run_both(&program, b""), "Hello, World!");
+        }
+    }
+
+    #[test]
+    fn test_input_random_and_wrapping() {
+        let instructions = vec![
+            Instruction::IN,
+            Instruction::IF,
+            Instruction::OUT,
+            Instruction::BAK(7),
+            Instruction::RND,
+            Instruction::FWD(7),
+            Instruction::SET(0),
+            Instruction::IN,
+            Instruction::EIF,
+            Instruction::BAK(7),
+            Instruction::OUT,
+            Instruction::SCB(3),
+            Instruction::PUT(0xE9),
+        ];
+
+        assert!(run_both(&instructions, b"poem").starts_with("poem"));
+    }
+
+    #[test]
+    fn test_dynamic_memory_grows() {
+        let mut instructions = vec![Instruction::INC(66)];
+        instructions.extend([Instruction::FWD(250); 40]);
+        instructions.extend([Instruction::INC(65), Instruction::OUT, Instruction::SCF(1)]);
+        instructions.extend([Instruction::BAK(250); 40]);
+        instructions.extend([Instruction::OUT, Instruction::BAK(1), Instruction::OUT]);
+
+        let mut results = Vec::new();
+        for jit in [false, true] {
+            let mut output = String::new();
+            let mut interpreter = Interpreter::new(instructions.clone())
+                .unwrap()
+                .with_output(|s: String| output.push_str(&s));
+            match jit {
+                true => interpreter.run_jit().unwrap(),
+                false => interpreter.run(),
+            }
+            let memory = interpreter.memory.raw_memory().to_vec();
+            let pointer = interpreter.memory.get_memory_pointer();
+            drop(interpreter);
+
+            results.push((output, memory, pointer));
+        }
+
+        assert_eq!(results[0], results[1]);
+        assert_eq!(results[0].0, "A\0B");
+    }
+
+    #[test]
+    fn test_resumes_at_instruction_pointer() {
+        let instructions = vec![
+            Instruction::PUT(b'a'),
+            Instruction::PUT(b'b'),
+            Instruction::END,
+        ];
+        let program = JitProgram::compile(&instructions).unwrap();
+
+        let mut output = String::new();
+        let mut interpreter = Interpreter::new(instructions)
+            .unwrap()
+            .with_output(|s: String| output.push_str(&s));
+        interpreter.step();
+        program.run(&mut interpreter);
+        assert!(interpreter.has_ended());
+        assert_eq!(interpreter.instruction_pointer, 2);
+        drop(interpreter);
+
+        assert_eq!(output, "ab");
+    }
+}
@@ -1,7 +1,29 @@
+pub mod annotation;
+pub mod brainfuck;
+pub mod bytecode;
+pub mod compiler;
+pub mod format;
+pub mod generator;
+pub mod incremental;
 pub mod instruction;
 pub mod interpreter;
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+pub mod jit;
+pub mod lint;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+#[cfg(any(
+    all(feature = "jit", target_arch = "x86_64", target_os = "linux"),
+    feature = "cranelift"
+))]
+mod native;
 pub mod optimizer;
 pub mod parser;
+pub mod repl;
+#[cfg(test)]
+pub(crate) mod samples;
+pub mod stream;
+pub mod synonyms;
 
 #[cfg(test)]
 mod test {
@@ -11,13 +33,13 @@ mod test {
         let instructions = crate::parser::Parser::parse(input);
         assert!(instructions.is_ok());
         let instructions = instructions.unwrap();
-        let output = std::sync::Arc::new(std::cell::RefCell::new(String::new()));
+        let output = std::rc::Rc::new(std::cell::RefCell::new(String::new()));
         let output_clone = output.clone();
-        let mut interpreter = crate::interpreter::Interpreter::new(instructions).with_output(
-            Box::new(move |s: String| {
+        let mut interpreter = crate::interpreter::Interpreter::new(instructions)
+            .unwrap()
+            .with_output(Box::new(move |s: String| {
                 output_clone.borrow_mut().push_str(s.as_str());
-            }),
-        );
+            }));
         interpreter.run();
 
         let result = output.borrow().to_string();
@@ -32,9 +54,10 @@ mod test {
         let instructions = crate::parser::Parser::parse(input);
         assert!(instructions.is_ok());
         let instructions = instructions.unwrap();
-        let output = std::sync::Arc::new(std::cell::RefCell::new(String::new()));
+        let output = std::rc::Rc::new(std::cell::RefCell::new(String::new()));
         let output_clone = output.clone();
         let mut interpreter = crate::interpreter::Interpreter::new(instructions)
+            .unwrap()
             .with_fixed_size_memory::<1>()
             .with_output(Box::new(move |s: String| {
                 output_clone.borrow_mut().push_str(s.as_str());
@@ -53,9 +76,10 @@ mod test {
         let input = "inc a a decc aa fwdfw a decc a a fwdfw aa inc a fwdfw a decc aaaaa bakbak aa ei bakbak a decc aa bakbak a decc aaa ei fwdfw a decc a outputa fwdfw aaa inc a outputa fwdfw aa outputa outputa inc aaa a outputa fwdfw a ei bakbak aaaa outputa inc a inc aa outputa decc aaaaaa outputa bakbak aa decc a outputa fwdfw aaaa inc a outputa";
         let instructions = crate::parser::Parser::parse(input);
         let instructions = instructions.unwrap();
-        let output = std::sync::Arc::new(std::cell::RefCell::new(String::new()));
+        let output = std::rc::Rc::new(std::cell::RefCell::new(String::new()));
         let output_clone = output.clone();
         let mut interpreter = crate::interpreter::Interpreter::new(instructions)
+            .unwrap()
             .with_fixed_size_memory::<100>()
             .with_output(Box::new(move |s: String| {
                 output_clone.borrow_mut().push_str(s.as_str());
@@ -0,0 +1,373 @@
+//! Warnings about poems that parse but probably don't do what the poet meant. Every [`Lint`]
+//! can be turned off on its own and every [`Warning`] points at the words it is about.
+
+use crate::{
+    annotation::Annotation,
+    parser::{opcode_name, Letters, ParserOptions},
+};
+use std::{collections::HashSet, fmt::Display, ops::Range, str::FromStr};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// Instructions after an `END` that can never run.
+    Unreachable,
+    /// An `INC` directly followed by a `DEC` of the same amount or the other way round.
+    CancellingPair,
+    /// An `IF` before anything changed the first cell, so its loop is always skipped.
+    DeadLoop,
+    /// A word of more than 10 letters, which is read as several digits.
+    SplitWord,
+    /// A non-ASCII letter, which is read as a word break unless Unicode letters are enabled.
+    NonAsciiLetter,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 5] = [
+        Lint::Unreachable,
+        Lint::CancellingPair,
+        Lint::DeadLoop,
+        Lint::SplitWord,
+        Lint::NonAsciiLetter,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Lint::Unreachable => "unreachable",
+            Lint::CancellingPair => "cancelling-pair",
+            Lint::DeadLoop => "dead-loop",
+            Lint::SplitWord => "split-word",
+            Lint::NonAsciiLetter => "non-ascii-letter",
+        }
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownLint(pub String);
+
+impl Display for UnknownLint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown lint: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownLint {}
+
+impl FromStr for Lint {
+    type Err = UnknownLint;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .into_iter()
+            .find(|lint| lint.name() == s)
+            .ok_or_else(|| UnknownLint(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub lint: Lint,
+    /// Byte range of the source the warning is about.
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+pub struct Linter {
+    enabled: HashSet<Lint>,
+    options: ParserOptions,
+}
+
+impl Linter {
+    /// A linter with every lint enabled.
+    pub fn new() -> Self {
+        Self {
+            enabled: Lint::ALL.into_iter().collect(),
+            options: ParserOptions::default(),
+        }
+    }
+
+    /// The dialect the poems are written in.
+    pub fn with_parser_options(self, options: ParserOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    pub fn with_lint(mut self, lint: Lint, enabled: bool) -> Self {
+        if enabled {
+            self.enabled.insert(lint);
+        } else {
+            self.enabled.remove(&lint);
+        }
+        self
+    }
+
+    pub fn is_enabled(&self, lint: Lint) -> bool {
+        self.enabled.contains(&lint)
+    }
+
+    /// Warnings of the enabled lints, ordered by their position in `source`.
+    pub fn lint(&self, source: &str) -> Vec<Warning> {
+        let annotation = Annotation::with_options(source, &self.options);
+        let mut warnings = Vec::new();
+        if self.is_enabled(Lint::Unreachable) {
+            unreachable(&annotation, &mut warnings);
+        }
+        if self.is_enabled(Lint::CancellingPair) {
+            cancelling_pairs(&annotation, &mut warnings);
+        }
+        if self.is_enabled(Lint::DeadLoop) {
+            dead_loop(&annotation, &mut warnings);
+        }
+        if self.is_enabled(Lint::SplitWord) {
+            split_words(&annotation, &mut warnings);
+        }
+        if self.is_enabled(Lint::NonAsciiLetter) && self.options.letters() == Letters::Ascii {
+            non_ascii_letters(source, &mut warnings);
+        }
+
+        warnings.sort_by_key(|warning| (warning.span.start, warning.span.end));
+        warnings
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The words of the operations from `first` to `last`, including the argument of `last`.
+fn operations_span(annotation: &Annotation, first: usize, last: usize) -> Range<usize> {
+    let operation = &annotation.operations[last];
+    let end = match annotation.argument(last) {
+        Some(_) => annotation.digits[operation.digit + 1].word,
+        None => annotation.digits[operation.digit].word,
+    };
+    annotation.operation_span(first).start..annotation.words[end].span.end
+}
+
+/// Everything after an `END` up to the end of its loop or the poem, since it could only be
+/// reached by falling through.
+fn unreachable(annotation: &Annotation, warnings: &mut Vec<Warning>) {
+    let operations = &annotation.operations;
+    let mut open = Vec::new();
+    let mut i = 0;
+    while i < operations.len() {
+        match operations[i].opcode {
+            0 => {
+                // the EIF closing the loop is unreachable as well, but not worth a warning
+                let end = open
+                    .last()
+                    .and_then(|start: &usize| operations[*start].matching)
+                    .unwrap_or(operations.len());
+                if end > i + 1 {
+                    warnings.push(Warning {
+                        lint: Lint::Unreachable,
+                        span: operations_span(annotation, i + 1, end - 1),
+                        message: "Instructions after END never run".to_string(),
+                    });
+                }
+                i = end;
+                continue;
+            }
+            1 => open.push(i),
+            2 => {
+                open.pop();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+fn cancelling_pairs(annotation: &Annotation, warnings: &mut Vec<Warning>) {
+    for i in 1..annotation.operations.len() {
+        let (first, second) = (&annotation.operations[i - 1], &annotation.operations[i]);
+        if !matches!((first.opcode, second.opcode), (3, 4) | (4, 3)) {
+            continue;
+        }
+
+        let argument = annotation.argument(i - 1);
+        if argument == annotation.argument(i) {
+            let argument = argument.unwrap_or_default();
+            warnings.push(Warning {
+                lint: Lint::CancellingPair,
+                span: operations_span(annotation, i - 1, i),
+                message: format!(
+                    "{} {} and {} {} cancel out",
+                    opcode_name(first.opcode),
+                    argument,
+                    opcode_name(second.opcode),
+                    argument
+                ),
+            });
+        }
+    }
+}
+
+/// Follows the first cell from the start, which is zero, until the pointer moves or the value
+/// becomes unknown, and warns about every `IF` reached while it is zero.
+fn dead_loop(annotation: &Annotation, warnings: &mut Vec<Warning>) {
+    let operations = &annotation.operations;
+    let mut cell = 0u8;
+    let mut i = 0;
+    while i < operations.len() {
+        match operations[i].opcode {
+            1 if cell == 0 => {
+                let Some(end) = operations[i].matching else {
+                    return;
+                };
+                warnings.push(Warning {
+                    lint: Lint::DeadLoop,
+                    span: operations_span(annotation, i, end),
+                    message: "The cell is always zero here, so the loop never runs".to_string(),
+                });
+                i = end;
+            }
+            3 => cell = cell.wrapping_add(annotation.argument(i).unwrap_or_default()),
+            4 => cell = cell.wrapping_sub(annotation.argument(i).unwrap_or_default()),
+            7 => {}
+            _ => return,
+        }
+        i += 1;
+    }
+}
+
+fn split_words(annotation: &Annotation, warnings: &mut Vec<Warning>) {
+    for word in annotation.words.iter().filter(|word| word.is_split()) {
+        let digits = annotation.digits[word.digits.clone()]
+            .iter()
+            .map(|digit| digit.value.to_string())
+            .collect::<Vec<_>>();
+        warnings.push(Warning {
+            lint: Lint::SplitWord,
+            span: word.span.clone(),
+            message: format!(
+                "{} letter word is read as the digits {}",
+                word.letters,
+                digits.join(", ")
+            ),
+        });
+    }
+}
+
+fn non_ascii_letters(source: &str, warnings: &mut Vec<Warning>) {
+    for (i, c) in source.char_indices() {
+        if c.is_alphabetic() && !c.is_ascii() {
+            warnings.push(Warning {
+                lint: Lint::NonAsciiLetter,
+                span: i..i + c.len_utf8(),
+                message: format!("'{}' is not an ASCII letter and breaks the word", c),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Lint, Linter};
+    #[cfg(feature = "unicode")]
+    use crate::parser::{Letters, ParserOptions};
+
+    fn lint(source: &str) -> Vec<(Lint, &str, String)> {
+        Linter::new()
+            .lint(source)
+            .into_iter()
+            .map(|warning| (warning.lint, &source[warning.span], warning.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_unreachable() {
+        assert_eq!(
+            lint("inc a outputa endprogram outputa inc aa"),
+            vec![(
+                Lint::Unreachable,
+                "outputa inc aa",
+                "Instructions after END never run".to_string()
+            )]
+        );
+
+        // inside a loop only the rest of the loop is unreachable
+        let source = "inc a a endprogram outputa ei outputa";
+        assert_eq!(
+            lint(source),
+            vec![(
+                Lint::Unreachable,
+                "outputa",
+                "Instructions after END never run".to_string()
+            )]
+        );
+        assert!(lint("inc a outputa endprogram").is_empty());
+        assert!(lint("inc a a endprogram ei").is_empty());
+    }
+
+    #[test]
+    fn test_cancelling_pair() {
+        assert_eq!(
+            lint("inc aaa decc aaa outputa inc aaa decc aa"),
+            vec![(
+                Lint::CancellingPair,
+                "inc aaa decc aaa",
+                "INC 3 and DEC 3 cancel out".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_dead_loop() {
+        assert_eq!(
+            lint("outputa a outputa ei inc a a ei"),
+            vec![(
+                Lint::DeadLoop,
+                "a outputa ei",
+                "The cell is always zero here, so the loop never runs".to_string()
+            )]
+        );
+        // INC 1, DEC 1 also cancel out
+        let warnings = lint("inc a decc a a ei");
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[1].0, Lint::DeadLoop);
+        assert!(lint("fwdfw a a ei").is_empty());
+    }
+
+    #[test]
+    fn test_split_word_and_non_ascii() {
+        assert_eq!(
+            lint("inc Überraschungen outputa"),
+            vec![
+                (
+                    Lint::NonAsciiLetter,
+                    "Ü",
+                    "'Ü' is not an ASCII letter and breaks the word".to_string()
+                ),
+                (
+                    Lint::SplitWord,
+                    "berraschungen",
+                    "13 letter word is read as the digits 1, 3".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_configuration() {
+        let linter = Linter::new().with_lint(Lint::SplitWord, false);
+        assert!(!linter.is_enabled(Lint::SplitWord));
+        assert!(linter.lint("inc aaaaaaaaaaaa").is_empty());
+        assert_eq!("dead-loop".parse(), Ok(Lint::DeadLoop));
+        assert!("nonsense".parse::<Lint>().is_err());
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_unicode_letters() {
+        let linter =
+            Linter::new().with_parser_options(ParserOptions::new().with_letters(Letters::Unicode));
+        assert!(linter.lint("inc für").is_empty());
+    }
+}
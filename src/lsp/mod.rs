@@ -0,0 +1,675 @@
+//! A language server for poems, speaking LSP over an [`lsp_server::Connection`]. It reports
+//! unmatched `IF`/`EIF` and missing arguments while typing, describes the instruction each word
+//! encodes on hover, highlights words by opcode and jumps between matching `IF` and `EIF`.
+//! Everything it shows comes from the [`Annotation`] of the document, the same analysis
+//! `poetic annotate` prints.
+//!
+//! The `poetic-lsp` binary runs [`run`] over stdio.
+
+use crate::{
+    annotation::{Annotation, Role},
+    parser::opcode_name,
+};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as LspNotification, PublishDiagnostics,
+    },
+    request::{GotoDefinition, HoverRequest, Request as LspRequest, SemanticTokensFullRequest},
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, SemanticToken, SemanticTokenType, SemanticTokens,
+    SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions, SemanticTokensParams,
+    SemanticTokensResult, SemanticTokensServerCapabilities, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use std::{collections::HashMap, error::Error};
+
+/// Token types in the order of their index in the legend.
+const TOKEN_TYPES: [SemanticTokenType; 6] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::OPERATOR,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::STRING,
+    SemanticTokenType::MACRO,
+    SemanticTokenType::NUMBER,
+];
+
+const fn token_type(opcode: u8) -> u32 {
+    match opcode {
+        3 | 4 => 1,
+        5 | 6 => 2,
+        7 | 8 => 3,
+        9 => 4,
+        // END, IF and EIF
+        _ => 0,
+    }
+}
+
+const ARGUMENT_TOKEN_TYPE: u32 = 5;
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: TOKEN_TYPES.to_vec(),
+                    token_modifiers: Vec::new(),
+                },
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..Default::default()
+            },
+        )),
+        ..Default::default()
+    }
+}
+
+struct Problem {
+    span: std::ops::Range<usize>,
+    message: String,
+}
+
+struct Document {
+    text: String,
+    annotation: Annotation,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let annotation = Annotation::new(&text);
+        Self { text, annotation }
+    }
+
+    /// LSP positions count UTF-16 code units.
+    fn position(&self, offset: usize) -> Position {
+        let before = &self.text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Position {
+            line: before.matches('\n').count() as u32,
+            character: before[line_start..].encode_utf16().count() as u32,
+        }
+    }
+
+    fn offset(&self, position: Position) -> Option<usize> {
+        let line_start = if position.line == 0 {
+            0
+        } else {
+            self.text
+                .match_indices('\n')
+                .nth(position.line as usize - 1)?
+                .0
+                + 1
+        };
+
+        let mut character = 0;
+        for (i, c) in self.text[line_start..].char_indices() {
+            if character >= position.character as usize || c == '\n' {
+                return Some(line_start + i);
+            }
+            character += c.len_utf16();
+        }
+        Some(self.text.len())
+    }
+
+    fn range(&self, span: std::ops::Range<usize>) -> Range {
+        Range::new(self.position(span.start), self.position(span.end))
+    }
+
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.problems()
+            .into_iter()
+            .map(|problem| Diagnostic {
+                range: self.range(problem.span),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("poetic".to_string()),
+                message: problem.message,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// Every unmatched `IF` and `EIF` and a trailing instruction without its argument.
+    fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        for (i, operation) in self.annotation.operations.iter().enumerate() {
+            let message = match (operation.opcode, operation.matching) {
+                (1, None) => "IF without matching EIF",
+                (2, None) => "EIF without matching IF",
+                _ => continue,
+            };
+            problems.push(Problem {
+                span: self.annotation.operation_span(i),
+                message: message.to_string(),
+            });
+        }
+
+        if let Some(digit) = self
+            .annotation
+            .digits
+            .iter()
+            .find(|digit| digit.role == Role::MissingArgument)
+        {
+            problems.push(Problem {
+                span: self.annotation.words[digit.word].span.clone(),
+                message: format!("{} needs an argument", opcode_name(digit.value)),
+            });
+        }
+
+        problems
+    }
+
+    /// Describes the digits a word encodes and what they mean, as markdown.
+    fn describe(&self, word: usize) -> String {
+        let word = &self.annotation.words[word];
+        let mut result = format!(
+            "{} letter{}",
+            word.letters,
+            if word.letters == 1 { "" } else { "s" }
+        );
+        for digit in &self.annotation.digits[word.digits.clone()] {
+            let role = match digit.role {
+                Role::Opcode(_) => format!("**{}**", opcode_name(digit.value)),
+                Role::Argument(operation) => {
+                    let opcode = self.annotation.operations[operation].opcode;
+                    let argument = if digit.value == 0 { 10 } else { digit.value };
+                    format!("argument {} of **{}**", argument, opcode_name(opcode))
+                }
+                Role::MissingArgument => {
+                    format!("**{}**, missing its argument", opcode_name(digit.value))
+                }
+            };
+            result.push_str(&format!("\n\n`{}` {}", digit.value, role));
+        }
+
+        result
+    }
+
+    fn hover(&self, position: Position) -> Option<Hover> {
+        let word = self.annotation.word_at(self.offset(position)?)?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: self.describe(word),
+            }),
+            range: Some(self.range(self.annotation.words[word].span.clone())),
+        })
+    }
+
+    /// The word of the `EIF` matching an `IF` under the cursor or the other way round.
+    fn matching(&self, position: Position) -> Option<Range> {
+        let word = self.annotation.word_at(self.offset(position)?)?;
+        let matching = self.annotation.digits[self.annotation.words[word].digits.clone()]
+            .iter()
+            .find_map(|digit| match digit.role {
+                Role::Opcode(operation) => self.annotation.operations[operation].matching,
+                _ => None,
+            })?;
+        Some(self.range(self.annotation.operation_span(matching)))
+    }
+
+    /// One token per word, typed by its first digit.
+    fn semantic_tokens(&self) -> Vec<SemanticToken> {
+        let mut tokens = Vec::new();
+        let mut previous = Position::new(0, 0);
+        for word in &self.annotation.words {
+            let digit = &self.annotation.digits[word.digits.start];
+            let token_type = match digit.role {
+                Role::Argument(_) => ARGUMENT_TOKEN_TYPE,
+                Role::Opcode(_) | Role::MissingArgument => token_type(digit.value),
+            };
+
+            let start = self.position(word.span.start);
+            let delta_start = if start.line == previous.line {
+                start.character - previous.character
+            } else {
+                start.character
+            };
+            tokens.push(SemanticToken {
+                delta_line: start.line - previous.line,
+                delta_start,
+                length: self.text[word.span.clone()].encode_utf16().count() as u32,
+                token_type,
+                token_modifiers_bitset: 0,
+            });
+            previous = start;
+        }
+
+        tokens
+    }
+}
+
+/// Performs the initialize handshake and serves requests until the client shuts down. Only
+/// errors of the connection end it, invalid notifications are logged to stderr and skipped.
+pub fn run(connection: &Connection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+
+    let mut documents = HashMap::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = handle_request(&documents, request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                let method = notification.method.clone();
+                let uri = match handle_notification(&mut documents, notification) {
+                    Ok(uri) => uri,
+                    // a malformed notification can't be answered, so it is only logged
+                    Err(error) => {
+                        eprintln!("poetic-lsp: ignoring {}: {}", method, error);
+                        None
+                    }
+                };
+                if let Some(uri) = uri {
+                    let diagnostics = documents
+                        .get(&uri)
+                        .map(Document::diagnostics)
+                        .unwrap_or_default();
+                    let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+                    connection
+                        .sender
+                        .send(Message::Notification(Notification::new(
+                            PublishDiagnostics::METHOD.to_string(),
+                            params,
+                        )))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Updates the documents and returns the one whose diagnostics changed.
+fn handle_notification(
+    documents: &mut HashMap<Url, Document>,
+    notification: Notification,
+) -> Result<Option<Url>, serde_json::Error> {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: DidOpenTextDocumentParams = serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            documents.insert(uri.clone(), Document::new(params.text_document.text));
+            Ok(Some(uri))
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: DidChangeTextDocumentParams = serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            // with full sync the last change holds the whole text
+            match params.content_changes.into_iter().last() {
+                Some(change) => {
+                    documents.insert(uri.clone(), Document::new(change.text));
+                    Ok(Some(uri))
+                }
+                None => Ok(None),
+            }
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: DidCloseTextDocumentParams = serde_json::from_value(notification.params)?;
+            documents.remove(&params.text_document.uri);
+            Ok(Some(params.text_document.uri))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn handle_request(documents: &HashMap<Url, Document>, request: Request) -> Response {
+    let id = request.id.clone();
+    let result = match request.method.as_str() {
+        HoverRequest::METHOD => respond::<HoverRequest>(documents, request, |document, params| {
+            document.hover(params.text_document_position_params.position)
+        }),
+        GotoDefinition::METHOD => {
+            respond::<GotoDefinition>(documents, request, |document, params| {
+                let position = params.text_document_position_params;
+                document.matching(position.position).map(|range| {
+                    GotoDefinitionResponse::Scalar(Location::new(position.text_document.uri, range))
+                })
+            })
+        }
+        SemanticTokensFullRequest::METHOD => {
+            respond::<SemanticTokensFullRequest>(documents, request, |document, _| {
+                Some(SemanticTokensResult::Tokens(SemanticTokens {
+                    result_id: None,
+                    data: document.semantic_tokens(),
+                }))
+            })
+        }
+        _ => Err(Response::new_err(
+            id,
+            ErrorCode::MethodNotFound as i32,
+            format!("Unsupported request {}", request.method),
+        )),
+    };
+
+    result.unwrap_or_else(|error| error)
+}
+
+/// Deserializes the params of `R`, looks up its document and answers with `handler`'s result.
+fn respond<R>(
+    documents: &HashMap<Url, Document>,
+    request: Request,
+    handler: impl FnOnce(&Document, R::Params) -> R::Result,
+) -> Result<Response, Response>
+where
+    R: LspRequest,
+    R::Params: DocumentParams,
+{
+    let id: RequestId = request.id;
+    let params: R::Params = serde_json::from_value(request.params).map_err(|error| {
+        Response::new_err(
+            id.clone(),
+            ErrorCode::InvalidParams as i32,
+            error.to_string(),
+        )
+    })?;
+    let document = documents.get(params.uri()).ok_or_else(|| {
+        Response::new_err(
+            id.clone(),
+            ErrorCode::InvalidParams as i32,
+            format!("Unknown document {}", params.uri()),
+        )
+    })?;
+
+    Ok(Response::new_ok(id, handler(document, params)))
+}
+
+trait DocumentParams {
+    fn uri(&self) -> &Url;
+}
+
+impl DocumentParams for HoverParams {
+    fn uri(&self) -> &Url {
+        &self.text_document_position_params.text_document.uri
+    }
+}
+
+impl DocumentParams for GotoDefinitionParams {
+    fn uri(&self) -> &Url {
+        &self.text_document_position_params.text_document.uri
+    }
+}
+
+impl DocumentParams for SemanticTokensParams {
+    fn uri(&self) -> &Url {
+        &self.text_document.uri
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::run;
+    use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+    use lsp_types::{
+        notification::{
+            DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized,
+            Notification as LspNotification, PublishDiagnostics,
+        },
+        request::{
+            GotoDefinition, HoverRequest, Initialize, Request as LspRequest,
+            SemanticTokensFullRequest, Shutdown,
+        },
+        DidChangeTextDocumentParams, DidOpenTextDocumentParams, GotoDefinitionResponse, Hover,
+        HoverContents, InitializeParams, Position, PublishDiagnosticsParams, Range,
+        SemanticTokensResult, TextDocumentContentChangeEvent, TextDocumentIdentifier,
+        TextDocumentItem, TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier,
+    };
+    use serde_json::{json, Value};
+    use std::thread::JoinHandle;
+
+    /// An in-process client talking to a server on another thread.
+    struct Client {
+        connection: Connection,
+        server: Option<JoinHandle<()>>,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            let (server, connection) = Connection::memory();
+            let server = std::thread::spawn(move || run(&server).unwrap());
+            let mut client = Self {
+                connection,
+                server: Some(server),
+                next_id: 0,
+            };
+            client.request::<Initialize>(json!(InitializeParams::default()));
+            client.notify::<Initialized>(json!({}));
+            client
+        }
+
+        fn request<R: LspRequest>(&mut self, params: Value) -> Response {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            self.connection
+                .sender
+                .send(Request::new(id.clone(), R::METHOD.to_string(), params).into())
+                .unwrap();
+            loop {
+                match self.connection.receiver.recv().unwrap() {
+                    Message::Response(response) if response.id == id => return response,
+                    _ => {}
+                }
+            }
+        }
+
+        fn result<R: LspRequest>(&mut self, params: Value) -> Value {
+            self.request::<R>(params).result.unwrap()
+        }
+
+        fn notify<N: LspNotification>(&self, params: Value) {
+            self.connection
+                .sender
+                .send(Notification::new(N::METHOD.to_string(), params).into())
+                .unwrap();
+        }
+
+        fn diagnostics(&self) -> PublishDiagnosticsParams {
+            loop {
+                if let Message::Notification(notification) =
+                    self.connection.receiver.recv().unwrap()
+                {
+                    if notification.method == PublishDiagnostics::METHOD {
+                        return serde_json::from_value(notification.params).unwrap();
+                    }
+                }
+            }
+        }
+
+        fn open(&self, uri: &Url, text: &str) -> PublishDiagnosticsParams {
+            self.notify::<DidOpenTextDocument>(json!(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "poetic".to_string(),
+                    1,
+                    text.to_string(),
+                ),
+            }));
+            self.diagnostics()
+        }
+    }
+
+    impl Drop for Client {
+        fn drop(&mut self) {
+            if std::thread::panicking() {
+                return;
+            }
+            self.request::<Shutdown>(Value::Null);
+            self.notify::<Exit>(Value::Null);
+            self.server.take().unwrap().join().unwrap();
+        }
+    }
+
+    fn position(uri: &Url, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri.clone()),
+            Position::new(line, character),
+        )
+    }
+
+    fn uri() -> Url {
+        Url::parse("file:///poem.ptc").unwrap()
+    }
+
+    #[test]
+    fn test_diagnostics_as_you_type() {
+        let mut client = Client::start();
+        let uri = uri();
+        let diagnostics = client.open(&uri, "inc a\na fwdfw");
+        let messages = diagnostics
+            .diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.range, diagnostic.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                (
+                    Range::new(Position::new(1, 0), Position::new(1, 1)),
+                    "IF without matching EIF"
+                ),
+                (
+                    Range::new(Position::new(1, 2), Position::new(1, 7)),
+                    "FWD needs an argument"
+                ),
+            ]
+        );
+
+        client.notify::<DidChangeTextDocument>(json!(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "inc a\na fwdfw a ei".to_string(),
+            }],
+        }));
+        assert!(client.diagnostics().diagnostics.is_empty());
+
+        // unknown documents are an error, not a crash
+        let response = client.request::<HoverRequest>(json!({
+            "textDocument": { "uri": "file:///missing.ptc" },
+            "position": { "line": 0, "character": 0 },
+        }));
+        assert!(response.error.is_some());
+    }
+
+    #[test]
+    fn test_invalid_notification_is_skipped() {
+        let mut client = Client::start();
+        let uri = uri();
+        client.notify::<DidOpenTextDocument>(json!({ "textDocument": 42 }));
+        let diagnostics = client.open(&uri, "inc a");
+        assert_eq!(diagnostics.uri, uri);
+        let hover = client.request::<HoverRequest>(json!(position(&uri, 0, 0)));
+        assert!(hover.error.is_none());
+    }
+
+    #[test]
+    fn test_hover() {
+        let mut client = Client::start();
+        let uri = uri();
+        client.open(&uri, "«inc» aaaaaaaaaaaa");
+        let hover: Hover =
+            serde_json::from_value(client.result::<HoverRequest>(json!(position(&uri, 0, 1))))
+                .unwrap();
+        let HoverContents::Markup(contents) = hover.contents else {
+            panic!("expected markup");
+        };
+        assert_eq!(contents.value, "3 letters\n\n`3` **INC**");
+        assert_eq!(
+            hover.range,
+            Some(Range::new(Position::new(0, 1), Position::new(0, 4)))
+        );
+
+        let hover: Hover =
+            serde_json::from_value(client.result::<HoverRequest>(json!(position(&uri, 0, 7))))
+                .unwrap();
+        let HoverContents::Markup(contents) = hover.contents else {
+            panic!("expected markup");
+        };
+        assert_eq!(
+            contents.value,
+            "12 letters\n\n`1` argument 1 of **INC**\n\n`2` **EIF**"
+        );
+
+        // whitespace has nothing to show
+        assert_eq!(
+            client.result::<HoverRequest>(json!(position(&uri, 0, 0))),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn test_matching_if_eif() {
+        let mut client = Client::start();
+        let uri = uri();
+        client.open(&uri, "inc a a\n  decc a\nei");
+        let response: GotoDefinitionResponse =
+            serde_json::from_value(client.result::<GotoDefinition>(json!(position(&uri, 2, 1))))
+                .unwrap();
+        let GotoDefinitionResponse::Scalar(location) = response else {
+            panic!("expected a single location");
+        };
+        assert_eq!(location.uri, uri);
+        assert_eq!(
+            location.range,
+            Range::new(Position::new(0, 6), Position::new(0, 7))
+        );
+
+        let response: GotoDefinitionResponse =
+            serde_json::from_value(client.result::<GotoDefinition>(json!(position(&uri, 0, 6))))
+                .unwrap();
+        let GotoDefinitionResponse::Scalar(location) = response else {
+            panic!("expected a single location");
+        };
+        assert_eq!(
+            location.range,
+            Range::new(Position::new(2, 0), Position::new(2, 2))
+        );
+
+        assert_eq!(
+            client.result::<GotoDefinition>(json!(position(&uri, 1, 3))),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let mut client = Client::start();
+        let uri = uri();
+        client.open(&uri, "inc a\n outputa endprogram");
+        let result: SemanticTokensResult =
+            serde_json::from_value(client.result::<SemanticTokensFullRequest>(json!({
+                "textDocument": { "uri": uri },
+            })))
+            .unwrap();
+        let SemanticTokensResult::Tokens(tokens) = result else {
+            panic!("expected tokens");
+        };
+        let data = tokens
+            .data
+            .iter()
+            .map(|token| {
+                (
+                    token.delta_line,
+                    token.delta_start,
+                    token.length,
+                    token.token_type,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            data,
+            vec![(0, 0, 3, 1), (0, 4, 1, 5), (1, 1, 7, 3), (0, 8, 10, 0)]
+        );
+    }
+}
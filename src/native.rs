@@ -0,0 +1,138 @@
+//! Glue between natively compiled programs and an [`Interpreter`]. Native code works directly on
+//! the tape of the interpreter's [`Memory`](crate::interpreter::Memory) and calls back into Rust through the function
+//! pointers of a [`Context`] for input, output, `RND` and whenever the memory pointer leaves the
+//! tape, so growing and wrapping behave exactly like in the interpreter.
+
+use crate::interpreter::{raw::RawMemory, Interpreter};
+use rand::RngCore;
+use std::{ffi::c_void, mem::offset_of};
+
+/// Shared between native code and the callbacks. The code keeps the tape in registers and only
+/// syncs it around calls that can move the pointer.
+#[repr(C)]
+pub(crate) struct Context {
+    base: *mut u8,
+    pointer: usize,
+    length: usize,
+    interpreter: *mut c_void,
+    output: unsafe extern "C" fn(*mut Context, u32),
+    /// Returns the input byte or a negative value if the cell should be left untouched.
+    input: unsafe extern "C" fn(*mut Context) -> i32,
+    random: unsafe extern "C" fn(*mut Context) -> u32,
+    /// Moves the pointer by the given delta, updating the whole tape.
+    seek: unsafe extern "C" fn(*mut Context, isize),
+}
+
+pub(crate) const BASE: usize = offset_of!(Context, base);
+pub(crate) const POINTER: usize = offset_of!(Context, pointer);
+pub(crate) const LENGTH: usize = offset_of!(Context, length);
+pub(crate) const OUTPUT: usize = offset_of!(Context, output);
+pub(crate) const INPUT: usize = offset_of!(Context, input);
+pub(crate) const RANDOM: usize = offset_of!(Context, random);
+pub(crate) const SEEK: usize = offset_of!(Context, seek);
+
+impl Context {
+    /// Sets up a context for running native code with the memory, input, output and random
+    /// number generator of the interpreter.
+    ///
+    /// Panics in the callbacks abort the process, as they can not unwind through native code.
+    pub(crate) fn new<I, O, M, R>(interpreter: &mut Interpreter<I, O, M, R>) -> Self
+    where
+        I: FnMut() -> Option<u8>,
+        O: FnMut(String),
+        M: RawMemory,
+        R: RngCore,
+    {
+        let pointer = interpreter.memory.get_memory_pointer();
+        let tape = interpreter.memory.raw_memory_mut();
+        Self {
+            base: tape.as_mut_ptr(),
+            length: tape.len(),
+            pointer,
+            interpreter: (interpreter as *mut Interpreter<I, O, M, R>).cast(),
+            output: output::<I, O, M, R>,
+            input: input::<I, O, M, R>,
+            random: random::<I, O, M, R>,
+            seek: seek::<I, O, M, R>,
+        }
+    }
+
+    /// Writes the final memory pointer back and marks the interpreter as ended at `stop`.
+    pub(crate) fn finish<I, O, M, R>(self, interpreter: &mut Interpreter<I, O, M, R>, stop: usize)
+    where
+        I: FnMut() -> Option<u8>,
+        O: FnMut(String),
+        M: RawMemory,
+        R: RngCore,
+    {
+        interpreter.memory.set_memory_pointer(self.pointer);
+        interpreter.end_at(stop);
+    }
+}
+
+/// # Safety
+/// `context` has to be set up by [`Context::new`] for the same types.
+unsafe fn from_context<'a, I, O, M, R>(context: *mut Context) -> &'a mut Interpreter<I, O, M, R>
+where
+    I: FnMut() -> Option<u8>,
+    O: FnMut(String),
+    M: RawMemory,
+    R: RngCore,
+{
+    &mut *(*context).interpreter.cast::<Interpreter<I, O, M, R>>()
+}
+
+unsafe extern "C" fn output<I, O, M, R>(context: *mut Context, value: u32)
+where
+    I: FnMut() -> Option<u8>,
+    O: FnMut(String),
+    M: RawMemory,
+    R: RngCore,
+{
+    let interpreter = from_context::<I, O, M, R>(context);
+    (interpreter.output)(format!("{}", value as u8 as char));
+}
+
+unsafe extern "C" fn input<I, O, M, R>(context: *mut Context) -> i32
+where
+    I: FnMut() -> Option<u8>,
+    O: FnMut(String),
+    M: RawMemory,
+    R: RngCore,
+{
+    let interpreter = from_context::<I, O, M, R>(context);
+    (interpreter.input)().map_or(-1, i32::from)
+}
+
+unsafe extern "C" fn random<I, O, M, R>(context: *mut Context) -> u32
+where
+    I: FnMut() -> Option<u8>,
+    O: FnMut(String),
+    M: RawMemory,
+    R: RngCore,
+{
+    let interpreter = from_context::<I, O, M, R>(context);
+    interpreter.random_byte() as u32
+}
+
+/// Moves the pointer through the [`Memory`](crate::interpreter::Memory) implementation, so it can grow or wrap the tape.
+unsafe extern "C" fn seek<I, O, M, R>(context: *mut Context, delta: isize)
+where
+    I: FnMut() -> Option<u8>,
+    O: FnMut(String),
+    M: RawMemory,
+    R: RngCore,
+{
+    let memory = &mut from_context::<I, O, M, R>(context).memory;
+    memory.set_memory_pointer((*context).pointer);
+    if delta >= 0 {
+        memory.inc_memory_pointer(delta as usize);
+    } else {
+        memory.dec_memory_pointer(delta.unsigned_abs());
+    }
+
+    (*context).pointer = memory.get_memory_pointer();
+    let tape = memory.raw_memory_mut();
+    (*context).base = tape.as_mut_ptr();
+    (*context).length = tape.len();
+}
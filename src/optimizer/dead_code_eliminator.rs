@@ -0,0 +1,405 @@
+use super::Optimize;
+use crate::instruction::Instruction;
+
+/// Removes instructions that can never run or have no effect: code following an `END` or `JMP`,
+/// `IF`/`EIF` loops that are never entered because the current cell is known to be zero, and
+/// runs of `INC`/`DEC` that cancel each other out.
+pub(crate) struct DeadCodeEliminator;
+
+/// What is known about the memory at a given instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TapeState {
+    /// Nothing has been written yet, every cell is zero.
+    AllZero,
+    /// Only the value of the current cell is known.
+    Current(u8),
+    Unknown,
+}
+
+impl TapeState {
+    const fn is_zero(self) -> bool {
+        matches!(self, TapeState::AllZero | TapeState::Current(0))
+    }
+
+    const fn add(self, value: u8) -> Self {
+        match self {
+            TapeState::AllZero => TapeState::Current(value),
+            TapeState::Current(v) => TapeState::Current(v.wrapping_add(value)),
+            TapeState::Unknown => TapeState::Unknown,
+        }
+    }
+
+    const fn sub(self, value: u8) -> Self {
+        match self {
+            TapeState::AllZero => TapeState::Current(0u8.wrapping_sub(value)),
+            TapeState::Current(v) => TapeState::Current(v.wrapping_sub(value)),
+            TapeState::Unknown => TapeState::Unknown,
+        }
+    }
+}
+
+fn find_matching_brackets(instructions: &[Instruction]) -> Option<Vec<usize>> {
+    let mut matching = vec![0; instructions.len()];
+    let mut open = Vec::new();
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::IF => open.push(i),
+            Instruction::EIF => {
+                let if_index = open.pop()?;
+                matching[if_index] = i;
+                matching[i] = if_index;
+            }
+            _ => {}
+        }
+    }
+
+    open.is_empty().then_some(matching)
+}
+
+fn find_jump_targets(instructions: &[Instruction]) -> Vec<bool> {
+    let mut targets = vec![false; instructions.len()];
+    for instruction in instructions {
+        if let Instruction::JIZ(n) | Instruction::JNZ(n) | Instruction::JMP(n) = instruction {
+            if let Some(target) = targets.get_mut(*n) {
+                *target = true;
+            }
+        }
+    }
+
+    targets
+}
+
+/// Returns the end of the unreachable code following an unconditional jump at `start`. Stops at
+/// jump targets and at the `EIF` of an enclosing loop, as both can still be reached.
+fn find_unreachable_end(instructions: &[Instruction], targets: &[bool], start: usize) -> usize {
+    let mut depth = 0;
+    let mut end = start + 1;
+    let mut j = start + 1;
+    while j < instructions.len() && !targets[j] {
+        match instructions[j] {
+            Instruction::IF => depth += 1,
+            Instruction::EIF if depth == 0 => break,
+            Instruction::EIF => depth -= 1,
+            _ => {}
+        }
+
+        j += 1;
+        if depth == 0 {
+            end = j;
+        }
+    }
+
+    end
+}
+
+/// Returns the end of the run of `INC`/`DEC` starting at `start` if the run sums up to zero.
+fn find_cancelling_run_end(
+    instructions: &[Instruction],
+    targets: &[bool],
+    start: usize,
+) -> Option<usize> {
+    let mut total: u8 = 0;
+    let mut j = start;
+    while j < instructions.len() && (j == start || !targets[j]) {
+        match instructions[j] {
+            Instruction::INC(n) => total = total.wrapping_add(n),
+            Instruction::DEC(n) => total = total.wrapping_sub(n),
+            _ => break,
+        }
+
+        j += 1;
+    }
+
+    (j - start > 1 && total == 0).then_some(j)
+}
+
+fn mark_dead_instructions(
+    instructions: &[Instruction],
+    matching: &[usize],
+    targets: &[bool],
+) -> Vec<bool> {
+    let mut dead = vec![false; instructions.len()];
+    let mut state = TapeState::AllZero;
+
+    let mut i = 0;
+    while i < instructions.len() {
+        if targets[i] {
+            state = TapeState::Unknown;
+        }
+
+        match instructions[i] {
+            Instruction::IF => {
+                let eif = matching[i];
+                if state.is_zero() && !targets[i + 1..=eif].contains(&true) {
+                    dead[i..=eif].fill(true);
+                    i = eif + 1;
+                    continue;
+                }
+
+                state = TapeState::Unknown;
+            }
+            Instruction::EIF => state = TapeState::Current(0),
+            Instruction::INC(_) | Instruction::DEC(_) => {
+                if let Some(end) = find_cancelling_run_end(instructions, targets, i) {
+                    dead[i..end].fill(true);
+                    i = end;
+                    continue;
+                }
+
+                state = match instructions[i] {
+                    Instruction::INC(n) => state.add(n),
+                    Instruction::DEC(n) => state.sub(n),
+                    _ => unreachable!(),
+                };
+            }
+            Instruction::FWD(_) | Instruction::BAK(_) => {
+                if state != TapeState::AllZero {
+                    state = TapeState::Unknown;
+                }
+            }
+            Instruction::SCF(_) | Instruction::SCB(_) => {
+                if state != TapeState::AllZero {
+                    state = TapeState::Current(0);
+                }
+            }
+            Instruction::SET(0) if state == TapeState::AllZero => {}
+            Instruction::SET(n) => state = TapeState::Current(n),
+            Instruction::IN | Instruction::RND => state = TapeState::Unknown,
+            Instruction::OUT | Instruction::PUT(_) | Instruction::JIZ(_) | Instruction::JNZ(_) => {}
+            Instruction::END | Instruction::JMP(_) => {
+                let end = find_unreachable_end(instructions, targets, i);
+                dead[i + 1..end].fill(true);
+                i = end;
+                state = TapeState::Unknown;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    dead
+}
+
+impl Optimize for DeadCodeEliminator {
+    fn optimize(&self, instructions: &[Instruction]) -> Vec<Instruction> {
+        let Some(matching) = find_matching_brackets(instructions) else {
+            return instructions.to_vec();
+        };
+        let targets = find_jump_targets(instructions);
+        let dead = mark_dead_instructions(instructions, &matching, &targets);
+
+        // maps every old index to the index of the next instruction that is kept
+        let mut new_index = Vec::with_capacity(instructions.len() + 1);
+        let mut kept = 0;
+        for is_dead in &dead {
+            new_index.push(kept);
+            if !is_dead {
+                kept += 1;
+            }
+        }
+        new_index.push(kept);
+
+        let remap = |n: usize| match new_index.get(n) {
+            Some(index) => *index,
+            None => n - instructions.len() + kept,
+        };
+
+        instructions
+            .iter()
+            .zip(dead)
+            .filter(|(_, is_dead)| !is_dead)
+            .map(|(instruction, _)| match *instruction {
+                Instruction::JIZ(n) => Instruction::JIZ(remap(n)),
+                Instruction::JNZ(n) => Instruction::JNZ(remap(n)),
+                Instruction::JMP(n) => Instruction::JMP(remap(n)),
+                instruction => instruction,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{instruction::Instruction, optimizer::Optimize};
+
+    #[test]
+    fn test_code_after_end_removed() {
+        let instructions = vec![
+            Instruction::INC(1),
+            Instruction::END,
+            Instruction::OUT,
+            Instruction::IF,
+            Instruction::DEC(1),
+            Instruction::EIF,
+        ];
+
+        let optimizer = super::DeadCodeEliminator;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![Instruction::INC(1), Instruction::END]
+        );
+    }
+
+    #[test]
+    fn test_code_after_end_in_loop_keeps_eif() {
+        let instructions = vec![
+            Instruction::IN,
+            Instruction::IF,
+            Instruction::END,
+            Instruction::OUT,
+            Instruction::EIF,
+            Instruction::OUT,
+        ];
+
+        let optimizer = super::DeadCodeEliminator;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![
+                Instruction::IN,
+                Instruction::IF,
+                Instruction::END,
+                Instruction::EIF,
+                Instruction::OUT,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loop_at_start_removed() {
+        let instructions = vec![
+            Instruction::IF,
+            Instruction::INC(1),
+            Instruction::EIF,
+            Instruction::INC(2),
+        ];
+
+        let optimizer = super::DeadCodeEliminator;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(optimized_instructions, vec![Instruction::INC(2)]);
+    }
+
+    #[test]
+    fn test_loop_after_loop_removed() {
+        let instructions = vec![
+            Instruction::IN,
+            Instruction::IF,
+            Instruction::DEC(1),
+            Instruction::EIF,
+            Instruction::IF,
+            Instruction::OUT,
+            Instruction::EIF,
+        ];
+
+        let optimizer = super::DeadCodeEliminator;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![
+                Instruction::IN,
+                Instruction::IF,
+                Instruction::DEC(1),
+                Instruction::EIF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loop_after_unknown_value_kept() {
+        let instructions = vec![
+            Instruction::INC(1),
+            Instruction::FWD(1),
+            Instruction::IF,
+            Instruction::OUT,
+            Instruction::EIF,
+        ];
+
+        let optimizer = super::DeadCodeEliminator;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(optimized_instructions, instructions);
+    }
+
+    #[test]
+    fn test_loop_after_known_zero_value_removed() {
+        let instructions = vec![
+            Instruction::IN,
+            Instruction::INC(3),
+            Instruction::SCF(1),
+            Instruction::IF,
+            Instruction::OUT,
+            Instruction::EIF,
+        ];
+
+        let optimizer = super::DeadCodeEliminator;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![Instruction::IN, Instruction::INC(3), Instruction::SCF(1)]
+        );
+    }
+
+    #[test]
+    fn test_cancelling_inc_dec_removed() {
+        let instructions = vec![
+            Instruction::IN,
+            Instruction::INC(200),
+            Instruction::INC(56),
+            Instruction::OUT,
+            Instruction::DEC(3),
+            Instruction::INC(3),
+            Instruction::OUT,
+        ];
+
+        let optimizer = super::DeadCodeEliminator;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![Instruction::IN, Instruction::OUT, Instruction::OUT]
+        );
+    }
+
+    #[test]
+    fn test_jump_targets_remapped() {
+        let instructions = vec![
+            Instruction::IN,
+            Instruction::JIZ(6),
+            Instruction::INC(1),
+            Instruction::DEC(1),
+            Instruction::OUT,
+            Instruction::JNZ(2),
+            Instruction::OUT,
+        ];
+
+        let optimizer = super::DeadCodeEliminator;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![
+                Instruction::IN,
+                Instruction::JIZ(4),
+                Instruction::OUT,
+                Instruction::JNZ(2),
+                Instruction::OUT,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jump_target_after_jmp_kept() {
+        let instructions = vec![
+            Instruction::JMP(3),
+            Instruction::OUT,
+            Instruction::OUT,
+            Instruction::INC(1),
+        ];
+
+        let optimizer = super::DeadCodeEliminator;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![Instruction::JMP(1), Instruction::INC(1)]
+        );
+    }
+}
@@ -0,0 +1,461 @@
+use crate::{
+    instruction::Instruction,
+    interpreter::{Interpreter, Memory},
+    parser::ParseError,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::VecDeque, fmt::Display};
+
+const DEFAULT_MAX_STEPS: usize = 100_000;
+
+/// Observable result of running a program for a limited number of steps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Execution {
+    pub output: Vec<u8>,
+    /// Memory with trailing zero cells removed, as memory that grows on demand may differ in size.
+    pub memory: Vec<u8>,
+    pub memory_pointer: usize,
+    pub terminated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    InvalidProgram(ParseError),
+    Output { expected: Vec<u8>, actual: Vec<u8> },
+    Memory { expected: Vec<u8>, actual: Vec<u8> },
+    MemoryPointer { expected: usize, actual: usize },
+    Termination { expected: bool, actual: bool },
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::InvalidProgram(error) => write!(f, "Invalid program: {}", error),
+            Mismatch::Output { expected, actual } => {
+                write!(
+                    f,
+                    "Output differs: expected {:?}, got {:?}",
+                    expected, actual
+                )
+            }
+            Mismatch::Memory { expected, actual } => {
+                write!(
+                    f,
+                    "Memory differs: expected {:?}, got {:?}",
+                    expected, actual
+                )
+            }
+            Mismatch::MemoryPointer { expected, actual } => {
+                write!(
+                    f,
+                    "Memory pointer differs: expected {}, got {}",
+                    expected, actual
+                )
+            }
+            Mismatch::Termination { expected, actual } => {
+                write!(
+                    f,
+                    "Termination differs: expected {}, got {}",
+                    expected, actual
+                )
+            }
+        }
+    }
+}
+
+/// Runs an original and an optimized program side by side on the same input and random numbers
+/// and compares their output, final memory and termination.
+///
+/// Programs that do not terminate within the step limit are only required to produce output
+/// that is a prefix of one another, since optimized programs usually need fewer steps.
+pub struct EquivalenceChecker {
+    input: Vec<u8>,
+    seed: u64,
+    max_steps: usize,
+}
+
+impl EquivalenceChecker {
+    pub fn new() -> Self {
+        Self {
+            input: Vec::new(),
+            seed: 0,
+            max_steps: DEFAULT_MAX_STEPS,
+        }
+    }
+
+    pub fn with_input(self, input: &[u8]) -> Self {
+        Self {
+            input: input.to_vec(),
+            ..self
+        }
+    }
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    pub fn with_max_steps(self, max_steps: usize) -> Self {
+        Self { max_steps, ..self }
+    }
+
+    pub fn execute<M: Memory + Default>(
+        &self,
+        instructions: &[Instruction],
+        max_steps: usize,
+    ) -> Result<Execution, ParseError> {
+        let mut input: VecDeque<u8> = self.input.iter().copied().collect();
+        let mut output = Vec::new();
+        let mut interpreter = Interpreter::new(instructions.to_vec())?
+            .with_memory(M::default())
+            .with_rng(StdRng::seed_from_u64(self.seed))
+            .with_io(
+                || input.pop_front(),
+                |s: String| output.extend(s.chars().map(|c| c as u8)),
+            );
+
+        let mut steps = 0;
+        while !interpreter.has_ended() && steps < max_steps {
+            interpreter.step();
+            steps += 1;
+        }
+
+        let terminated = interpreter.has_ended();
+        let memory_pointer = interpreter.memory.get_memory_pointer();
+        let mut memory = interpreter.memory.raw_memory().to_vec();
+        drop(interpreter);
+
+        while memory.last() == Some(&0) {
+            memory.pop();
+        }
+
+        Ok(Execution {
+            output,
+            memory,
+            memory_pointer,
+            terminated,
+        })
+    }
+
+    pub fn check<M: Memory + Default>(
+        &self,
+        original: &[Instruction],
+        optimized: &[Instruction],
+    ) -> Result<(), Mismatch> {
+        let expected = self
+            .execute::<M>(original, self.max_steps)
+            .map_err(Mismatch::InvalidProgram)?;
+        // optimizations may add a few setup instructions, so give the optimized program some slack
+        let actual = self
+            .execute::<M>(optimized, self.max_steps * 2 + optimized.len())
+            .map_err(Mismatch::InvalidProgram)?;
+
+        if !expected.terminated {
+            let shorter = expected.output.len().min(actual.output.len());
+            if expected.output[..shorter] != actual.output[..shorter] {
+                return Err(Mismatch::Output {
+                    expected: expected.output,
+                    actual: actual.output,
+                });
+            }
+
+            return Ok(());
+        }
+
+        if !actual.terminated {
+            return Err(Mismatch::Termination {
+                expected: expected.terminated,
+                actual: actual.terminated,
+            });
+        }
+
+        if expected.output != actual.output {
+            return Err(Mismatch::Output {
+                expected: expected.output,
+                actual: actual.output,
+            });
+        }
+
+        if expected.memory != actual.memory {
+            return Err(Mismatch::Memory {
+                expected: expected.memory,
+                actual: actual.memory,
+            });
+        }
+
+        if expected.memory_pointer != actual.memory_pointer {
+            return Err(Mismatch::MemoryPointer {
+                expected: expected.memory_pointer,
+                actual: actual.memory_pointer,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for EquivalenceChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generates random programs with balanced `IF`/`EIF` loops, using the argument range the parser
+/// can produce.
+pub struct ProgramGenerator {
+    rng: StdRng,
+    max_length: usize,
+    max_depth: usize,
+}
+
+impl ProgramGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            max_length: 32,
+            max_depth: 3,
+        }
+    }
+
+    pub fn with_max_length(self, max_length: usize) -> Self {
+        Self { max_length, ..self }
+    }
+
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    pub fn generate(&mut self) -> Vec<Instruction> {
+        let length = self.rng.gen_range(0..=self.max_length);
+        let mut result = Vec::with_capacity(length);
+        self.generate_block(&mut result, length, 0);
+
+        result
+    }
+
+    fn generate_block(&mut self, result: &mut Vec<Instruction>, length: usize, depth: usize) {
+        let end = result.len() + length;
+        while result.len() < end {
+            let remaining = end - result.len();
+            if remaining >= 2 && depth < self.max_depth && self.rng.gen_ratio(1, 6) {
+                let body = self.rng.gen_range(0..=remaining - 2);
+                result.push(Instruction::IF);
+                self.generate_block(result, body, depth + 1);
+                result.push(Instruction::EIF);
+                continue;
+            }
+
+            let argument = self.rng.gen_range(1..=10);
+            let instruction = match self.rng.gen_range(0..40) {
+                0..=9 => Instruction::INC(argument),
+                10..=17 => Instruction::DEC(argument),
+                18..=24 => Instruction::FWD(argument),
+                25..=31 => Instruction::BAK(argument),
+                32..=35 => Instruction::OUT,
+                36 | 37 => Instruction::IN,
+                38 => Instruction::RND,
+                _ => Instruction::END,
+            };
+            result.push(instruction);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EquivalenceChecker, Mismatch, ProgramGenerator};
+    use crate::{
+        compiler::MemoryModel,
+        instruction::Instruction,
+        interpreter::{DynamicMemory, FixedMemory, Memory},
+        optimizer::{
+            dead_code_eliminator::DeadCodeEliminator, fwd_bak_merger::FwdBakMerger,
+            if_eif_jmp_rewriter::IfEifJmpRewriter, inc_dec_merger::IncDecMerger,
+            jnz_remover::JnzRemover, scan_loop_rewriter::ScanLoopRewriter, Optimize, Optimizer,
+            PartialEvaluator,
+        },
+        parser::ParseError,
+    };
+
+    const PROGRAMS: u64 = 300;
+
+    fn assert_preserves_behaviour<M: Memory + Default>(optimizer: &dyn Optimize) {
+        for seed in 0..PROGRAMS {
+            let program = ProgramGenerator::new(seed).generate();
+            let optimized = optimizer.optimize(&program);
+            let checker = EquivalenceChecker::new()
+                .with_input(b"poetic")
+                .with_seed(seed)
+                .with_max_steps(2_000);
+
+            if let Err(mismatch) = checker.check::<M>(&program, &optimized) {
+                panic!(
+                    "{}\noriginal: {:?}\noptimized: {:?}",
+                    mismatch, program, optimized
+                );
+            }
+        }
+    }
+
+    fn assert_preserves_behaviour_for_fixed_memory(optimizer: &dyn Optimize) {
+        assert_preserves_behaviour::<FixedMemory<1>>(optimizer);
+        assert_preserves_behaviour::<FixedMemory<7>>(optimizer);
+        assert_preserves_behaviour::<FixedMemory<300>>(optimizer);
+    }
+
+    fn assert_preserves_behaviour_for_all_memory(optimizer: &dyn Optimize) {
+        assert_preserves_behaviour_for_fixed_memory(optimizer);
+        assert_preserves_behaviour::<DynamicMemory>(optimizer);
+    }
+
+    #[test]
+    fn generated_programs_are_balanced() {
+        let mut generator = ProgramGenerator::new(1).with_max_length(64);
+        for _ in 0..PROGRAMS {
+            let program = generator.generate();
+            let depth = program.iter().try_fold(0usize, |depth, x| match x {
+                Instruction::IF => Some(depth + 1),
+                Instruction::EIF => depth.checked_sub(1),
+                _ => Some(depth),
+            });
+            assert_eq!(depth, Some(0));
+        }
+    }
+
+    #[test]
+    fn checker_detects_different_output() {
+        let original = vec![Instruction::INC(1), Instruction::OUT];
+        let optimized = vec![Instruction::INC(2), Instruction::OUT];
+
+        let result = EquivalenceChecker::new().check::<DynamicMemory>(&original, &optimized);
+        assert_eq!(
+            result,
+            Err(Mismatch::Output {
+                expected: vec![1],
+                actual: vec![2]
+            })
+        );
+    }
+
+    #[test]
+    fn checker_detects_different_memory() {
+        let original = vec![Instruction::INC(1), Instruction::FWD(1)];
+        let optimized = vec![Instruction::FWD(1)];
+
+        let result = EquivalenceChecker::new().check::<DynamicMemory>(&original, &optimized);
+        assert_eq!(
+            result,
+            Err(Mismatch::Memory {
+                expected: vec![1],
+                actual: vec![]
+            })
+        );
+    }
+
+    #[test]
+    fn checker_detects_different_termination() {
+        let original = vec![Instruction::INC(1)];
+        let optimized = vec![Instruction::INC(1), Instruction::IF, Instruction::EIF];
+
+        let result = EquivalenceChecker::new()
+            .with_max_steps(100)
+            .check::<DynamicMemory>(&original, &optimized);
+        assert_eq!(
+            result,
+            Err(Mismatch::Termination {
+                expected: true,
+                actual: false
+            })
+        );
+    }
+
+    #[test]
+    fn checker_detects_invalid_program() {
+        let original = vec![Instruction::OUT];
+        let optimized = vec![Instruction::IF];
+
+        let result = EquivalenceChecker::new().check::<DynamicMemory>(&original, &optimized);
+        assert_eq!(
+            result,
+            Err(Mismatch::InvalidProgram(ParseError::MissingEif))
+        );
+    }
+
+    #[test]
+    fn checker_uses_input_and_seed() {
+        let program = vec![Instruction::IN, Instruction::OUT, Instruction::RND];
+
+        let checker = EquivalenceChecker::new().with_input(b"a").with_seed(7);
+        let first = checker.execute::<DynamicMemory>(&program, 10).unwrap();
+        let second = checker.execute::<DynamicMemory>(&program, 10).unwrap();
+        assert_eq!(first.output, b"a");
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn inc_dec_merger_preserves_behaviour() {
+        assert_preserves_behaviour_for_all_memory(&IncDecMerger);
+    }
+
+    #[test]
+    fn fwd_bak_merger_preserves_behaviour() {
+        assert_preserves_behaviour_for_fixed_memory(&FwdBakMerger { wrapping: true });
+        assert_preserves_behaviour::<DynamicMemory>(&FwdBakMerger { wrapping: false });
+    }
+
+    #[test]
+    fn scan_loop_rewriter_preserves_behaviour() {
+        assert_preserves_behaviour_for_all_memory(&ScanLoopRewriter);
+    }
+
+    #[test]
+    fn dead_code_eliminator_preserves_behaviour() {
+        assert_preserves_behaviour_for_all_memory(&DeadCodeEliminator);
+    }
+
+    #[test]
+    fn if_eif_jmp_rewriter_preserves_behaviour() {
+        assert_preserves_behaviour_for_all_memory(&IfEifJmpRewriter);
+    }
+
+    #[test]
+    fn jnz_remover_preserves_behaviour() {
+        assert_preserves_behaviour_for_all_memory(&JnzRemover);
+    }
+
+    #[test]
+    fn partial_evaluator_preserves_behaviour() {
+        fn evaluator<M: Memory + Default>() -> PartialEvaluator<M> {
+            PartialEvaluator::new().with_max_steps(2_000)
+        }
+
+        assert_preserves_behaviour::<FixedMemory<1>>(&evaluator::<FixedMemory<1>>());
+        assert_preserves_behaviour::<FixedMemory<7>>(&evaluator::<FixedMemory<7>>());
+        assert_preserves_behaviour::<FixedMemory<300>>(&evaluator::<FixedMemory<300>>());
+        assert_preserves_behaviour::<DynamicMemory>(&evaluator::<DynamicMemory>());
+    }
+
+    #[test]
+    fn optimizer_preserves_behaviour() {
+        // moves wrap the same way on fixed memory of any size
+        assert_preserves_behaviour_for_fixed_memory(
+            &Optimizer::new().with_memory(MemoryModel::fixed(1).unwrap()),
+        );
+        assert_preserves_behaviour::<DynamicMemory>(&Optimizer::new());
+    }
+
+    #[test]
+    fn optimizer_keeps_order_of_moves_on_dynamic_memory() {
+        // BAK wraps to the end of the memory, FWD then grows it
+        let program = vec![
+            Instruction::INC(65),
+            Instruction::BAK(1),
+            Instruction::FWD(1),
+            Instruction::OUT,
+        ];
+        let optimized = Optimizer::new().optimize(&program);
+        assert_eq!(
+            EquivalenceChecker::new().check::<DynamicMemory>(&program, &optimized),
+            Ok(())
+        );
+    }
+}
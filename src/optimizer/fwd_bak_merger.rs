@@ -1,7 +1,14 @@
 use super::Optimize;
 use crate::instruction::Instruction;
 
-pub(crate) struct FwdBakMerger;
+/// Merges consecutive `FWD`/`BAK` instructions into their total movement.
+///
+/// Moves in opposite directions are only merged on `wrapping` memory. `DynamicMemory` grows
+/// past its end but wraps below zero to its current end, so there the result depends on the
+/// order of the moves.
+pub(crate) struct FwdBakMerger {
+    pub(crate) wrapping: bool,
+}
 
 fn create_fwd_bak_instructions_from_total(total: i64) -> Vec<Instruction> {
     let mut instructions = Vec::new();
@@ -37,15 +44,19 @@ fn create_fwd_bak_instructions_from_total(total: i64) -> Vec<Instruction> {
     instructions
 }
 
-const fn get_total_count_fwd_bak(instructions: &[Instruction], start: usize) -> (i64, usize) {
+const fn get_total_count_fwd_bak(
+    instructions: &[Instruction],
+    start: usize,
+    forward: Option<bool>,
+) -> (i64, usize) {
     let mut total: i64 = 0;
     let mut j = start;
     while j < instructions.len() {
         match &instructions[j] {
-            Instruction::FWD(y) => {
+            Instruction::FWD(y) if !matches!(forward, Some(false)) => {
                 total += *y as i64;
             }
-            Instruction::BAK(y) => {
+            Instruction::BAK(y) if !matches!(forward, Some(true)) => {
                 total -= *y as i64;
             }
             _ => {
@@ -67,7 +78,8 @@ impl Optimize for FwdBakMerger {
         while i < instructions.len() {
             match instructions[i] {
                 Instruction::FWD(x) => {
-                    let (mut total, count) = get_total_count_fwd_bak(instructions, i + 1);
+                    let forward = (!self.wrapping).then_some(true);
+                    let (mut total, count) = get_total_count_fwd_bak(instructions, i + 1, forward);
 
                     total += x as i64;
 
@@ -76,7 +88,8 @@ impl Optimize for FwdBakMerger {
                     i += count;
                 }
                 Instruction::BAK(x) => {
-                    let (mut total, count) = get_total_count_fwd_bak(instructions, i + 1);
+                    let forward = (!self.wrapping).then_some(false);
+                    let (mut total, count) = get_total_count_fwd_bak(instructions, i + 1, forward);
 
                     total -= x as i64;
 
@@ -106,7 +119,7 @@ mod test {
             Instruction::FWD(3),
         ];
 
-        let optimizer = super::FwdBakMerger;
+        let optimizer = super::FwdBakMerger { wrapping: true };
         instructions = optimizer.optimize(&instructions);
         assert_eq!(instructions, vec![Instruction::FWD(6)]);
     }
@@ -123,7 +136,7 @@ mod test {
             Instruction::FWD(3),
         ];
 
-        let optimizer = super::FwdBakMerger;
+        let optimizer = super::FwdBakMerger { wrapping: true };
         instructions = optimizer.optimize(&instructions);
         assert_eq!(
             instructions,
@@ -139,7 +152,7 @@ mod test {
             Instruction::BAK(3),
         ];
 
-        let optimizer = super::FwdBakMerger;
+        let optimizer = super::FwdBakMerger { wrapping: true };
         instructions = optimizer.optimize(&instructions);
         assert_eq!(instructions, vec![Instruction::BAK(6)]);
     }
@@ -156,7 +169,7 @@ mod test {
             Instruction::BAK(3),
         ];
 
-        let optimizer = super::FwdBakMerger;
+        let optimizer = super::FwdBakMerger { wrapping: true };
         instructions = optimizer.optimize(&instructions);
         assert_eq!(
             instructions,
@@ -173,7 +186,7 @@ mod test {
             Instruction::BAK(4), // -4 = -2
         ];
 
-        let optimizer = super::FwdBakMerger;
+        let optimizer = super::FwdBakMerger { wrapping: true };
         instructions = optimizer.optimize(&instructions);
         assert_eq!(instructions, vec![Instruction::BAK(2)]);
     }
@@ -182,7 +195,7 @@ mod test {
     fn test_fwd_greater255_merged() {
         let mut instructions = vec![Instruction::FWD(150), Instruction::FWD(150)];
 
-        let optimizer = super::FwdBakMerger;
+        let optimizer = super::FwdBakMerger { wrapping: true };
         instructions = optimizer.optimize(&instructions);
         assert_eq!(
             instructions,
@@ -194,11 +207,33 @@ mod test {
     fn test_bak_greater255_merged() {
         let mut instructions = vec![Instruction::BAK(150), Instruction::BAK(150)];
 
-        let optimizer = super::FwdBakMerger;
+        let optimizer = super::FwdBakMerger { wrapping: true };
         instructions = optimizer.optimize(&instructions);
         assert_eq!(
             instructions,
             vec![Instruction::BAK(u8::MAX), Instruction::BAK(45)]
         );
     }
+
+    #[test]
+    fn test_fwd_bak_kept_apart_without_wrapping() {
+        let mut instructions = vec![
+            Instruction::FWD(1),
+            Instruction::FWD(2),
+            Instruction::BAK(3),
+            Instruction::BAK(4),
+            Instruction::FWD(5),
+        ];
+
+        let optimizer = super::FwdBakMerger { wrapping: false };
+        instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::FWD(3),
+                Instruction::BAK(7),
+                Instruction::FWD(5)
+            ]
+        );
+    }
 }
@@ -4,8 +4,10 @@ use crate::instruction::Instruction;
 
 use super::Optimize;
 
+#[allow(dead_code)]
 pub(crate) struct JnzRemover;
 
+#[allow(dead_code)]
 impl JnzRemover {
     fn no_memory_altering_instructions(&self, instructions: &[Instruction]) -> bool {
         instructions
@@ -13,7 +15,11 @@ impl JnzRemover {
             .any(|x| {
                 matches!(
                     x,
-                    Instruction::INC(_) | Instruction::DEC(_) | Instruction::IN | Instruction::RND
+                    Instruction::INC(_)
+                        | Instruction::DEC(_)
+                        | Instruction::IN
+                        | Instruction::RND
+                        | Instruction::SET(_)
                 )
             })
             .not()
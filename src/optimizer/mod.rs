@@ -1,29 +1,45 @@
 use self::{
-    fwd_bak_merger::FwdBakMerger, if_eif_jmp_rewriter::IfEifJmpRewriter,
-    inc_dec_merger::IncDecMerger,
+    dead_code_eliminator::DeadCodeEliminator, fwd_bak_merger::FwdBakMerger,
+    if_eif_jmp_rewriter::IfEifJmpRewriter, inc_dec_merger::IncDecMerger,
+    scan_loop_rewriter::ScanLoopRewriter,
 };
-use crate::instruction::Instruction;
+use crate::{compiler::MemoryModel, instruction::Instruction};
 
+pub use self::partial_evaluator::PartialEvaluator;
+
+mod dead_code_eliminator;
+pub mod equivalence;
 mod fwd_bak_merger;
 mod if_eif_jmp_rewriter;
 mod inc_dec_merger;
 mod jnz_remover;
+mod partial_evaluator;
+mod scan_loop_rewriter;
 
 pub trait Optimize {
     #[must_use]
     fn optimize(&self, instructions: &[Instruction]) -> Vec<Instruction>;
 }
 
-pub struct Optimizer;
+pub struct Optimizer {
+    memory: MemoryModel,
+    blank_tape: bool,
+}
 
 impl Optimize for Optimizer {
     fn optimize(&self, instructions: &[Instruction]) -> Vec<Instruction> {
         let mut result = instructions.to_vec();
-        let optimizers: Vec<Box<dyn Optimize>> = vec![
+        let mut optimizers: Vec<Box<dyn Optimize>> = vec![
             Box::new(IncDecMerger),
-            Box::new(FwdBakMerger),
-            Box::new(IfEifJmpRewriter),
+            Box::new(FwdBakMerger {
+                wrapping: matches!(self.memory, MemoryModel::Fixed(_)),
+            }),
+            Box::new(ScanLoopRewriter),
         ];
+        if self.blank_tape {
+            optimizers.push(Box::new(DeadCodeEliminator));
+        }
+        optimizers.push(Box::new(IfEifJmpRewriter));
 
         for optimizer in &optimizers {
             result = optimizer.optimize(&result);
@@ -34,8 +50,24 @@ impl Optimize for Optimizer {
 }
 
 impl Optimizer {
+    /// Optimizes for a growing tape starting with zeros.
     pub fn new() -> Self {
-        Self
+        Self {
+            memory: MemoryModel::Dynamic,
+            blank_tape: true,
+        }
+    }
+
+    /// The memory the program runs on. Moves in both directions are only merged on a fixed
+    /// memory, as a growing one makes the pointer depend on the order of the moves.
+    pub fn with_memory(self, memory: MemoryModel) -> Self {
+        Self { memory, ..self }
+    }
+
+    /// Whether programs start on a tape of zeros, as they do in a new interpreter. Without it
+    /// no loops are removed for never being entered, for programs continuing on a used tape.
+    pub fn with_blank_tape(self, blank_tape: bool) -> Self {
+        Self { blank_tape, ..self }
     }
 }
 
@@ -47,7 +79,7 @@ impl Default for Optimizer {
 
 #[cfg(test)]
 mod test {
-    use crate::{instruction::Instruction, optimizer::Optimize};
+    use crate::{compiler::MemoryModel, instruction::Instruction, optimizer::Optimize};
 
     #[test]
     fn test_inc_dec_fwd_bak_merged() {
@@ -57,26 +89,91 @@ mod test {
             Instruction::FWD(2),
             Instruction::BAK(1),
         ];
-        let optimizer = super::Optimizer::default();
+        let optimizer = super::Optimizer::new().with_memory(MemoryModel::fixed(30000).unwrap());
         let optimized_instructions = optimizer.optimize(&instructions);
         assert_eq!(
             optimized_instructions,
             vec![Instruction::INC(1), Instruction::FWD(1)]
         );
+
+        let optimizer = super::Optimizer::new();
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![
+                Instruction::INC(1),
+                Instruction::FWD(2),
+                Instruction::BAK(1)
+            ]
+        );
     }
 
     #[test]
     fn test_inc_dec_fwd_bak_merged_if_eif() {
-        let instructions = vec![Instruction::IF, Instruction::INC(2), Instruction::EIF];
-        let optimizer = super::Optimizer::default();
+        let instructions = vec![
+            Instruction::IN,
+            Instruction::IF,
+            Instruction::INC(2),
+            Instruction::EIF,
+        ];
+        let optimizer = super::Optimizer::new();
         let optimized_instructions = optimizer.optimize(&instructions);
         assert_eq!(
             optimized_instructions,
             vec![
-                Instruction::JIZ(2),
+                Instruction::IN,
+                Instruction::JIZ(3),
                 Instruction::INC(2),
-                Instruction::JNZ(0),
+                Instruction::JNZ(1),
             ]
         );
     }
+
+    #[test]
+    fn test_scan_loop_rewritten() {
+        let instructions = vec![
+            Instruction::IN,
+            Instruction::IF,
+            Instruction::FWD(1),
+            Instruction::FWD(1),
+            Instruction::EIF,
+        ];
+        let optimizer = super::Optimizer::new();
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![Instruction::IN, Instruction::SCF(2)]
+        );
+    }
+
+    #[test]
+    fn test_loops_kept_without_blank_tape() {
+        let instructions = vec![Instruction::IF, Instruction::OUT, Instruction::EIF];
+        let optimizer = super::Optimizer::new().with_blank_tape(false);
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![Instruction::JIZ(2), Instruction::OUT, Instruction::JNZ(0)]
+        );
+    }
+
+    #[test]
+    fn test_dead_code_removed() {
+        let instructions = vec![
+            Instruction::IF,
+            Instruction::OUT,
+            Instruction::EIF,
+            Instruction::INC(3),
+            Instruction::DEC(3),
+            Instruction::OUT,
+            Instruction::END,
+            Instruction::OUT,
+        ];
+        let optimizer = super::Optimizer::new();
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![Instruction::OUT, Instruction::END]
+        );
+    }
 }
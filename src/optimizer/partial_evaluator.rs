@@ -0,0 +1,325 @@
+use super::Optimize;
+use crate::{
+    instruction::Instruction,
+    interpreter::{DynamicMemory, Interpreter, Memory},
+};
+use std::{cell::RefCell, marker::PhantomData, rc::Rc};
+
+const DEFAULT_MAX_STEPS: usize = 1_000_000;
+
+/// Executes the deterministic prefix of a program, everything up to the first `IN` or `RND`,
+/// at optimization time and replaces it with `PUT` instructions emitting its output followed by
+/// `SET` instructions restoring the memory it left behind.
+///
+/// The result depends on the memory model, so the evaluator has to be created for the same
+/// [`Memory`] the program is later run with.
+pub struct PartialEvaluator<M: Memory + Default = DynamicMemory> {
+    max_steps: usize,
+    memory: PhantomData<M>,
+}
+
+impl<M: Memory + Default> PartialEvaluator<M> {
+    pub fn new() -> Self {
+        Self {
+            max_steps: DEFAULT_MAX_STEPS,
+            memory: PhantomData,
+        }
+    }
+
+    /// Limits how many instructions are executed at optimization time, so programs that never
+    /// read input or terminate do not hang the optimizer.
+    pub fn with_max_steps(self, max_steps: usize) -> Self {
+        Self {
+            max_steps,
+            memory: PhantomData,
+        }
+    }
+}
+
+impl<M: Memory + Default> Default for PartialEvaluator<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn push_pointer_move(result: &mut Vec<Instruction>, from: usize, to: usize) {
+    let mut distance = to.abs_diff(from);
+    while distance > 0 {
+        let step = distance.min(u8::MAX as usize);
+        result.push(if to > from {
+            Instruction::FWD(step as u8)
+        } else {
+            Instruction::BAK(step as u8)
+        });
+        distance -= step;
+    }
+}
+
+fn restore_memory<M: Memory + Default>(memory: &M) -> Vec<Instruction> {
+    let mut result = Vec::new();
+    let mut pointer = 0;
+    for (i, value) in memory.raw_memory().iter().enumerate() {
+        if *value != 0 {
+            push_pointer_move(&mut result, pointer, i);
+            result.push(Instruction::SET(*value));
+            pointer = i;
+        }
+    }
+
+    // touch the last cell so memory that grows on demand ends up with the same size
+    if memory.get_memory_size() > M::default().get_memory_size() {
+        push_pointer_move(&mut result, pointer, memory.get_memory_size() - 1);
+        pointer = memory.get_memory_size() - 1;
+    }
+
+    push_pointer_move(&mut result, pointer, memory.get_memory_pointer());
+
+    result
+}
+
+fn offset_jumps(instructions: &[Instruction], offset: isize) -> Vec<Instruction> {
+    let offset_target = |n: usize| n.checked_add_signed(offset).unwrap_or(0);
+    instructions
+        .iter()
+        .map(|instruction| match *instruction {
+            Instruction::JIZ(n) => Instruction::JIZ(offset_target(n)),
+            Instruction::JNZ(n) => Instruction::JNZ(offset_target(n)),
+            Instruction::JMP(n) => Instruction::JMP(offset_target(n)),
+            instruction => instruction,
+        })
+        .collect()
+}
+
+/// Checks if the code starting at `start` can run on its own, without jumping back into or
+/// closing loops of the code before it.
+fn is_self_contained(instructions: &[Instruction], start: usize) -> bool {
+    let mut depth = 0;
+    for instruction in &instructions[start..] {
+        match instruction {
+            Instruction::IF => depth += 1,
+            Instruction::EIF if depth == 0 => return false,
+            Instruction::EIF => depth -= 1,
+            Instruction::JIZ(n) | Instruction::JNZ(n) | Instruction::JMP(n) if *n < start => {
+                return false
+            }
+            _ => {}
+        }
+    }
+
+    true
+}
+
+impl<M: Memory + Default> Optimize for PartialEvaluator<M> {
+    fn optimize(&self, instructions: &[Instruction]) -> Vec<Instruction> {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let output_clone = output.clone();
+        let Ok(interpreter) = Interpreter::new(instructions.to_vec()) else {
+            return instructions.to_vec();
+        };
+        let mut interpreter =
+            interpreter
+                .with_memory(M::default())
+                .with_output(move |s: String| {
+                    output_clone.borrow_mut().extend(s.chars().map(|c| c as u8));
+                });
+
+        let mut steps = 0;
+        while !interpreter.has_ended() && steps < self.max_steps {
+            if matches!(
+                interpreter.instructions[interpreter.instruction_pointer],
+                Instruction::IN | Instruction::RND
+            ) {
+                break;
+            }
+
+            interpreter.step();
+            steps += 1;
+        }
+
+        if steps == 0 {
+            return instructions.to_vec();
+        }
+
+        let mut result: Vec<Instruction> = output
+            .borrow()
+            .iter()
+            .map(|byte| Instruction::PUT(*byte))
+            .collect();
+        result.extend(restore_memory(&interpreter.memory));
+        if interpreter.has_ended() {
+            result.push(Instruction::END);
+            return result;
+        }
+
+        let stop = interpreter.instruction_pointer;
+        if is_self_contained(instructions, stop) {
+            let offset = result.len() as isize - stop as isize;
+            result.extend(offset_jumps(&instructions[stop..], offset));
+        } else {
+            let offset = result.len() + 1;
+            result.push(Instruction::JMP(offset + stop));
+            result.extend(offset_jumps(instructions, offset as isize));
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PartialEvaluator;
+    use crate::{
+        instruction::Instruction,
+        interpreter::{DynamicMemory, FixedMemory, Interpreter, Memory},
+        optimizer::Optimize,
+    };
+
+    #[test]
+    fn test_output_precomputed() {
+        let instructions = vec![
+            Instruction::INC(5),
+            Instruction::IF,
+            Instruction::FWD(1),
+            Instruction::INC(13),
+            Instruction::BAK(1),
+            Instruction::DEC(1),
+            Instruction::EIF,
+            Instruction::FWD(1),
+            Instruction::OUT,
+            Instruction::OUT,
+        ];
+
+        let optimizer = PartialEvaluator::<DynamicMemory>::new();
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![
+                Instruction::PUT(65),
+                Instruction::PUT(65),
+                Instruction::FWD(1),
+                Instruction::SET(65),
+                Instruction::END
+            ]
+        );
+    }
+
+    #[test]
+    fn test_memory_restored_before_input() {
+        let instructions = vec![
+            Instruction::INC(3),
+            Instruction::OUT,
+            Instruction::FWD(2),
+            Instruction::INC(4),
+            Instruction::BAK(1),
+            Instruction::IN,
+            Instruction::OUT,
+        ];
+
+        let optimizer = PartialEvaluator::<DynamicMemory>::new();
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![
+                Instruction::PUT(3),
+                Instruction::SET(3),
+                Instruction::FWD(2),
+                Instruction::SET(4),
+                Instruction::BAK(1),
+                Instruction::IN,
+                Instruction::OUT,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_program_starting_with_input_untouched() {
+        let instructions = vec![Instruction::IN, Instruction::OUT];
+
+        let optimizer = PartialEvaluator::<DynamicMemory>::new();
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(optimized_instructions, instructions);
+    }
+
+    #[test]
+    fn test_stop_inside_loop_jumps_into_original() {
+        let instructions = vec![
+            Instruction::INC(2),
+            Instruction::IF,
+            Instruction::OUT,
+            Instruction::RND,
+            Instruction::DEC(1),
+            Instruction::EIF,
+        ];
+
+        let optimizer = PartialEvaluator::<DynamicMemory>::new();
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![
+                Instruction::PUT(2),
+                Instruction::SET(2),
+                Instruction::JMP(6),
+                Instruction::INC(2),
+                Instruction::IF,
+                Instruction::OUT,
+                Instruction::RND,
+                Instruction::DEC(1),
+                Instruction::EIF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixed_memory_wraparound() {
+        let instructions = vec![Instruction::BAK(1), Instruction::INC(7), Instruction::IN];
+
+        let optimizer = PartialEvaluator::<FixedMemory<4>>::new();
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![Instruction::FWD(3), Instruction::SET(7), Instruction::IN]
+        );
+    }
+
+    #[test]
+    fn test_max_steps_limits_evaluation() {
+        let instructions = vec![Instruction::INC(1), Instruction::IF, Instruction::EIF];
+
+        let optimizer = PartialEvaluator::<DynamicMemory>::new().with_max_steps(10);
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(optimized_instructions[0], Instruction::SET(1));
+        assert!(optimized_instructions.contains(&Instruction::EIF));
+    }
+
+    #[test]
+    fn test_same_output_and_memory() {
+        let instructions =
+            crate::parser::Parser::parse(crate::samples::HELLO_WORLD_SHORTEST).unwrap();
+
+        let optimizer = PartialEvaluator::<FixedMemory<100>>::new();
+        let optimized_instructions = optimizer.optimize(&instructions);
+
+        let mut expected_output = String::new();
+        let mut expected = Interpreter::new(instructions)
+            .unwrap()
+            .with_fixed_size_memory::<100>()
+            .with_output(|s: String| expected_output.push_str(&s));
+        expected.run();
+        let expected_memory = expected.memory.raw_memory().to_vec();
+        let expected_pointer = expected.memory.get_memory_pointer();
+        drop(expected);
+
+        let mut actual_output = String::new();
+        let mut actual = Interpreter::new(optimized_instructions)
+            .unwrap()
+            .with_fixed_size_memory::<100>()
+            .with_output(|s: String| actual_output.push_str(&s));
+        actual.run();
+
+        assert_eq!(actual.memory.raw_memory(), expected_memory);
+        assert_eq!(actual.memory.get_memory_pointer(), expected_pointer);
+        drop(actual);
+        assert_eq!(actual_output, expected_output);
+        assert_eq!(actual_output, "Hello, World!");
+    }
+}
@@ -0,0 +1,95 @@
+use super::Optimize;
+use crate::instruction::Instruction;
+
+/// Rewrites `IF FWD n EIF` and `IF BAK n EIF` loops, which step through memory until they hit a
+/// zero cell, into single `SCF(n)`/`SCB(n)` scan instructions.
+pub(crate) struct ScanLoopRewriter;
+
+impl Optimize for ScanLoopRewriter {
+    fn optimize(&self, instructions: &[Instruction]) -> Vec<Instruction> {
+        let mut result = Vec::new();
+
+        let mut i = 0;
+        while i < instructions.len() {
+            match instructions[i..] {
+                [Instruction::IF, Instruction::FWD(n), Instruction::EIF, ..] => {
+                    result.push(Instruction::SCF(n));
+                    i += 2;
+                }
+                [Instruction::IF, Instruction::BAK(n), Instruction::EIF, ..] => {
+                    result.push(Instruction::SCB(n));
+                    i += 2;
+                }
+                [instruction, ..] => result.push(instruction),
+                [] => {}
+            }
+
+            i += 1;
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{instruction::Instruction, optimizer::Optimize};
+
+    #[test]
+    fn test_fwd_scan_loop() {
+        let instructions = vec![Instruction::IF, Instruction::FWD(1), Instruction::EIF];
+
+        let optimizer = super::ScanLoopRewriter;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(optimized_instructions, vec![Instruction::SCF(1)]);
+    }
+
+    #[test]
+    fn test_bak_scan_loop() {
+        let instructions = vec![Instruction::IF, Instruction::BAK(3), Instruction::EIF];
+
+        let optimizer = super::ScanLoopRewriter;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(optimized_instructions, vec![Instruction::SCB(3)]);
+    }
+
+    #[test]
+    fn test_nested_scan_loop() {
+        let instructions = vec![
+            Instruction::IF,
+            Instruction::DEC(1),
+            Instruction::IF,
+            Instruction::FWD(2),
+            Instruction::EIF,
+            Instruction::BAK(1),
+            Instruction::EIF,
+        ];
+
+        let optimizer = super::ScanLoopRewriter;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(
+            optimized_instructions,
+            vec![
+                Instruction::IF,
+                Instruction::DEC(1),
+                Instruction::SCF(2),
+                Instruction::BAK(1),
+                Instruction::EIF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_scan_loop_untouched() {
+        let instructions = vec![
+            Instruction::IF,
+            Instruction::FWD(1),
+            Instruction::INC(1),
+            Instruction::EIF,
+        ];
+
+        let optimizer = super::ScanLoopRewriter;
+        let optimized_instructions = optimizer.optimize(&instructions);
+        assert_eq!(optimized_instructions, instructions);
+    }
+}
@@ -1,13 +1,19 @@
 use crate::instruction::Instruction;
 use split_digits::SplitDigitIterator;
-use std::{cmp::Ordering, fmt::Display};
+use std::{cmp::Ordering, fmt::Display, ops::Range};
+#[cfg(feature = "unicode")]
+use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum ParseError {
     UnknownInstruction(u8),
     NeedsArgument(u8),
     MissingIf,
     MissingEif,
+    /// A word of the given number of letters with [`LongWords::Error`].
+    WordTooLong(usize),
 }
 
 impl Display for ParseError {
@@ -17,26 +23,256 @@ impl Display for ParseError {
                 write!(f, "Unknown instruction: {}", instruction)
             }
             ParseError::NeedsArgument(instruction) => {
-                match Parser::get_instruction_name(instruction) {
-                    Some(instruction_name) => {
-                        write!(f, "{} Instruction needs an argument", instruction_name)
-                    }
-                    // should never happen
-                    None => {
-                        write!(
-                            f,
-                            "Unknown instruction \"{}\" needs an argument",
-                            instruction
-                        )
-                    }
-                }
+                write!(
+                    f,
+                    "{} Instruction needs an argument",
+                    opcode_name(*instruction)
+                )
             }
             ParseError::MissingIf => write!(f, "Missing IF"),
             ParseError::MissingEif => write!(f, "Missing EIF"),
+            ParseError::WordTooLong(letters) => {
+                write!(f, "Word of {} letters is too long", letters)
+            }
         }
     }
 }
 
+/// What counts as a letter of a word. `Unicode` only exists with the `unicode` feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Letters {
+    /// Only `a-z` and `A-Z`, as the language specifies.
+    #[default]
+    Ascii,
+    /// Every grapheme cluster starting with an alphabetic character is one letter, so "é" and
+    /// "ß" are letters whether written as one character or with combining marks.
+    #[cfg(feature = "unicode")]
+    Unicode,
+}
+
+/// How words of more than 10 letters are read, 10 letters are always `0`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LongWords {
+    /// As the digits of their length, as the language specifies.
+    #[default]
+    Split,
+    /// As their length modulo 10.
+    Modulo,
+    /// As a [`ParseError::WordTooLong`].
+    Error,
+}
+
+/// The rules for reading words, so dialects of the language can be parsed. The defaults follow
+/// the language specification.
+///
+/// Besides [`Parser::parse_with`], the annotation, incremental parser, linter and synonym
+/// rewriter take them too, and then read exactly the words the parser reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParserOptions {
+    letters: Letters,
+    joiners: Vec<char>,
+    ignored: Vec<char>,
+    digits_are_letters: bool,
+    long_words: LongWords,
+}
+
+/// What a segment of the source is to the word it is in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Segment {
+    Letter,
+    Joiner,
+    Ignored,
+    Break,
+}
+
+/// Groups segments, given by their byte offset, into words ending at a break or at joiners not
+/// followed by a letter.
+#[derive(Debug, Default)]
+pub(crate) struct WordReader {
+    /// Start and letter count of the current word.
+    word: Option<(usize, usize)>,
+    /// Start of the joiners after the last letter.
+    joiners: Option<usize>,
+}
+
+impl WordReader {
+    /// Returns the span and letter count of the word the segment ends.
+    pub(crate) fn push(&mut self, i: usize, segment: Segment) -> Option<(Range<usize>, usize)> {
+        match segment {
+            Segment::Letter => {
+                self.joiners = None;
+                self.word.get_or_insert((i, 0)).1 += 1;
+            }
+            Segment::Ignored => {
+                self.word.get_or_insert((i, 0));
+            }
+            Segment::Joiner if self.word.is_some_and(|(_, letters)| letters > 0) => {
+                self.joiners.get_or_insert(i);
+            }
+            Segment::Joiner | Segment::Break => {
+                let end = self.joiners.take().unwrap_or(i);
+                if let Some((start, letters)) = self.word.take() {
+                    if letters > 0 {
+                        return Some((start..end, letters));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl ParserOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_letters(self, letters: Letters) -> Self {
+        Self { letters, ..self }
+    }
+
+    /// Characters joining the letters on both sides into one word without counting, like the
+    /// hyphen in "well-known". Anywhere else they break words. None by default.
+    pub fn with_joiners(self, joiners: impl IntoIterator<Item = char>) -> Self {
+        Self {
+            joiners: joiners.into_iter().collect(),
+            ..self
+        }
+    }
+
+    /// Characters removed before reading words, so they neither count nor break words. Only
+    /// the apostrophe by default.
+    pub fn with_ignored(self, ignored: impl IntoIterator<Item = char>) -> Self {
+        Self {
+            ignored: ignored.into_iter().collect(),
+            ..self
+        }
+    }
+
+    /// Counts digits written in the text as letters instead of word breaks.
+    pub fn with_digits_as_letters(self, digits_are_letters: bool) -> Self {
+        Self {
+            digits_are_letters,
+            ..self
+        }
+    }
+
+    pub fn with_long_words(self, long_words: LongWords) -> Self {
+        Self { long_words, ..self }
+    }
+
+    pub fn letters(&self) -> Letters {
+        self.letters
+    }
+
+    pub fn joiners(&self) -> &[char] {
+        &self.joiners
+    }
+
+    pub fn ignored(&self) -> &[char] {
+        &self.ignored
+    }
+
+    pub fn digits_are_letters(&self) -> bool {
+        self.digits_are_letters
+    }
+
+    pub fn long_words(&self) -> LongWords {
+        self.long_words
+    }
+
+    /// The digits a word of `letters` letters is read as.
+    pub fn digits(&self, letters: usize) -> Result<Vec<u8>, ParseError> {
+        match self.long_words {
+            LongWords::Modulo => Ok(vec![(letters % 10) as u8]),
+            LongWords::Error if letters > 10 => Err(ParseError::WordTooLong(letters)),
+            _ => Ok(Parser::word_digits(letters)),
+        }
+    }
+
+    pub(crate) fn segment(&self, c: char) -> Segment {
+        let is_letter = match self.letters {
+            Letters::Ascii => {
+                c.is_ascii_alphabetic() || self.digits_are_letters && c.is_ascii_digit()
+            }
+            #[cfg(feature = "unicode")]
+            Letters::Unicode => c.is_alphabetic() || self.digits_are_letters && c.is_numeric(),
+        };
+
+        if self.ignored.contains(&c) {
+            Segment::Ignored
+        } else if is_letter {
+            Segment::Letter
+        } else if self.joiners.contains(&c) {
+            Segment::Joiner
+        } else {
+            Segment::Break
+        }
+    }
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            letters: Letters::Ascii,
+            joiners: Vec::new(),
+            ignored: vec!['\''],
+            digits_are_letters: false,
+            long_words: LongWords::Split,
+        }
+    }
+}
+
+/// The name of an opcode, `0` and anything unknown is `END`.
+pub const fn opcode_name(opcode: u8) -> &'static str {
+    match opcode {
+        1 => "IF",
+        2 => "EIF",
+        3 => "INC",
+        4 => "DEC",
+        5 => "FWD",
+        6 => "BAK",
+        7 => "OUT",
+        8 => "IN",
+        9 => "RND",
+        _ => "END",
+    }
+}
+
+/// Whether the digit after `opcode` is its argument.
+pub(crate) const fn takes_argument(opcode: u8) -> bool {
+    matches!(opcode, 3..=6)
+}
+
+/// The instruction of `opcode` with the digit following it as argument, where `0` means 10.
+/// `argument` is only looked at for opcodes that take one.
+pub(crate) const fn decode(opcode: u8, argument: Option<u8>) -> Result<Instruction, ParseError> {
+    let argument = match argument {
+        Some(0) => 10,
+        Some(argument) => argument,
+        None if takes_argument(opcode) => return Err(ParseError::NeedsArgument(opcode)),
+        None => 0,
+    };
+    Ok(match opcode {
+        0 | 10 => Instruction::END,
+        1 => Instruction::IF,
+        2 => Instruction::EIF,
+        3 => Instruction::INC(argument),
+        4 => Instruction::DEC(argument),
+        5 => Instruction::FWD(argument),
+        6 => Instruction::BAK(argument),
+        7 => Instruction::OUT,
+        8 => Instruction::IN,
+        9 => Instruction::RND,
+        _ => return Err(ParseError::UnknownInstruction(opcode)),
+    })
+}
+
 pub struct Parser {}
 
 impl Parser {
@@ -53,6 +289,16 @@ impl Parser {
         }
     }
 
+    /// The digits a word of `letters` letters is read as, words longer than 10 letters are
+    /// split into the digits of their length.
+    pub(crate) fn word_digits(letters: usize) -> Vec<u8> {
+        match letters.cmp(&10) {
+            Ordering::Less => vec![letters as u8],
+            Ordering::Equal => vec![0],
+            Ordering::Greater => Parser::split_digits(letters),
+        }
+    }
+
     pub fn parse_intermediate(source: &str) -> Vec<u8> {
         let mut result = Vec::new();
 
@@ -62,22 +308,54 @@ impl Parser {
             .collect::<String>()
             .split_whitespace()
             .map(|w| w.len())
-            .for_each(|d| match d.cmp(&10) {
-                Ordering::Less => result.push(d as u8),
-                Ordering::Equal => result.push(0),
-                Ordering::Greater => result.append(&mut Parser::split_digits(d)),
-            });
+            .for_each(|d| result.append(&mut Parser::word_digits(d)));
 
         result
     }
 
-    const fn argument_conversion(argument: u8) -> u8 {
-        match argument {
-            0 => 10,
-            v => v,
+    /// Like [`Parser::parse_intermediate`], reading words as configured by `options`.
+    pub fn parse_intermediate_with(
+        source: &str,
+        options: &ParserOptions,
+    ) -> Result<Vec<u8>, ParseError> {
+        let mut result = Vec::new();
+        for (_, letters) in Parser::word_spans(source, options) {
+            result.append(&mut options.digits(letters)?);
+        }
+
+        Ok(result)
+    }
+
+    /// The byte range and letter count of every word in `source`, read as configured by
+    /// `options`. Words without letters are skipped.
+    pub fn word_spans(source: &str, options: &ParserOptions) -> Vec<(Range<usize>, usize)> {
+        match options.letters {
+            Letters::Ascii => Parser::collect_words(
+                source.char_indices().map(|(i, c)| (i, options.segment(c))),
+                source.len(),
+            ),
+            #[cfg(feature = "unicode")]
+            Letters::Unicode => Parser::collect_words(
+                source.grapheme_indices(true).map(|(i, grapheme)| {
+                    let c = grapheme.chars().next().unwrap_or(' ');
+                    (i, options.segment(c))
+                }),
+                source.len(),
+            ),
         }
     }
 
+    fn collect_words(
+        segments: impl Iterator<Item = (usize, Segment)>,
+        end: usize,
+    ) -> Vec<(Range<usize>, usize)> {
+        let mut reader = WordReader::default();
+        segments
+            .chain([(end, Segment::Break)])
+            .filter_map(|(i, segment)| reader.push(i, segment))
+            .collect()
+    }
+
     const fn check_if_eif_mismatch(instructions: &[Instruction]) -> Option<ParseError> {
         // check for matching if/eif
         let mut i = 0;
@@ -137,55 +415,12 @@ impl Parser {
         None
     }
 
-    const fn get_instruction_name(instruction: &u8) -> Option<&'static str> {
-        match instruction {
-            0 => Some("END"),
-            1 => Some("IF"),
-            2 => Some("EIF"),
-            3 => Some("INC"),
-            4 => Some("DEC"),
-            5 => Some("FWD"),
-            6 => Some("BAK"),
-            7 => Some("OUT"),
-            8 => Some("IN"),
-            9 => Some("RND"),
-            10 => Some("END"),
-            _ => None,
-        }
-    }
-
     pub fn parse_instructions(intermediate: &[u8]) -> Result<Vec<Instruction>, ParseError> {
         let mut result = Vec::new();
-        let mut iter = intermediate.iter();
-        while let Some(arg) = iter.next() {
-            let instruction = match arg {
-                0 => Ok(Instruction::END),
-                1 => Ok(Instruction::IF),
-                2 => Ok(Instruction::EIF),
-                3 => iter
-                    .next()
-                    .map(|x| Instruction::INC(Parser::argument_conversion(*x)))
-                    .ok_or(ParseError::NeedsArgument(3)),
-                4 => iter
-                    .next()
-                    .map(|x| Instruction::DEC(Parser::argument_conversion(*x)))
-                    .ok_or(ParseError::NeedsArgument(4)),
-                5 => iter
-                    .next()
-                    .map(|x| Instruction::FWD(Parser::argument_conversion(*x)))
-                    .ok_or(ParseError::NeedsArgument(5)),
-                6 => iter
-                    .next()
-                    .map(|x| Instruction::BAK(Parser::argument_conversion(*x)))
-                    .ok_or(ParseError::NeedsArgument(6)),
-                7 => Ok(Instruction::OUT),
-                8 => Ok(Instruction::IN),
-                9 => Ok(Instruction::RND),
-                10 => Ok(Instruction::END),
-                _ => Err(ParseError::UnknownInstruction(*arg)),
-            }?;
-
-            result.push(instruction);
+        let mut iter = intermediate.iter().copied();
+        while let Some(opcode) = iter.next() {
+            let argument = takes_argument(opcode).then(|| iter.next()).flatten();
+            result.push(decode(opcode, argument)?);
         }
 
         Parser::check_if_eif_mismatch(&result).map_or(Ok(result), Err)
@@ -195,11 +430,21 @@ impl Parser {
         let intermediate = Self::parse_intermediate(source);
         Self::parse_instructions(&intermediate)
     }
+
+    pub fn parse_with(
+        source: &str,
+        options: &ParserOptions,
+    ) -> Result<Vec<Instruction>, ParseError> {
+        let intermediate = Self::parse_intermediate_with(source, options)?;
+        Self::parse_instructions(&intermediate)
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Parser;
+    #[cfg(feature = "unicode")]
+    use super::Letters;
+    use super::{LongWords, Parser, ParserOptions};
     use crate::{instruction::Instruction, parser::ParseError};
 
     #[test]
@@ -422,6 +667,87 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_options_default_to_ascii() {
+        let source = "Don't stop, extraordinarily   brave\nhearts! ' -- a b für";
+        assert_eq!(
+            Parser::parse_intermediate_with(source, &ParserOptions::new()),
+            Ok(Parser::parse_intermediate(source))
+        );
+        assert_eq!(
+            Parser::parse_intermediate_with("Straße", &ParserOptions::new()),
+            Ok(vec![4, 1])
+        );
+    }
+
+    #[test]
+    fn test_joiners_and_ignored() {
+        let options = ParserOptions::new().with_joiners(['-']);
+        assert_eq!(
+            Parser::parse_intermediate_with("well-known - -a b--c d-", &options),
+            Ok(vec![9, 1, 2, 1])
+        );
+        assert_eq!(
+            Parser::word_spans("d-'", &options),
+            vec![(0..1, 1)],
+            "trailing joiners are not part of the word"
+        );
+
+        // without the apostrophe ignored it breaks words like any other character
+        let options = ParserOptions::new().with_ignored(['*']);
+        assert_eq!(
+            Parser::parse_intermediate_with("don't *bold*ly", &options),
+            Ok(vec![3, 1, 6])
+        );
+    }
+
+    #[test]
+    fn test_digits_as_letters() {
+        let options = ParserOptions::new().with_digits_as_letters(true);
+        assert_eq!(
+            Parser::parse_intermediate_with("R2D2 4u", &options),
+            Ok(vec![4, 2])
+        );
+    }
+
+    #[test]
+    fn test_long_words() {
+        let source = "aaaaaaaaaa aaaaaaaaaaaa";
+        let options = ParserOptions::new().with_long_words(LongWords::Modulo);
+        assert_eq!(
+            Parser::parse_intermediate_with(source, &options),
+            Ok(vec![0, 2])
+        );
+        let options = ParserOptions::new().with_long_words(LongWords::Error);
+        assert_eq!(
+            Parser::parse_intermediate_with(source, &options),
+            Err(ParseError::WordTooLong(12))
+        );
+        assert_eq!(
+            Parser::parse_with("aaaaaaaaaa", &options),
+            Ok(vec![Instruction::END])
+        );
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_unicode_letters() {
+        let options = ParserOptions::new().with_letters(Letters::Unicode);
+        assert_eq!(
+            Parser::parse_intermediate_with("Straße für l'été", &options),
+            Ok(vec![6, 3, 4])
+        );
+        // "é" as "e" and a combining accent is still one letter
+        assert_eq!(
+            Parser::parse_intermediate_with("e\u{301}te\u{301}", &options),
+            Ok(vec![3])
+        );
+        assert_eq!(
+            Parser::parse_with("ïnç à", &options),
+            Ok(vec![Instruction::INC(1)])
+        );
+    }
+
     #[test]
     fn test_if_eif_match() {
         let instructions = vec![Instruction::IF, Instruction::EIF];
@@ -0,0 +1,325 @@
+//! A read-eval-print loop driver running every line of a poem on the same [`Interpreter`], so
+//! the tape survives between lines. Front-ends read lines, pass them to [`Repl::eval`] and show
+//! the [`Response`].
+//!
+//! Lines starting with `:` are meta commands, see [`HELP`].
+
+use crate::{
+    interpreter::{Interpreter, Memory},
+    optimizer::{Optimize, Optimizer},
+    parser::{ParseError, Parser},
+};
+use rand::RngCore;
+use std::{fmt::Display, path::Path};
+
+pub const HELP: &str = "\
+:memory [radius]   show the cells around the memory pointer
+:reset             clear the memory and any unfinished input
+:load <file>       run a poem from a file
+:optimize          toggle the optimizer
+:help              show this help
+:quit              leave";
+
+const DEFAULT_RADIUS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    /// The input ran to completion.
+    Ran,
+    /// The input ends inside a loop or before an argument, the next line continues it.
+    Incomplete,
+    /// Output of a meta command.
+    Message(String),
+    Quit,
+}
+
+#[derive(Debug)]
+pub enum ReplError {
+    Parse(ParseError),
+    Io(std::io::Error),
+    UnknownCommand(String),
+    /// The input ran for the given number of instructions without finishing.
+    StepLimit(u64),
+}
+
+impl Display for ReplError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplError::Parse(error) => write!(f, "{}", error),
+            ReplError::Io(error) => write!(f, "{}", error),
+            ReplError::UnknownCommand(command) => {
+                write!(f, "Unknown command :{}, try :help", command)
+            }
+            ReplError::StepLimit(limit) => write!(f, "Step limit of {} reached", limit),
+        }
+    }
+}
+
+impl std::error::Error for ReplError {}
+
+pub struct Repl<I, O, M, R>
+where
+    I: FnMut() -> Option<u8>,
+    O: FnMut(String),
+    M: Memory,
+    R: RngCore,
+{
+    pub interpreter: Interpreter<I, O, M, R>,
+    optimize: bool,
+    step_limit: Option<u64>,
+    pending: String,
+}
+
+impl<I, O, M, R> Repl<I, O, M, R>
+where
+    I: FnMut() -> Option<u8>,
+    O: FnMut(String),
+    M: Memory,
+    R: RngCore,
+{
+    pub fn new(interpreter: Interpreter<I, O, M, R>) -> Self {
+        Self {
+            interpreter,
+            optimize: false,
+            step_limit: None,
+            pending: String::new(),
+        }
+    }
+
+    pub fn with_optimizer(self, optimize: bool) -> Self {
+        Self { optimize, ..self }
+    }
+
+    /// Stops every input after `step_limit` instructions, the tape keeps its state.
+    pub fn with_step_limit(self, step_limit: u64) -> Self {
+        Self {
+            step_limit: Some(step_limit),
+            ..self
+        }
+    }
+
+    pub fn is_optimizing(&self) -> bool {
+        self.optimize
+    }
+
+    /// Whether earlier lines wait for the rest of a loop or an argument.
+    pub fn is_incomplete(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn eval(&mut self, line: &str) -> Result<Response, ReplError> {
+        if let Some(command) = line.trim().strip_prefix(':') {
+            return self.command(command);
+        }
+
+        self.pending.push_str(line);
+        self.pending.push('\n');
+        let source = std::mem::take(&mut self.pending);
+        match self.execute(&source) {
+            Err(ReplError::Parse(ParseError::MissingEif | ParseError::NeedsArgument(_))) => {
+                self.pending = source;
+                Ok(Response::Incomplete)
+            }
+            result => result.map(|_| Response::Ran),
+        }
+    }
+
+    /// Runs a complete poem on the current tape.
+    pub fn execute(&mut self, source: &str) -> Result<(), ReplError> {
+        let mut instructions = Parser::parse(source).map_err(ReplError::Parse)?;
+        if self.optimize {
+            // the tape keeps the values of earlier lines
+            instructions = Optimizer::new()
+                .with_blank_tape(false)
+                .optimize(&instructions);
+        }
+        self.interpreter
+            .load(instructions)
+            .map_err(ReplError::Parse)?;
+
+        let mut steps = 0;
+        while !self.interpreter.has_ended() {
+            if let Some(limit) = self.step_limit.filter(|limit| steps >= *limit) {
+                return Err(ReplError::StepLimit(limit));
+            }
+
+            self.interpreter.step();
+            steps += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Zeroes the memory, moves the pointer to the first cell and drops unfinished input.
+    pub fn reset(&mut self) {
+        let memory = &mut self.interpreter.memory;
+        for cell in 0..memory.get_memory_size() {
+            memory.set_memory_pointer(cell);
+            memory.set_memory_value(0);
+        }
+        memory.set_memory_pointer(0);
+        self.pending.clear();
+    }
+
+    /// Shows up to `radius` cells on both sides of the memory pointer, prefixed with the index
+    /// of the first cell and the pointer's cell in brackets.
+    pub fn show_memory(&self, radius: usize) -> String {
+        let memory = self.interpreter.memory.raw_memory();
+        let pointer = self.interpreter.memory.get_memory_pointer();
+        let start = pointer.saturating_sub(radius);
+        let end = pointer.saturating_add(radius).min(memory.len() - 1);
+
+        let cells = (start..=end)
+            .map(|i| {
+                if i == pointer {
+                    format!("[{}]", memory[i])
+                } else {
+                    memory[i].to_string()
+                }
+            })
+            .collect::<Vec<_>>();
+        format!("{}: {}", start, cells.join(" "))
+    }
+
+    fn command(&mut self, command: &str) -> Result<Response, ReplError> {
+        let (name, argument) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(name, argument)| (name, argument.trim()));
+        match name {
+            "memory" | "m" => {
+                let radius = match argument {
+                    "" => DEFAULT_RADIUS,
+                    radius => radius
+                        .parse()
+                        .map_err(|_| ReplError::UnknownCommand(command.to_string()))?,
+                };
+                Ok(Response::Message(self.show_memory(radius)))
+            }
+            "reset" => {
+                self.reset();
+                Ok(Response::Message("Memory cleared".to_string()))
+            }
+            "load" => {
+                let source = std::fs::read_to_string(Path::new(argument)).map_err(ReplError::Io)?;
+                self.pending.clear();
+                self.execute(&source).map(|_| Response::Ran)
+            }
+            "optimize" => {
+                self.optimize = !self.optimize;
+                Ok(Response::Message(format!(
+                    "Optimizer {}",
+                    if self.optimize { "on" } else { "off" }
+                )))
+            }
+            "help" | "h" => Ok(Response::Message(HELP.to_string())),
+            "quit" | "q" => Ok(Response::Quit),
+            _ => Err(ReplError::UnknownCommand(command.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Repl, ReplError, Response};
+    use crate::{
+        interpreter::{DynamicMemory, Interpreter},
+        parser::ParseError,
+    };
+    use rand::rngs::ThreadRng;
+    use std::{cell::RefCell, rc::Rc};
+
+    type TestRepl<O> = Repl<fn() -> Option<u8>, O, DynamicMemory, ThreadRng>;
+
+    fn repl(output: &Rc<RefCell<String>>) -> TestRepl<impl FnMut(String)> {
+        let output = output.clone();
+        let interpreter = Interpreter::new(Vec::new())
+            .unwrap()
+            .with_output(move |s: String| output.borrow_mut().push_str(&s));
+        Repl::new(interpreter)
+    }
+
+    #[test]
+    fn test_tape_persists_between_lines() {
+        let output = Rc::new(RefCell::new(String::new()));
+        let mut repl = repl(&output);
+        assert_eq!(
+            repl.eval("inc aaaaaaaaa inc aaaaaaa").unwrap(),
+            Response::Ran
+        );
+        assert_eq!(repl.eval("outputa").unwrap(), Response::Ran);
+        assert_eq!(repl.eval("inc a outputa").unwrap(), Response::Ran);
+        assert_eq!(output.borrow().as_str(), "\u{10}\u{11}");
+    }
+
+    #[test]
+    fn test_incomplete_lines() {
+        let output = Rc::new(RefCell::new(String::new()));
+        let mut repl = repl(&output);
+        assert_eq!(repl.eval("inc aaa a").unwrap(), Response::Incomplete);
+        assert!(repl.is_incomplete());
+        assert_eq!(repl.eval("outputa decc").unwrap(), Response::Incomplete);
+        assert_eq!(repl.eval("a ei").unwrap(), Response::Ran);
+        assert!(!repl.is_incomplete());
+        assert_eq!(output.borrow().as_str(), "\u{3}\u{2}\u{1}");
+
+        assert!(matches!(
+            repl.eval("ei"),
+            Err(ReplError::Parse(ParseError::MissingIf))
+        ));
+        assert!(!repl.is_incomplete());
+    }
+
+    #[test]
+    fn test_optimizer_keeps_loops_on_used_tape() {
+        let output = Rc::default();
+        let mut repl = repl(&output).with_optimizer(true);
+        repl.eval("inc a").unwrap();
+        repl.eval("a outputa decc a ei").unwrap();
+        assert_eq!(*output.borrow(), "\x01");
+    }
+
+    #[test]
+    fn test_meta_commands() {
+        let mut repl = repl(&Rc::default());
+        repl.eval("inc aa fwdfw aaa inc aaaaa").unwrap();
+        assert_eq!(
+            repl.eval(":memory 2").unwrap(),
+            Response::Message("1: 0 0 [5] 0 0".to_string())
+        );
+        assert_eq!(
+            repl.eval(":m").unwrap(),
+            Response::Message("0: 2 0 0 [5] 0 0 0 0 0 0 0 0".to_string())
+        );
+
+        assert_eq!(
+            repl.eval(":optimize").unwrap(),
+            Response::Message("Optimizer on".to_string())
+        );
+        assert!(repl.is_optimizing());
+
+        assert_eq!(
+            repl.eval(":reset").unwrap(),
+            Response::Message("Memory cleared".to_string())
+        );
+        assert_eq!(
+            repl.eval(":memory 1").unwrap(),
+            Response::Message("0: [0] 0".to_string())
+        );
+
+        assert!(matches!(
+            repl.eval(":frobnicate"),
+            Err(ReplError::UnknownCommand(_))
+        ));
+        assert_eq!(repl.eval(":quit").unwrap(), Response::Quit);
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let mut repl = repl(&Rc::default()).with_step_limit(10);
+        assert!(matches!(
+            repl.eval("inc a a ei"),
+            Err(ReplError::StepLimit(10))
+        ));
+        assert_eq!(repl.eval("outputa").unwrap(), Response::Ran);
+    }
+}
@@ -0,0 +1,7 @@
+//! Poems shared by the tests.
+
+/// Prints `Hello World!`.
+pub(crate) const HELLO_WORLD: &str = "inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa outputa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aa outputa inc aaaaaaa outputa outputa inc aaa outputa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaa outputa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc a outputa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaa outputa inc aaa outputa decc aaaaaa outputa decc aaaaaaaa outputa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaa outputa endprogram";
+
+/// Prints `Hello, World!`, moving below the first cell so it only works on a wrapping memory.
+pub(crate) const HELLO_WORLD_SHORTEST: &str = "inc a a decc aa fwdfw a decc a a fwdfw aa inc a fwdfw a decc aaaaa bakbak aa ei bakbak a decc aa bakbak a decc aaa ei fwdfw a decc a outputa fwdfw aaa inc a outputa fwdfw aa outputa outputa inc aaa a outputa fwdfw a ei bakbak aaaa outputa inc a inc aa outputa decc aaaaaa outputa bakbak aa decc a outputa fwdfw aaaa inc a outputa";
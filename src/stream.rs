@@ -0,0 +1,401 @@
+//! Parses poems piece by piece from a [`BufRead`] or an iterator of chunks, yielding every
+//! instruction as soon as its words are complete. Words, UTF-8 sequences and grapheme clusters
+//! may be split across chunks, only the unfinished end of the previous chunk is kept, so poems
+//! of any size are parsed in constant memory.
+//!
+//! Errors are reported as soon as they are found: an `EIF` without `IF` when it is read,
+//! everything else at the end of the input. Unlike [`Parser::parse`](crate::parser::Parser::parse),
+//! instructions before the error have already been yielded by then.
+//!
+//! [`Interpreter::run_stream`](crate::interpreter::Interpreter::run_stream) runs the instructions
+//! while they are parsed.
+
+use crate::{
+    instruction::Instruction,
+    parser::{self, takes_argument, Letters, ParseError, ParserOptions, Segment, WordReader},
+};
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    io::{self, BufRead},
+    iter::Map,
+};
+#[cfg(feature = "unicode")]
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug)]
+pub enum StreamError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::Io(error) => write!(f, "{}", error),
+            StreamError::Parse(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+impl From<ParseError> for StreamError {
+    fn from(error: ParseError) -> Self {
+        StreamError::Parse(error)
+    }
+}
+
+impl From<io::Error> for StreamError {
+    fn from(error: io::Error) -> Self {
+        StreamError::Io(error)
+    }
+}
+
+/// The chunks of a [`BufRead`], as large as its buffer.
+pub struct ReadChunks<R: BufRead> {
+    reader: R,
+}
+
+impl<R: BufRead> Iterator for ReadChunks<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let chunk = match self.reader.fill_buf() {
+                Ok([]) => return None,
+                Ok(buffer) => buffer.to_vec(),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Some(Err(error)),
+            };
+            self.reader.consume(chunk.len());
+            return Some(Ok(chunk));
+        }
+    }
+}
+
+pub struct StreamParser<C> {
+    chunks: C,
+    options: ParserOptions,
+    /// The end of the last chunk that is not a complete UTF-8 sequence yet.
+    bytes: Vec<u8>,
+    /// The last grapheme cluster, which the next chunk may continue.
+    carry: String,
+    /// Byte offset of `carry` in the input.
+    offset: usize,
+    words: WordReader,
+    /// An opcode waiting for its argument.
+    opcode: Option<u8>,
+    open_ifs: usize,
+    ready: VecDeque<Instruction>,
+    /// Yielded once the instructions read before it are.
+    error: Option<StreamError>,
+    done: bool,
+}
+
+impl<R: BufRead> StreamParser<ReadChunks<R>> {
+    pub fn from_reader(reader: R) -> Self {
+        Self::new(ReadChunks { reader })
+    }
+}
+
+impl<I> StreamParser<Map<I, fn(I::Item) -> io::Result<I::Item>>>
+where
+    I: Iterator,
+    I::Item: AsRef<[u8]>,
+{
+    /// Reads chunks of text, like the lines of a generated poem.
+    pub fn from_chunks(chunks: impl IntoIterator<IntoIter = I>) -> Self {
+        Self::new(
+            chunks
+                .into_iter()
+                .map(Ok as fn(I::Item) -> io::Result<I::Item>),
+        )
+    }
+}
+
+impl<C, B> StreamParser<C>
+where
+    C: Iterator<Item = io::Result<B>>,
+    B: AsRef<[u8]>,
+{
+    pub fn new(chunks: C) -> Self {
+        Self {
+            chunks,
+            options: ParserOptions::default(),
+            bytes: Vec::new(),
+            carry: String::new(),
+            offset: 0,
+            words: WordReader::default(),
+            opcode: None,
+            open_ifs: 0,
+            ready: VecDeque::new(),
+            error: None,
+            done: false,
+        }
+    }
+
+    pub fn with_options(self, options: ParserOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    /// Reads the words of a chunk, keeping what the next chunk may continue. `None` is the end
+    /// of the input.
+    fn read(&mut self, chunk: Option<&[u8]>) -> Result<(), ParseError> {
+        let text = match chunk {
+            Some(chunk) => {
+                self.bytes.extend_from_slice(chunk);
+                let text = decode(&mut self.bytes);
+                self.carry.push_str(&text);
+                std::mem::take(&mut self.carry)
+            }
+            None => {
+                // an incomplete sequence at the end is invalid
+                let mut text = std::mem::take(&mut self.carry);
+                text.push_str(&String::from_utf8_lossy(&std::mem::take(&mut self.bytes)));
+                text
+            }
+        };
+
+        let mut segments = match self.options.letters() {
+            Letters::Ascii => text
+                .char_indices()
+                .map(|(i, c)| (i, self.options.segment(c)))
+                .collect::<Vec<_>>(),
+            #[cfg(feature = "unicode")]
+            Letters::Unicode => {
+                let mut graphemes = text.grapheme_indices(true).collect::<Vec<_>>();
+                if chunk.is_some() {
+                    if let Some((_, last)) = graphemes.pop() {
+                        self.carry = last.to_string();
+                    }
+                }
+                graphemes
+                    .into_iter()
+                    .map(|(i, grapheme)| {
+                        let c = grapheme.chars().next().unwrap_or(' ');
+                        (i, self.options.segment(c))
+                    })
+                    .collect()
+            }
+        };
+        let length = text.len() - self.carry.len();
+        if chunk.is_none() {
+            segments.push((length, Segment::Break));
+        }
+
+        for (i, segment) in segments {
+            if let Some((_, letters)) = self.words.push(self.offset + i, segment) {
+                for digit in self.options.digits(letters)? {
+                    self.digit(digit)?;
+                }
+            }
+        }
+        self.offset += length;
+
+        if chunk.is_none() {
+            if let Some(opcode) = self.opcode {
+                return Err(ParseError::NeedsArgument(opcode));
+            }
+            if self.open_ifs > 0 {
+                return Err(ParseError::MissingEif);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn digit(&mut self, digit: u8) -> Result<(), ParseError> {
+        let instruction = match self.opcode.take() {
+            Some(opcode) => parser::decode(opcode, Some(digit))?,
+            None if takes_argument(digit) => {
+                self.opcode = Some(digit);
+                return Ok(());
+            }
+            None => parser::decode(digit, None)?,
+        };
+        match instruction {
+            Instruction::IF => self.open_ifs += 1,
+            Instruction::EIF => {
+                self.open_ifs = self.open_ifs.checked_sub(1).ok_or(ParseError::MissingIf)?;
+            }
+            _ => {}
+        }
+
+        self.ready.push_back(instruction);
+        Ok(())
+    }
+}
+
+/// Takes the valid UTF-8 from the start of `bytes`, leaving an incomplete sequence at the end.
+/// Invalid sequences become replacement characters.
+fn decode(bytes: &mut Vec<u8>) -> String {
+    let mut text = String::new();
+    let mut rest = &bytes[..];
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                text.push_str(valid);
+                rest = &[];
+                break;
+            }
+            Err(error) => {
+                let (valid, after) = rest.split_at(error.valid_up_to());
+                text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                match error.error_len() {
+                    Some(length) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        rest = &after[length..];
+                    }
+                    None => {
+                        rest = after;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    *bytes = rest.to_vec();
+    text
+}
+
+impl<C, B> Iterator for StreamParser<C>
+where
+    C: Iterator<Item = io::Result<B>>,
+    B: AsRef<[u8]>,
+{
+    type Item = Result<Instruction, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(instruction) = self.ready.pop_front() {
+                return Some(Ok(instruction));
+            }
+            if let Some(error) = self.error.take() {
+                return Some(Err(error));
+            }
+            if self.done {
+                return None;
+            }
+
+            let result = match self.chunks.next() {
+                Some(Ok(chunk)) => self.read(Some(chunk.as_ref())).map_err(StreamError::from),
+                Some(Err(error)) => Err(StreamError::Io(error)),
+                None => {
+                    self.done = true;
+                    self.read(None).map_err(StreamError::from)
+                }
+            };
+            if let Err(error) = result {
+                self.done = true;
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StreamError, StreamParser};
+    use crate::{
+        instruction::Instruction,
+        parser::{ParseError, Parser, ParserOptions},
+        samples::HELLO_WORLD_SHORTEST,
+    };
+    use std::io::BufReader;
+
+    fn collect<C, B>(parser: StreamParser<C>) -> Result<Vec<Instruction>, StreamError>
+    where
+        C: Iterator<Item = std::io::Result<B>>,
+        B: AsRef<[u8]>,
+    {
+        parser.collect()
+    }
+
+    #[test]
+    fn test_every_chunk_size_matches_parse() {
+        let source = format!("Don't  {}\n(aaaaaaaaaaaa ei)", HELLO_WORLD_SHORTEST);
+        let expected = Parser::parse(&source).unwrap();
+        for size in 1..20 {
+            let chunks = source.as_bytes().chunks(size).collect::<Vec<_>>();
+            let instructions = collect(StreamParser::from_chunks(chunks)).unwrap();
+            assert_eq!(instructions, expected, "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_reader() {
+        // a tiny buffer splits words and the two bytes of "é"
+        let source = "inc é aa outputa";
+        let reader = BufReader::with_capacity(3, source.as_bytes());
+        let instructions = collect(StreamParser::from_reader(reader)).unwrap();
+        assert_eq!(instructions, Parser::parse(source).unwrap());
+        assert_eq!(instructions, vec![Instruction::INC(2), Instruction::OUT]);
+    }
+
+    #[test]
+    fn test_instructions_before_the_end() {
+        // the instructions of the first chunks come before the rest is read
+        let chunks = ["inc a out", "puta a "]
+            .into_iter()
+            .chain(std::iter::repeat("x"));
+        let mut parser = StreamParser::from_chunks(chunks);
+        assert_eq!(parser.next().unwrap().unwrap(), Instruction::INC(1));
+        assert_eq!(parser.next().unwrap().unwrap(), Instruction::OUT);
+        assert_eq!(parser.next().unwrap().unwrap(), Instruction::IF);
+    }
+
+    #[test]
+    fn test_errors() {
+        let errors = [
+            ("inc a ei a", ParseError::MissingIf),
+            ("a inc a", ParseError::MissingEif),
+            ("a ei inc", ParseError::NeedsArgument(3)),
+        ];
+        for (source, error) in errors {
+            let result = collect(StreamParser::from_chunks([source]));
+            assert!(
+                matches!(result, Err(StreamError::Parse(ref e)) if *e == error),
+                "{}",
+                source
+            );
+        }
+
+        let mut parser = StreamParser::from_chunks(["inc a ei"]);
+        assert_eq!(parser.next().unwrap().unwrap(), Instruction::INC(1));
+        assert!(parser.next().unwrap().is_err());
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn test_options() {
+        let options = ParserOptions::new().with_joiners(['-']);
+        let source = "well-known well- known";
+        for size in 1..6 {
+            let chunks = source.as_bytes().chunks(size);
+            let parser = StreamParser::from_chunks(chunks).with_options(options.clone());
+            assert_eq!(
+                collect(parser).unwrap(),
+                Parser::parse_with(source, &options).unwrap()
+            );
+        }
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_unicode_graphemes_across_chunks() {
+        use crate::parser::Letters;
+
+        let options = ParserOptions::new().with_letters(Letters::Unicode);
+        let source = "inc e\u{301}te\u{301} fu\u{308}r outputa";
+        for size in 1..8 {
+            let chunks = source.as_bytes().chunks(size);
+            let parser = StreamParser::from_chunks(chunks).with_options(options.clone());
+            assert_eq!(
+                collect(parser).unwrap(),
+                Parser::parse_with(source, &options).unwrap()
+            );
+        }
+    }
+}
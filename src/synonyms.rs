@@ -0,0 +1,331 @@
+//! Polishes the wording of a poem without touching its program. Only the letter count of a
+//! word matters to the parser, so a word can be replaced by any synonym of the same length.
+//! [`Rewriter`] looks words up in a word list of synonym groups, suggests the ones that fit and
+//! applies replacements after checking each one is a single word of the same length.
+//!
+//! ```
+//! use poetic::{parser::Parser, synonyms::Rewriter};
+//!
+//! let rewriter = Rewriter::new().with_word_list("huge, vast, large\nsmall, tiny");
+//! let source = "Huge minds, small words";
+//! assert_eq!(rewriter.synonyms("Huge"), vec!["Vast"]);
+//! let rewritten = rewriter.rewrite(source, |_| Some(0));
+//! assert_eq!(rewritten, "Vast minds, small words");
+//! assert_eq!(Parser::parse(&rewritten), Parser::parse(source));
+//! ```
+
+use crate::parser::{Parser, ParserOptions};
+use std::{collections::HashMap, fmt::Display, ops::Range};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RewriteError {
+    /// The range is not the span of a word of the poem.
+    NotAWord(Range<usize>),
+    /// The range overlaps the range of an earlier replacement.
+    Overlapping(Range<usize>),
+    /// The replacement is not read as exactly one word.
+    NotOneWord(String),
+    /// The replacement has a different number of letters than the word it replaces.
+    LetterCount {
+        replacement: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Display for RewriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RewriteError::NotAWord(span) => {
+                write!(f, "No word at bytes {}..{}", span.start, span.end)
+            }
+            RewriteError::Overlapping(span) => {
+                write!(f, "Replacements overlap at bytes {}..{}", span.start, span.end)
+            }
+            RewriteError::NotOneWord(replacement) => {
+                write!(f, "\"{}\" is not a single word", replacement)
+            }
+            RewriteError::LetterCount {
+                replacement,
+                expected,
+                found,
+            } => write!(
+                f,
+                "\"{}\" has {} letters instead of {}",
+                replacement, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RewriteError {}
+
+/// A word of a poem and the synonyms it could be replaced with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    /// Byte range of the word in the source.
+    pub span: Range<usize>,
+    pub word: String,
+    /// Synonyms with the same number of letters, in the case of the word.
+    pub synonyms: Vec<String>,
+}
+
+pub struct Rewriter {
+    groups: Vec<Vec<String>>,
+    /// The groups of every lowercase word.
+    index: HashMap<String, Vec<usize>>,
+    options: ParserOptions,
+}
+
+impl Rewriter {
+    /// A rewriter without any synonyms.
+    pub fn new() -> Self {
+        Self {
+            groups: Vec::new(),
+            index: HashMap::new(),
+            options: ParserOptions::default(),
+        }
+    }
+
+    /// The dialect the poems and synonyms are written in.
+    pub fn with_parser_options(self, options: ParserOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    /// Adds a group of words that can replace each other.
+    pub fn with_synonyms(mut self, words: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let group = words
+            .into_iter()
+            .map(|word| word.as_ref().trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>();
+        for word in &group {
+            let groups = self.index.entry(word.clone()).or_default();
+            if groups.last() != Some(&self.groups.len()) {
+                groups.push(self.groups.len());
+            }
+        }
+        self.groups.push(group);
+        self
+    }
+
+    /// Adds a word list with a group of comma separated synonyms on every line. Empty lines and
+    /// lines starting with `#` are skipped.
+    pub fn with_word_list(self, list: &str) -> Self {
+        list.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .fold(self, |rewriter, line| rewriter.with_synonyms(line.split(',')))
+    }
+
+    /// The synonyms of the word with as many letters as it has, in its case.
+    pub fn synonyms(&self, word: &str) -> Vec<String> {
+        let Some(letters) = self.letters(word) else {
+            return Vec::new();
+        };
+        let key = word.to_lowercase();
+        let mut synonyms: Vec<String> = Vec::new();
+        for group in self.index.get(&key).into_iter().flatten() {
+            for synonym in &self.groups[*group] {
+                let synonym = match_case(word, synonym);
+                if synonym.to_lowercase() != key
+                    && self.letters(&synonym) == Some(letters)
+                    && !synonyms.contains(&synonym)
+                {
+                    synonyms.push(synonym);
+                }
+            }
+        }
+
+        synonyms
+    }
+
+    /// Every word of the poem that has synonyms of the same length.
+    pub fn suggest(&self, source: &str) -> Vec<Suggestion> {
+        Parser::word_spans(source, &self.options)
+            .into_iter()
+            .filter_map(|(span, _)| {
+                let word = &source[span.clone()];
+                let synonyms = self.synonyms(word);
+                (!synonyms.is_empty()).then(|| Suggestion {
+                    span,
+                    word: word.to_string(),
+                    synonyms,
+                })
+            })
+            .collect()
+    }
+
+    /// Replaces words with the synonym at the index `choose` returns for their suggestion,
+    /// `None` keeps the word.
+    pub fn rewrite(
+        &self,
+        source: &str,
+        mut choose: impl FnMut(&Suggestion) -> Option<usize>,
+    ) -> String {
+        let replacements = self
+            .suggest(source)
+            .into_iter()
+            .filter_map(|suggestion| {
+                let synonym = suggestion.synonyms.get(choose(&suggestion)?)?.clone();
+                Some((suggestion.span, synonym))
+            })
+            .collect::<Vec<_>>();
+        // suggestions are whole words with synonyms of the same length
+        self.apply(source, &replacements)
+            .unwrap_or_else(|_| source.to_string())
+    }
+
+    /// Replaces the words at the byte ranges, as long as every replacement is read as one word
+    /// with the same number of letters, so the instructions stay the same.
+    pub fn apply(
+        &self,
+        source: &str,
+        replacements: &[(Range<usize>, String)],
+    ) -> Result<String, RewriteError> {
+        let words = Parser::word_spans(source, &self.options);
+        let mut replacements = replacements.iter().collect::<Vec<_>>();
+        replacements.sort_by_key(|(span, _)| span.start);
+
+        let mut result = String::new();
+        let mut position = 0;
+        for (span, replacement) in replacements {
+            if span.start < position {
+                return Err(RewriteError::Overlapping(span.clone()));
+            }
+            let expected = words
+                .binary_search_by_key(&span.start, |(word, _)| word.start)
+                .ok()
+                .filter(|i| words[*i].0 == *span)
+                .map(|i| words[i].1)
+                .ok_or_else(|| RewriteError::NotAWord(span.clone()))?;
+            let found = self
+                .letters(replacement)
+                .ok_or_else(|| RewriteError::NotOneWord(replacement.clone()))?;
+            if found != expected {
+                return Err(RewriteError::LetterCount {
+                    replacement: replacement.clone(),
+                    expected,
+                    found,
+                });
+            }
+
+            result.push_str(&source[position..span.start]);
+            result.push_str(replacement);
+            position = span.end;
+        }
+        result.push_str(&source[position..]);
+
+        Ok(result)
+    }
+
+    /// The letter count of `word` if it is read as exactly one word.
+    fn letters(&self, word: &str) -> Option<usize> {
+        match Parser::word_spans(word, &self.options).as_slice() {
+            [(span, letters)] if *span == (0..word.len()) => Some(*letters),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Rewriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The synonym in the case of the word: all uppercase, capitalized or as listed.
+fn match_case(word: &str, synonym: &str) -> String {
+    let mut letters = word.chars().filter(|c| c.is_alphabetic());
+    let first_upper = letters.next().is_some_and(char::is_uppercase);
+    let all_upper = first_upper && letters.clone().count() > 0 && letters.all(char::is_uppercase);
+
+    if all_upper {
+        synonym.to_uppercase()
+    } else if first_upper {
+        let mut chars = synonym.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        synonym.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RewriteError, Rewriter};
+    use crate::parser::{Parser, ParserOptions};
+
+    const WORDS: &str = "# adjectives\nbig, fat, large, great, huge, vast\nsmall, tiny, wee\n\nquick, swift, rapid, fast\ndon't, do not, mustn't";
+
+    #[test]
+    fn test_synonyms() {
+        let rewriter = Rewriter::new().with_word_list(WORDS);
+        assert_eq!(rewriter.synonyms("big"), vec!["fat"]);
+        assert_eq!(rewriter.synonyms("Large"), vec!["Great"]);
+        assert_eq!(rewriter.synonyms("QUICK"), vec!["SWIFT", "RAPID"]);
+        assert_eq!(rewriter.synonyms("Small"), Vec::<String>::new());
+        // "do not" is two words and "mustn't" has 6 letters
+        assert_eq!(rewriter.synonyms("don't"), Vec::<String>::new());
+        assert_eq!(rewriter.synonyms("unknown"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_suggest_and_rewrite() {
+        let rewriter = Rewriter::new().with_word_list(WORDS);
+        let source = "A quick, big fox";
+        let suggestions = rewriter.suggest(source);
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].span, 2..7);
+        assert_eq!(suggestions[0].synonyms, vec!["swift", "rapid"]);
+
+        let rewritten = rewriter.rewrite(source, |suggestion| {
+            (suggestion.word == "quick").then_some(1)
+        });
+        assert_eq!(rewritten, "A rapid, big fox");
+        assert_eq!(
+            Parser::parse_intermediate(&rewritten),
+            Parser::parse_intermediate(source)
+        );
+    }
+
+    #[test]
+    fn test_apply_checks_replacements() {
+        let rewriter = Rewriter::new();
+        let source = "inc aaa, outputa";
+        assert_eq!(
+            rewriter.apply(source, &[(4..7, "cat".to_string()), (0..3, "Inc".to_string())]),
+            Ok("Inc cat, outputa".to_string())
+        );
+        assert_eq!(
+            rewriter.apply(source, &[(4..8, "cats".to_string())]),
+            Err(RewriteError::NotAWord(4..8))
+        );
+        assert_eq!(
+            rewriter.apply(source, &[(4..7, "a b".to_string())]),
+            Err(RewriteError::NotOneWord("a b".to_string()))
+        );
+        assert_eq!(
+            rewriter.apply(source, &[(4..7, "cats".to_string())]),
+            Err(RewriteError::LetterCount {
+                replacement: "cats".to_string(),
+                expected: 3,
+                found: 4
+            })
+        );
+        assert_eq!(
+            rewriter.apply(source, &[(4..7, "cat".to_string()), (4..7, "dog".to_string())]),
+            Err(RewriteError::Overlapping(4..7))
+        );
+    }
+
+    #[test]
+    fn test_parser_options() {
+        let rewriter = Rewriter::new()
+            .with_parser_options(ParserOptions::new().with_joiners(['-']))
+            .with_synonyms(["well-known", "notorious", "famous"]);
+        assert_eq!(rewriter.synonyms("well-known"), vec!["notorious"]);
+    }
+}
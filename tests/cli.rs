@@ -0,0 +1,231 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+const HELLO_WORLD: &str = "inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa outputa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aa outputa inc aaaaaaa outputa outputa inc aaa outputa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaa outputa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaaaaa inc a outputa inc aaaaaaaaa inc aaaaaaaaa inc aaaaaa outputa inc aaa outputa decc aaaaaa outputa decc aaaaaaaa outputa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaaaaaaa decc aaaa outputa endprogram";
+
+const HELLO_WORLD_SHORTEST: &str = "inc a a decc aa fwdfw a decc a a fwdfw aa inc a fwdfw a decc aaaaa bakbak aa ei bakbak a decc aa bakbak a decc aaa ei fwdfw a decc a outputa fwdfw aaa inc a outputa fwdfw aa outputa outputa inc aaa a outputa fwdfw a ei bakbak aaaa outputa inc a inc aa outputa decc aaaaaa outputa bakbak aa decc a outputa fwdfw aaaa inc a outputa";
+
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("poetic-cli-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn poetic(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_poetic"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn run_hello_world() {
+    let file = temp_file("hello.ptc", HELLO_WORLD.as_bytes());
+    let output = poetic(&["run", file.to_str().unwrap()], b"");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "Hello World!");
+
+    let file = temp_file("shortest.ptc", HELLO_WORLD_SHORTEST.as_bytes());
+    let output = poetic(
+        &["run", file.to_str().unwrap(), "--memory-size", "100", "-O"],
+        b"",
+    );
+    assert_eq!(stdout(&output), "Hello, World!");
+}
+
+#[test]
+fn run_eof_policy_and_seed() {
+    // INC 1, IN, OUT
+    let file = temp_file("eof.ptc", b"inc a aaaaaaaa aaaaaaa");
+    let path = file.to_str().unwrap();
+    assert_eq!(stdout(&poetic(&["run", path], b"")), "\u{1}");
+    assert_eq!(stdout(&poetic(&["run", path, "--eof", "zero"], b"")), "\0");
+    assert_eq!(stdout(&poetic(&["run", path], b"A")), "A");
+
+    // RND, OUT
+    let file = temp_file("random.ptc", b"aaaaaaaaa aaaaaaa");
+    let path = file.to_str().unwrap();
+    let first = poetic(&["run", path, "--seed", "42"], b"");
+    let second = poetic(&["run", path, "--seed", "42"], b"");
+    assert_eq!(first.stdout, second.stdout);
+}
+
+#[test]
+fn run_cell_width() {
+    let file = temp_file("width.ptc", b"inc aaa outputa");
+    let path = file.to_str().unwrap();
+    assert_eq!(stdout(&poetic(&["run", path, "--cell-width", "8"], b"")), "\u{3}");
+
+    let output = poetic(&["run", path, "--cell-width", "16"], b"");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unsupported cell width 16"));
+}
+
+#[test]
+fn run_step_limit() {
+    // IF, EIF loops forever on a non zero cell
+    let file = temp_file("forever.ptc", b"inc a a ei");
+    let output = poetic(&["trace", file.to_str().unwrap(), "--step-limit", "5"], b"");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().count(), 6);
+    assert!(stderr.ends_with("error: step limit of 5 reached\n"));
+}
+
+#[test]
+fn check_reports_position() {
+    let file = temp_file("unmatched.ptc", b"inc a\n a");
+    let path = file.to_str().unwrap();
+    let output = poetic(&["check", path], b"");
+    assert!(!output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!("{}:2:2: error: Missing EIF at \"a\"\n", path)
+    );
+}
+
+#[test]
+fn opt_asm_disasm_round_trip() {
+    let file = temp_file("opt.ptc", HELLO_WORLD_SHORTEST.as_bytes());
+    let bytecode = file.with_extension("ptcb");
+    let output = poetic(
+        &[
+            "opt",
+            file.to_str().unwrap(),
+            "-o",
+            bytecode.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+
+    let asm = stdout(&poetic(&["disasm", bytecode.to_str().unwrap()], b""));
+    assert!(asm.starts_with("; memory: dynamic\nINC 1\nJIZ "));
+    let asm_file = temp_file("opt.asm", asm.as_bytes());
+    let assembled = file.with_extension("asm.ptcb");
+    let output = poetic(
+        &[
+            "asm",
+            asm_file.to_str().unwrap(),
+            "-o",
+            assembled.to_str().unwrap(),
+            "--memory-size",
+            "100",
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+
+    let output = poetic(&["run", assembled.to_str().unwrap()], b"");
+    assert_eq!(stdout(&output), "Hello, World!");
+}
+
+#[test]
+fn compile_to_brainfuck() {
+    let file = temp_file("bf.ptc", b"inc aaa outputa");
+    let output = poetic(&["compile", file.to_str().unwrap(), "--target", "bf"], b"");
+    assert_eq!(stdout(&output), "+++.");
+}
+
+#[test]
+fn repl_keeps_tape() {
+    let output = poetic(
+        &["repl"],
+        b"inc aaaaaaaaa inc aaaaaaaa\ninc a outputa\ninc a a\ndecc a\nei\n:memory 1\n:quit\n",
+    );
+    assert!(output.status.success());
+    let stdout = stdout(&output);
+    assert!(stdout.contains("> \u{12}\n"));
+    assert!(stdout.contains("... ... > 0: [0] 0\n"));
+}
+
+#[test]
+fn annotate_poem() {
+    let file = temp_file("annotate.ptc", b"inc aaaaaaaaaaaa fwdfw\n");
+    let output = poetic(&["annotate", file.to_str().unwrap()], b"");
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "inc   aaaaaaaaaaaa fwdfw\n3:INC 12=1,2:EIF   5:FWD?\n"
+    );
+}
+
+#[test]
+fn format_poem_in_place() {
+    let file = temp_file("format.ptc", b"inc  aa a\noutputa ,ei\n\n\n");
+    let path = file.to_str().unwrap();
+    let output = poetic(&["format", path, "--width", "10", "--indent", "2"], b"");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "inc aa a\n  outputa,\nei\n");
+
+    let output = poetic(&["format", path, "--write"], b"");
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "inc aa a outputa, ei\n"
+    );
+}
+
+#[test]
+fn synonyms_keep_instructions() {
+    let file = temp_file("synonyms.ptc", b"Inc a\nquick outputa");
+    let words = temp_file("synonyms.txt", b"quick, swift, fast\ninc, add");
+    let (path, words) = (file.to_str().unwrap(), words.to_str().unwrap());
+    let output = poetic(&["synonyms", path, "--words", words], b"");
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{0}:1:1: \"Inc\" could be Add\n{0}:2:1: \"quick\" could be swift\n",
+            path
+        )
+    );
+
+    let output = poetic(&["synonyms", path, "--words", words, "--apply"], b"");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "Add a\nswift outputa");
+}
+
+#[test]
+fn lint_reports_warnings() {
+    let file = temp_file("lint.ptc", b"inc aaa\ndecc aaa outputa endprogram outputa");
+    let path = file.to_str().unwrap();
+    let output = poetic(&["lint", path], b"");
+    assert!(!output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{0}:1:1: warning[cancelling-pair]: INC 3 and DEC 3 cancel out at \"inc aaa\ndecc aaa\"\n\
+             {0}:2:29: warning[unreachable]: Instructions after END never run at \"outputa\"\n",
+            path
+        )
+    );
+
+    let output = poetic(
+        &[
+            "lint",
+            path,
+            "-A",
+            "unreachable",
+            "--allow",
+            "cancelling-pair",
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+}